@@ -463,8 +463,8 @@ fn add_bench_section_size(scenarios: &mut Scenarios) {
         let options = ScheduleOptions {
             genesis_size: *genesis_size,
             opaque_to_add,
-            // 1 gossip event every 10 steps in one peer in the network
-            prob_gossip: 0.1 / *genesis_size as f64,
+            // 1 gossip event every 100ms (10 steps) in one peer in the network
+            gossip_period_ms: 100 * *genesis_size as u64,
             // 1 opaque event per 10 steps
             prob_opaque: 0.1,
             // Events will be seen within 2 gossip events
@@ -490,8 +490,8 @@ fn add_bench_section_size(scenarios: &mut Scenarios) {
         let options = ScheduleOptions {
             genesis_size: *genesis_size,
             opaque_to_add,
-            // 1 gossip event every 80 steps in one peer in the network
-            prob_gossip: 0.1 / (*genesis_size as f64 * 8.0),
+            // 1 gossip event every 800ms (80 steps) in one peer in the network
+            gossip_period_ms: 800 * *genesis_size as u64,
             // 1 opaque event per 10 steps
             prob_opaque: 0.1,
             // Events will be seen within 2 gossip events
@@ -511,8 +511,8 @@ fn add_bench_section_size(scenarios: &mut Scenarios) {
         let options = ScheduleOptions {
             genesis_size: *genesis_size,
             opaque_to_add,
-            // 1 gossip event every 640 steps in one peer in the network
-            prob_gossip: 0.1 / (*genesis_size as f64 * 64.0),
+            // 1 gossip event every 6400ms (640 steps) in one peer in the network
+            gossip_period_ms: 6400 * *genesis_size as u64,
             // 1 opaque event per 10 steps
             prob_opaque: 0.1,
             // Events will be seen within 2 gossip events
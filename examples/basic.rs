@@ -112,7 +112,7 @@ impl Peer {
     fn from_genesis(
         our_id: PeerId,
         genesis_group: &BTreeSet<PeerId>,
-        secure_rng: Box<dyn Rng>,
+        secure_rng: Box<dyn Rng + Send>,
     ) -> Self {
         Self {
             id: our_id.clone(),
@@ -132,7 +132,7 @@ impl Peer {
         our_id: PeerId,
         genesis_group: &BTreeSet<PeerId>,
         section: &BTreeSet<PeerId>,
-        secure_rng: Box<dyn Rng>,
+        secure_rng: Box<dyn Rng + Send>,
     ) -> Self {
         Self {
             id: our_id.clone(),
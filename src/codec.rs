@@ -0,0 +1,119 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Encodings used to put gossip messages on the wire.
+//!
+//! Hashing and signing always use the canonical encoding (see `serialise`), which is not pluggable.
+//! A `Codec` only affects how messages travel between peers.  Unless it is the built-in `Bincode`
+//! codec, which produces the canonical encoding, the codec encodes the whole message, events
+//! included, and the message carries a digest of its canonical encoding which is checked on decode.
+//! This way a codec which doesn't faithfully round-trip every message is reported as an
+//! `Error::CodecMismatch` instead of as invalid signatures or malice by the sender.
+
+use crate::{
+    error::Error,
+    hash::{Hash, HashDomain, Hasher},
+    serialise,
+};
+use maidsafe_utilities::serialisation;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{any::TypeId, fmt::Debug};
+
+/// Encoding of gossip messages on the wire.
+pub trait Codec: 'static {
+    /// Error returned when encoding or decoding fails.
+    type Error: Debug;
+
+    /// Encodes `message` into bytes.
+    fn encode<M: Serialize>(message: &M) -> Result<Vec<u8>, Self::Error>;
+
+    /// Decodes a message from bytes produced by `encode`.
+    ///
+    /// The header of a gossip message is decoded on its own before the rest of it, so this must
+    /// also succeed in decoding a struct from the encoding of a struct starting with the same
+    /// fields followed by more, as self-describing formats like JSON do.
+    fn decode<M: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<M, Self::Error>;
+}
+
+/// The default codec: bincode, which is also the canonical encoding.
+#[derive(Clone, Copy, Debug)]
+pub struct Bincode;
+
+impl Codec for Bincode {
+    type Error = serialisation::SerialisationError;
+
+    fn encode<M: Serialize>(message: &M) -> Result<Vec<u8>, Self::Error> {
+        serialisation::serialise(message)
+    }
+
+    fn decode<M: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<M, Self::Error> {
+        serialisation::deserialise(bytes)
+    }
+}
+
+/// Self-describing JSON codec, for interoperability with peers not written in Rust.
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    type Error = serde_json::Error;
+
+    fn encode<M: Serialize>(message: &M) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(message)
+    }
+
+    fn decode<M: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<M, Self::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+// Whether `C` produces exactly the canonical encoding, so that the digest check can be skipped.
+// This is decided here rather than by the codec, so that no other codec can opt out of the check.
+pub(crate) fn is_canonical<C: Codec>() -> bool {
+    TypeId::of::<C>() == TypeId::of::<Bincode>()
+}
+
+pub(crate) fn encode<C: Codec, M: Serialize>(message: &M) -> Result<Vec<u8>, Error> {
+    C::encode(message).map_err(|error| {
+        debug!("Failed to encode message: {:?}", error);
+        Error::CodecFailure
+    })
+}
+
+pub(crate) fn decode<C: Codec, M: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<M, Error> {
+    C::decode(bytes).map_err(|error| {
+        debug!("Failed to decode message: {:?}", error);
+        Error::CodecFailure
+    })
+}
+
+// Digest of the canonical encoding of `message`, sent along with messages encoded with any other
+// codec.
+pub(crate) fn digest<M: Serialize + Debug>(hasher: Hasher, message: &M) -> Hash {
+    hasher.hash(HashDomain::Message, &serialise(message))
+}
+
+// Checks that `message`, as decoded with a non-canonical codec, has the canonical encoding the
+// sender computed `expected_digest` over.
+pub(crate) fn check_digest<M: Serialize + Debug>(
+    hasher: Hasher,
+    message: &M,
+    expected_digest: &Hash,
+) -> Result<(), Error> {
+    if digest(hasher, message) == *expected_digest {
+        Ok(())
+    } else {
+        warn!(
+            "Decoded message doesn't match its canonical encoding: {:?}",
+            message
+        );
+        Err(Error::CodecMismatch)
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    gossip::EventHash,
+    id::{PublicId, SecretId},
+    meta_voting::{BoolSet, MetaVote, Step},
+    peer_list::{Peer, PeerIndex, PeerIndexMap, PeerIndexSet, PeerList},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+/// Explanation of how the owning peer decided a group of consensused blocks, intended to be
+/// attached to incident reports.  Returned by
+/// [Parsec::explain_consensus](struct.Parsec.html#method.explain_consensus).
+///
+/// All the blocks decided by the same meta-election share the same trace.
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ConsensusTrace<P: PublicId> {
+    /// Heights of the blocks decided by the meta-election, internal ones included.
+    pub heights: Range<usize>,
+    /// Hash of the event whose meta votes decided the meta-election, or `None` if there was no
+    /// need for meta-voting because we were the only voter.
+    pub decided_by: Option<EventHash>,
+    /// First observer event created by each voter during the meta-election, along with its
+    /// observees, as known when the meta-election was decided.
+    pub observers: BTreeMap<P, ObserverTrace<P>>,
+    /// Progression of the meta votes about each voter, as held by the deciding event.  The last
+    /// meta vote of each progression carries its decision.
+    pub meta_votes: BTreeMap<P, Vec<MetaVoteTrace>>,
+    /// Coin tosses used by the meta votes about each voter, indexed by round.
+    pub coin_tosses: BTreeMap<P, BTreeMap<usize, bool>>,
+}
+
+/// Observer event, as part of a [ConsensusTrace](struct.ConsensusTrace.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ObserverTrace<P: PublicId> {
+    /// Hash of the observer event.
+    pub event: EventHash,
+    /// Voters whose interesting events the observer event strongly sees.
+    pub observees: BTreeSet<P>,
+}
+
+/// State of a single meta vote, as part of a [ConsensusTrace](struct.ConsensusTrace.html).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MetaVoteTrace {
+    /// Round of the meta vote.
+    pub round: usize,
+    /// Step of the meta vote within its round.
+    pub step: MetaVoteStep,
+    /// Estimates of the meta vote.
+    pub estimates: BTreeSet<bool>,
+    /// Binary values of the meta vote.
+    pub bin_values: BTreeSet<bool>,
+    /// Auxiliary value of the meta vote, if set.
+    pub aux_value: Option<bool>,
+    /// Decision of the meta vote, if reached.
+    pub decision: Option<bool>,
+}
+
+/// Step of a meta vote within its round.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MetaVoteStep {
+    /// The coin is forced to `true`.
+    ForcedTrue,
+    /// The coin is forced to `false`.
+    ForcedFalse,
+    /// The coin is tossed.
+    GenuineFlip,
+}
+
+// What we need to record when a meta-election is decided in order to produce its
+// `ConsensusTrace` later on.  Peers and events are never removed, so it's safe to refer to them by
+// index and hash.
+pub(crate) struct DecisionRecord {
+    pub heights: Range<usize>,
+    pub decided_by: Option<EventHash>,
+    pub observers: PeerIndexMap<(EventHash, PeerIndexSet)>,
+    pub meta_votes: PeerIndexMap<Vec<MetaVote>>,
+    pub coin_tosses: PeerIndexMap<BTreeMap<usize, bool>>,
+}
+
+impl DecisionRecord {
+    pub fn to_trace<S: SecretId>(&self, peer_list: &PeerList<S>) -> ConsensusTrace<S::PublicId> {
+        let peer_id = |peer_index: PeerIndex| peer_list.get(peer_index).map(Peer::id).cloned();
+
+        ConsensusTrace {
+            heights: self.heights.clone(),
+            decided_by: self.decided_by,
+            observers: self
+                .observers
+                .iter()
+                .filter_map(|(peer_index, (event, observees))| {
+                    let observer = ObserverTrace {
+                        event: *event,
+                        observees: observees.iter().filter_map(peer_id).collect(),
+                    };
+                    Some((peer_id(peer_index)?, observer))
+                })
+                .collect(),
+            meta_votes: self
+                .meta_votes
+                .iter()
+                .filter_map(|(peer_index, meta_votes)| {
+                    let meta_votes = meta_votes.iter().map(MetaVoteTrace::new).collect();
+                    Some((peer_id(peer_index)?, meta_votes))
+                })
+                .collect(),
+            coin_tosses: self
+                .coin_tosses
+                .iter()
+                .filter_map(|(peer_index, coin_tosses)| {
+                    Some((peer_id(peer_index)?, coin_tosses.clone()))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MetaVoteTrace {
+    pub(crate) fn new(meta_vote: &MetaVote) -> Self {
+        MetaVoteTrace {
+            round: meta_vote.round,
+            step: match meta_vote.step {
+                Step::ForcedTrue => MetaVoteStep::ForcedTrue,
+                Step::ForcedFalse => MetaVoteStep::ForcedFalse,
+                Step::GenuineFlip => MetaVoteStep::GenuineFlip,
+            },
+            estimates: bool_set(meta_vote.values.estimates()),
+            bin_values: bool_set(meta_vote.values.bin_values()),
+            aux_value: meta_vote.values.aux_value(),
+            decision: meta_vote.decision(),
+        }
+    }
+}
+
+fn bool_set(input: BoolSet) -> BTreeSet<bool> {
+    [false, true]
+        .iter()
+        .cloned()
+        .filter(|value| input.contains(*value))
+        .collect()
+}
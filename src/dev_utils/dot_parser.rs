@@ -11,8 +11,8 @@ use crate::error::Error;
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 use crate::gossip::EventContextRef;
 use crate::{
-    gossip::{CauseInput, Event, EventIndex, Graph, IndexedEventRef},
-    hash::{Hash, HASH_LEN},
+    gossip::{CauseInput, Event, EventIndex, Graph, IndexedEventRef, ProtocolVersion},
+    hash::{Hash, Hasher, HASH_LEN},
     maidsafe_utilities::serialisation::deserialise,
     meta_voting::{
         BoolSet, MetaElection, MetaEvent, MetaVote, Observer, Step, UnconsensusedEvents,
@@ -542,6 +542,7 @@ fn parse_observation() -> Parser<u8, Observation<Transaction, PeerId>> {
         | parse_opaque()
         | parse_start_dkg()
         | parse_dkg_msg()
+        | parse_upgrade()
 }
 
 fn parse_accusation() -> Parser<u8, (PeerId, MaliceInput)> {
@@ -639,6 +640,19 @@ fn parse_dkg_msg() -> Parser<u8, Observation<Transaction, PeerId>> {
     .map(Observation::DkgMessage)
 }
 
+fn parse_upgrade() -> Parser<u8, Observation<Transaction, PeerId>> {
+    (seq(b"Upgrade(")
+        * is_a(digit)
+            .repeat(1..)
+            .convert(String::from_utf8)
+            .convert(|s| u32::from_str(&s))
+        - seq(b")"))
+    .map(|version| Observation::Upgrade {
+        version: ProtocolVersion(version),
+        related_info: vec![],
+    })
+}
+
 fn parse_transaction() -> Parser<u8, String> {
     is_a(alphanum).repeat(1..).convert(String::from_utf8)
 }
@@ -694,7 +708,10 @@ fn parse_interesting_content() -> Parser<u8, ObservationMap> {
             .into_iter()
             .map(|payload| {
                 (
-                    ObservationKey::Supermajority(ObservationHash::from(&payload)),
+                    ObservationKey::Supermajority(ObservationHash::new(
+                        Hasher::default(),
+                        &payload,
+                    )),
                     payload,
                 )
             })
@@ -825,6 +842,7 @@ impl ParsedContents {
             peer_list: &self.peer_list,
             observations: &self.observations,
             consensus_mode: self.consensus_mode,
+            hasher: Hasher::default(),
         }
     }
 
@@ -1280,7 +1298,7 @@ mod tests {
         let options = ScheduleOptions {
             genesis_size: 4,
             opaque_to_add: 5,
-            prob_gossip: 0.1,
+            gossip_period_ms: 100,
             ..Default::default()
         };
         let schedule = Schedule::new(&mut env, &options);
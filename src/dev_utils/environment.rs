@@ -9,10 +9,11 @@
 use crate::{
     dev_utils::{
         network::{ConsensusError, Network},
-        new_common_rng, RngChoice, RngDebug, Schedule,
+        new_common_rng, PerformanceReport, RngChoice, RngDebug, Schedule,
     },
     observation::ConsensusMode,
 };
+use rand::{SeedableRng, XorShiftRng};
 use std::fmt;
 
 pub struct Environment {
@@ -40,10 +41,29 @@ impl Environment {
         Self::with_consensus_mode(seed, ConsensusMode::Supermajority)
     }
 
-    pub fn execute_schedule(&mut self, schedule: Schedule) -> Result<(), ConsensusError> {
+    /// Executes the schedule with RNGs seeded from it, so that executing the same schedule again,
+    /// e.g. once loaded from a file, reproduces the same run.
+    pub fn execute_schedule(
+        &mut self,
+        schedule: Schedule,
+    ) -> Result<PerformanceReport, ConsensusError> {
+        self.reseed(&schedule);
         self.network
             .execute_schedule(&mut self.rng, &mut self.rng2, schedule)
     }
+
+    /// Executes the schedule like `execute_schedule`, but only checks that the blocks consensused
+    /// so far are consistent.
+    pub fn execute_schedule_prefix(&mut self, schedule: Schedule) -> Result<(), ConsensusError> {
+        self.reseed(&schedule);
+        self.network
+            .execute_schedule_prefix(&mut self.rng, &mut self.rng2, schedule)
+    }
+
+    fn reseed(&mut self, schedule: &Schedule) {
+        self.rng = Box::new(XorShiftRng::from_seed(schedule.seed));
+        self.rng2 = Box::new(XorShiftRng::from_seed(schedule.seed));
+    }
 }
 
 impl fmt::Debug for Environment {
@@ -0,0 +1,148 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{ConsensusError, Environment, Network, Schedule};
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Bounds of the search performed by `check_interleavings`.
+#[derive(Clone, Debug)]
+pub struct InterleavingBounds {
+    /// Maximum number of `Gossip` and `Deliver` events added to the schedule
+    pub max_depth: usize,
+    /// Maximum number of messages in flight before a peer is allowed to gossip again
+    pub max_messages_in_flight: usize,
+}
+
+impl Default for InterleavingBounds {
+    fn default() -> Self {
+        InterleavingBounds {
+            max_depth: 6,
+            max_messages_in_flight: 2,
+        }
+    }
+}
+
+/// Systematically explores the executions of a tiny network: extends `schedule`, typically
+/// created by `Schedule::from_votes`, with every possible sequence of `Gossip` and `Deliver`
+/// events within the given bounds, i.e. every choice of gossip partners and every order of
+/// delivery of the messages in flight, checking after each event that the blocks consensused so
+/// far are consistent.
+///
+/// Every execution uses a fresh environment created by `new_env`, and is seeded from the
+/// schedule.  The additional steps of `schedule` are dropped and intermediate consistency checks
+/// enabled.  As the number of interleavings grows exponentially with the depth, this is only
+/// practical for 3 or 4 peers and a couple of observations.
+///
+/// Returns the number of interleavings checked, or the first counterexample found, along with its
+/// error.  The counterexample can be replayed by passing it to
+/// `Environment::execute_schedule_prefix` in an environment created by `new_env`.  An execution
+/// in which a peer panics, e.g. because it fails to handle a message, is a counterexample failing
+/// with `ConsensusError::Panicked`.
+pub fn check_interleavings<F>(
+    schedule: Schedule,
+    bounds: &InterleavingBounds,
+    new_env: F,
+) -> Result<usize, (Schedule, ConsensusError)>
+where
+    F: FnMut() -> Environment,
+{
+    check_interleavings_with(schedule, bounds, new_env, |_| Ok(()))
+}
+
+/// Same as `check_interleavings`, but also checks `property` against the network after each event.
+/// A counterexample found this way fails `property` once replayed by
+/// `Environment::execute_schedule_prefix`.
+pub fn check_interleavings_with<F, P>(
+    schedule: Schedule,
+    bounds: &InterleavingBounds,
+    mut new_env: F,
+    mut property: P,
+) -> Result<usize, (Schedule, ConsensusError)>
+where
+    F: FnMut() -> Environment,
+    P: FnMut(&Network) -> Result<(), ConsensusError>,
+{
+    let mut schedule = schedule;
+    schedule.additional_steps = 0..0;
+    schedule.options.intermediate_consistency_checks = true;
+
+    let max_len = schedule.events.len() + bounds.max_depth;
+    let mut num_interleavings = 0;
+    explore(
+        &mut schedule,
+        max_len,
+        bounds,
+        &mut new_env,
+        &mut property,
+        &mut num_interleavings,
+    )?;
+    Ok(num_interleavings)
+}
+
+// Checks the schedule, then each of its extensions by one event in turn, depth first.
+fn explore<F, P>(
+    schedule: &mut Schedule,
+    max_len: usize,
+    bounds: &InterleavingBounds,
+    new_env: &mut F,
+    property: &mut P,
+    num_interleavings: &mut usize,
+) -> Result<(), (Schedule, ConsensusError)>
+where
+    F: FnMut() -> Environment,
+    P: FnMut(&Network) -> Result<(), ConsensusError>,
+{
+    let mut env = new_env();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        env.execute_schedule_prefix(schedule.clone())
+            .and_then(|()| property(&env.network))
+    }))
+    .unwrap_or_else(|payload| Err(ConsensusError::Panicked(panic_message(payload))));
+    if let Err(error) = result {
+        return Err((schedule.clone(), error));
+    }
+
+    let choices = if schedule.events.len() < max_len {
+        env.network
+            .interleaving_choices(bounds.max_messages_in_flight)
+    } else {
+        vec![]
+    };
+    if choices.is_empty() {
+        *num_interleavings += 1;
+        return Ok(());
+    }
+
+    for choice in choices {
+        schedule.events.push(choice);
+        explore(
+            schedule,
+            max_len,
+            bounds,
+            new_env,
+            property,
+            num_interleavings,
+        )?;
+        let _ = schedule.events.pop();
+    }
+    Ok(())
+}
+
+// Returns the message a panic was raised with.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
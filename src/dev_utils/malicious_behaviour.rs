@@ -0,0 +1,584 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Observation;
+use crate::{
+    error::{Error, Result},
+    gossip::{Event, EventIndex, PackedEvent, Request, Response},
+    mock::{PeerId, Transaction},
+    observation::{Malice, Observation as ParsecObservation},
+    parsec::TestParsec,
+    peer_list::PeerIndex,
+};
+use itertools::Itertools;
+use rand::Rng;
+use std::{collections::BTreeSet, iter};
+
+type TestParsecImpl = TestParsec<Transaction, PeerId>;
+
+/// Number of copies of each gossip request sent by a spamming peer.
+const SPAM_COPIES: usize = 10;
+
+/// Kinds of misbehaviour a malicious test peer can be configured with via `ScheduleOptions`.
+///
+/// Each malicious peer behaves well until its first attempt to gossip, at which point it
+/// misbehaves as described below.  Except for `WithholdEvents` and `Spam`, it then ignores all
+/// incoming messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MaliciousBehaviourType {
+    /// Creates a `Requesting` event to a random peer, and a forked one to the recipient.
+    Fork,
+    /// Votes for two different observations in two events with the same self-parent, sending
+    /// one to the recipient and keeping the other one for everyone else.
+    EquivocatingVote,
+    /// Votes for the same observation twice.
+    DuplicateVote,
+    /// Never sends anything to a random half of its peers, and ignores their requests.
+    WithholdEvents,
+    /// Falsely accuses the recipient of having forked.
+    InvalidAccusation,
+    /// Sends gossip to peers which it isn't allowed to gossip to yet.
+    PrematureGossip,
+    /// Sends several copies of each gossip request.
+    Spam,
+    /// Sends the recipient a request whose `Requesting` event names another peer.
+    ForgedRecipient,
+}
+
+/// How honest peers are expected to react to a malicious behaviour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Detection {
+    /// Honest peers accuse the malicious peer and the accusation gets consensused.
+    Accusation,
+    /// Honest peers reject the malicious peer's messages.
+    Rejection,
+    /// Parsec doesn't detect this behaviour, so we only check it doesn't prevent consensus.
+    None,
+}
+
+impl MaliciousBehaviourType {
+    /// How honest peers are expected to react once a peer has misbehaved this way.  Apart from
+    /// the rejection of duplicate messages, detection requires the `malice-detection` feature.
+    pub(crate) fn detection(self) -> Detection {
+        match self {
+            MaliciousBehaviourType::Spam => Detection::Rejection,
+            _ if !cfg!(feature = "malice-detection") => Detection::None,
+            MaliciousBehaviourType::Fork
+            | MaliciousBehaviourType::EquivocatingVote
+            | MaliciousBehaviourType::DuplicateVote
+            | MaliciousBehaviourType::InvalidAccusation => Detection::Accusation,
+            MaliciousBehaviourType::PrematureGossip | MaliciousBehaviourType::ForgedRecipient => {
+                Detection::Rejection
+            }
+            MaliciousBehaviourType::WithholdEvents => Detection::None,
+        }
+    }
+
+    pub(crate) fn create(self, rng: Box<dyn Rng + Send>) -> Box<dyn MaliciousBehaviour> {
+        match self {
+            MaliciousBehaviourType::Fork => Box::new(Forking {
+                rng,
+                misbehaved: false,
+            }),
+            MaliciousBehaviourType::EquivocatingVote => {
+                Box::new(EquivocatingVotes { misbehaved: false })
+            }
+            MaliciousBehaviourType::DuplicateVote => Box::new(DuplicateVotes { misbehaved: false }),
+            MaliciousBehaviourType::WithholdEvents => Box::new(Withholding { rng, victims: None }),
+            MaliciousBehaviourType::InvalidAccusation => {
+                Box::new(FalseAccusation { misbehaved: false })
+            }
+            MaliciousBehaviourType::PrematureGossip => {
+                Box::new(PrematureGossip { misbehaved: false })
+            }
+            MaliciousBehaviourType::Spam => Box::new(Spamming { misbehaved: false }),
+            MaliciousBehaviourType::ForgedRecipient => Box::new(ForgedRecipient {
+                rng,
+                misbehaved: false,
+            }),
+        }
+    }
+}
+
+/// Behaviour of a malicious test peer.  The hooks are called by the test network in place of the
+/// corresponding `Parsec` methods.
+pub(crate) trait MaliciousBehaviour: Send {
+    /// Returns the requests to send to `recipient`, which may not be a valid gossip recipient.
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>>;
+
+    /// Handles a request from `src`, returning the response to send back, if any.
+    fn handle_request(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        src: &PeerId,
+        req: Request<Transaction, PeerId>,
+    ) -> Result<Option<Response<Transaction, PeerId>>> {
+        if self.has_misbehaved() {
+            return Ok(None);
+        }
+        parsec.handle_request(src, req).map(Some)
+    }
+
+    /// Handles a response from `src`.
+    fn handle_response(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        src: &PeerId,
+        resp: Response<Transaction, PeerId>,
+    ) -> Result<()> {
+        if self.has_misbehaved() {
+            return Ok(());
+        }
+        parsec.handle_response(src, resp)
+    }
+
+    /// Returns whether we have misbehaved yet.
+    fn has_misbehaved(&self) -> bool;
+
+    /// Returns whether honest peers are right to accuse us of `malice`.
+    fn is_expected_accusation(&self, _malice: &Malice<Transaction, PeerId>) -> bool {
+        false
+    }
+
+    /// Returns whether honest peers are right to reject our messages with `error`.
+    fn is_expected_rejection(&self, _error: &Error) -> bool {
+        false
+    }
+}
+
+struct Forking {
+    rng: Box<dyn Rng + Send>,
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for Forking {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if self.misbehaved {
+            return parsec.create_gossip(recipient).map(|req| vec![req]);
+        }
+
+        let recipient_index = parsec.get_peer_index(recipient)?;
+        parsec.confirm_allowed_to_gossip_to(recipient_index)?;
+
+        let self_id = parsec.our_pub_id().clone();
+        let common_self_parent_index = parsec.our_last_event_index();
+
+        // Add a `Requesting` event to our graph to a dummy recipient.
+        let dummy_recipient_id = {
+            let mut gossip_recipients = parsec
+                .gossip_recipients()
+                .filter(|peer_id| **peer_id != self_id && *peer_id != recipient)
+                .cloned()
+                .collect_vec();
+            self.rng.shuffle(&mut gossip_recipients);
+            unwrap!(gossip_recipients.into_iter().next())
+        };
+        let _ = parsec.create_gossip(&dummy_recipient_id)?;
+
+        // Create the forking event.
+        let event = Event::new_from_requesting(
+            common_self_parent_index,
+            recipient,
+            parsec.event_context(),
+        )?;
+        self.misbehaved = true;
+        Ok(vec![fork_request(
+            parsec,
+            recipient_index,
+            parsec.our_last_event_index(),
+            &event,
+        )])
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_accusation(&self, malice: &Malice<Transaction, PeerId>) -> bool {
+        match malice {
+            Malice::Fork(..) => true,
+            _ => false,
+        }
+    }
+}
+
+struct EquivocatingVotes {
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for EquivocatingVotes {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if self.misbehaved {
+            return parsec.create_gossip(recipient).map(|req| vec![req]);
+        }
+
+        let recipient_index = parsec.get_peer_index(recipient)?;
+        parsec.confirm_allowed_to_gossip_to(recipient_index)?;
+
+        // Vote for one observation in our graph, and for another one in the forked event we only
+        // send to the recipient.  As the forked event isn't in our graph, we can't create a
+        // `Requesting` event on top of it, so the recipient will reject the request.
+        let common_self_parent_index = parsec.our_last_event_index();
+        let event = parsec.new_event_from_observation(
+            common_self_parent_index,
+            fabricated_vote(recipient, b"equivocating vote 1"),
+        )?;
+        let partner_event_index = parsec.add_event(event)?;
+        let forked_event = parsec.new_event_from_observation(
+            common_self_parent_index,
+            fabricated_vote(recipient, b"equivocating vote 2"),
+        )?;
+
+        self.misbehaved = true;
+        Ok(vec![fork_request(
+            parsec,
+            recipient_index,
+            partner_event_index,
+            &forked_event,
+        )])
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_accusation(&self, malice: &Malice<Transaction, PeerId>) -> bool {
+        match malice {
+            Malice::Fork(..) => true,
+            _ => false,
+        }
+    }
+
+    fn is_expected_rejection(&self, error: &Error) -> bool {
+        *error == Error::InvalidMessage
+    }
+}
+
+struct DuplicateVotes {
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for DuplicateVotes {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if !self.misbehaved {
+            let observation = fabricated_vote(recipient, b"duplicate vote");
+            for _ in 0..2 {
+                let self_parent = parsec.our_last_event_index();
+                let event = parsec.new_event_from_observation(self_parent, observation.clone())?;
+                let _ = parsec.add_event(event)?;
+            }
+            self.misbehaved = true;
+        }
+        parsec.create_gossip(recipient).map(|req| vec![req])
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_accusation(&self, malice: &Malice<Transaction, PeerId>) -> bool {
+        match malice {
+            Malice::DuplicateVote(..) => true,
+            _ => false,
+        }
+    }
+}
+
+struct Withholding {
+    rng: Box<dyn Rng + Send>,
+    // The peers we withhold our events from, chosen when we first gossip.
+    victims: Option<BTreeSet<PeerId>>,
+}
+
+impl Withholding {
+    fn is_victim(&self, peer_id: &PeerId) -> bool {
+        self.victims
+            .as_ref()
+            .map_or(false, |victims| victims.contains(peer_id))
+    }
+}
+
+impl MaliciousBehaviour for Withholding {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if self.victims.is_none() {
+            let mut peer_ids = parsec.gossip_recipients().cloned().collect_vec();
+            self.rng.shuffle(&mut peer_ids);
+            let num_victims = (peer_ids.len() + 1) / 2;
+            self.victims = Some(peer_ids.into_iter().take(num_victims).collect());
+        }
+
+        let request = parsec.create_gossip(recipient)?;
+        if self.is_victim(recipient) {
+            Ok(vec![])
+        } else {
+            Ok(vec![request])
+        }
+    }
+
+    fn handle_request(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        src: &PeerId,
+        req: Request<Transaction, PeerId>,
+    ) -> Result<Option<Response<Transaction, PeerId>>> {
+        if self.is_victim(src) {
+            return Ok(None);
+        }
+        parsec.handle_request(src, req).map(Some)
+    }
+
+    fn handle_response(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        src: &PeerId,
+        resp: Response<Transaction, PeerId>,
+    ) -> Result<()> {
+        parsec.handle_response(src, resp)
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.victims
+            .as_ref()
+            .map_or(false, |victims| !victims.is_empty())
+    }
+}
+
+struct FalseAccusation {
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for FalseAccusation {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if !self.misbehaved {
+            let recipient_index = parsec.get_peer_index(recipient)?;
+            parsec.confirm_allowed_to_gossip_to(recipient_index)?;
+
+            // Accuse the recipient of a fork nobody else has seen.  The accusation will be the
+            // self-parent of our next `Requesting` event, which makes it checkable by the
+            // recipient.
+            let accused_event_index = parsec
+                .peer_list()
+                .last_event(recipient_index)
+                .unwrap_or_else(|| parsec.our_last_event_index());
+            let accused_event_hash = *unwrap!(parsec.graph().get(accused_event_index)).hash();
+            parsec.vote_for(ParsecObservation::Accusation {
+                offender: recipient.clone(),
+                malice: Malice::Fork(accused_event_hash),
+            })?;
+            self.misbehaved = true;
+        }
+        parsec.create_gossip(recipient).map(|req| vec![req])
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_accusation(&self, malice: &Malice<Transaction, PeerId>) -> bool {
+        match malice {
+            Malice::InvalidAccusation(..) => true,
+            _ => false,
+        }
+    }
+}
+
+struct PrematureGossip {
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for PrematureGossip {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if self.misbehaved
+            || parsec
+                .gossip_recipients()
+                .any(|peer_id| peer_id == recipient)
+        {
+            return parsec.create_gossip(recipient).map(|req| vec![req]);
+        }
+
+        // We can't create a `Requesting` event for a peer we aren't allowed to gossip to, so just
+        // send our whole graph.
+        self.misbehaved = true;
+        Ok(vec![Request::new(pack_events(
+            parsec,
+            parsec.graph().iter().map(|event| event.inner()),
+        ))])
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_rejection(&self, error: &Error) -> bool {
+        match error {
+            Error::PrematureGossip | Error::InvalidMessage => true,
+            _ => false,
+        }
+    }
+}
+
+struct Spamming {
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for Spamming {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        let request = parsec.create_gossip(recipient)?;
+        self.misbehaved = true;
+        Ok(iter::repeat(request).take(SPAM_COPIES).collect())
+    }
+
+    fn handle_request(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        src: &PeerId,
+        req: Request<Transaction, PeerId>,
+    ) -> Result<Option<Response<Transaction, PeerId>>> {
+        parsec.handle_request(src, req).map(Some)
+    }
+
+    fn handle_response(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        src: &PeerId,
+        resp: Response<Transaction, PeerId>,
+    ) -> Result<()> {
+        parsec.handle_response(src, resp)
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_rejection(&self, error: &Error) -> bool {
+        // All copies but the first one are rejected.
+        *error == Error::DuplicateMessage
+    }
+}
+
+struct ForgedRecipient {
+    rng: Box<dyn Rng + Send>,
+    misbehaved: bool,
+}
+
+impl MaliciousBehaviour for ForgedRecipient {
+    fn create_gossip(
+        &mut self,
+        parsec: &mut TestParsecImpl,
+        recipient: &PeerId,
+    ) -> Result<Vec<Request<Transaction, PeerId>>> {
+        if self.misbehaved {
+            return parsec.create_gossip(recipient).map(|req| vec![req]);
+        }
+
+        let recipient_index = parsec.get_peer_index(recipient)?;
+        parsec.confirm_allowed_to_gossip_to(recipient_index)?;
+
+        let self_id = parsec.our_pub_id().clone();
+        let mut other_recipients = parsec
+            .gossip_recipients()
+            .filter(|peer_id| **peer_id != self_id && *peer_id != recipient)
+            .cloned()
+            .collect_vec();
+        self.rng.shuffle(&mut other_recipients);
+        let forged_recipient = if let Some(peer_id) = other_recipients.into_iter().next() {
+            peer_id
+        } else {
+            return parsec.create_gossip(recipient).map(|req| vec![req]);
+        };
+
+        // Send our whole graph so that the recipient doesn't miss any ancestor of the
+        // `Requesting` event, which only the forged recipient is expected to handle.
+        let _ = parsec.create_gossip(&forged_recipient)?;
+        self.misbehaved = true;
+        Ok(vec![Request::new(pack_events(
+            parsec,
+            parsec.graph().iter().map(|event| event.inner()),
+        ))])
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.misbehaved
+    }
+
+    fn is_expected_rejection(&self, error: &Error) -> bool {
+        *error == Error::InvalidMessage
+    }
+}
+
+// Returns the request which would normally be sent to the recipient (containing the events
+// returned by `Parsec::events_to_gossip_to_peer`), but with all events from the partner event
+// onwards replaced with the forked event.
+fn fork_request(
+    parsec: &TestParsecImpl,
+    recipient_index: PeerIndex,
+    partner_event_index: EventIndex,
+    forked_event: &Event<PeerId>,
+) -> Request<Transaction, PeerId> {
+    let events = if parsec.peer_list().last_event(recipient_index).is_some() {
+        unwrap!(parsec.events_to_gossip_to_peer(recipient_index))
+    } else {
+        parsec.graph().iter().map(|event| event.inner()).collect()
+    };
+
+    let events = events
+        .into_iter()
+        .take_while(|event| unwrap!(parsec.graph().get_index(event.hash())) != partner_event_index)
+        .chain(iter::once(forked_event));
+    Request::new(pack_events(parsec, events))
+}
+
+fn pack_events<'a, I>(parsec: &TestParsecImpl, events: I) -> Vec<PackedEvent<Transaction, PeerId>>
+where
+    I: IntoIterator<Item = &'a Event<PeerId>>,
+{
+    events
+        .into_iter()
+        .map(|event| unwrap!(event.pack(parsec.event_context())))
+        .collect()
+}
+
+// A vote which will never reach consensus, since only the malicious peer makes it.
+fn fabricated_vote(peer_id: &PeerId, related_info: &[u8]) -> Observation {
+    ParsecObservation::Remove {
+        peer_id: peer_id.clone(),
+        related_info: related_info.to_vec(),
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{ConsensusError, Environment, Schedule, ScheduleEvent};
+use std::{
+    mem,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Shrinks a failing schedule by delta debugging: repeatedly removes chunks of its events, keeping
+/// each removal after which executing the schedule still fails with the same `ConsensusError`
+/// variant, until no single event can be removed any more.
+///
+/// Every execution uses a fresh environment created by `new_env`, and is seeded from the schedule
+/// so that the failure reproduces deterministically.  Before each candidate is executed, its
+/// expected peers and numbers of blocks are recomputed from its remaining events (see
+/// `Schedule::recompute_expectations`), so that the failure isn't merely caused by the removed
+/// events no longer meeting the original expectations.  The genesis event and the additional
+/// steps are always kept.  Candidates which panic rather than return an error are treated as not
+/// reproducing the failure.
+///
+/// Returns the smallest failing schedule found, along with its error, or `None` if `schedule`
+/// doesn't fail in the first place.
+pub fn minimise_schedule<F>(
+    schedule: Schedule,
+    mut new_env: F,
+) -> Option<(Schedule, ConsensusError)>
+where
+    F: FnMut() -> Environment,
+{
+    let mut error = execute(&schedule, &mut new_env)?;
+    let consensus_mode = new_env().network.consensus_mode();
+    let mut schedule = schedule;
+    let genesis = schedule.events.remove(0);
+    let mut events = mem::replace(&mut schedule.events, vec![]);
+    let mut minimised = None;
+
+    let mut num_chunks = 2;
+    while !events.is_empty() {
+        let chunk_size = (events.len() + num_chunks - 1) / num_chunks;
+        let mut reduced = false;
+
+        for start in (0..events.len()).step_by(chunk_size) {
+            let end = (start + chunk_size).min(events.len());
+            let mut candidate = schedule.clone();
+            candidate.events = events_without(&genesis, &events, start, end);
+            candidate.recompute_expectations(consensus_mode);
+            if let Some(new_error) = execute(&candidate, &mut new_env) {
+                if mem::discriminant(&new_error) == mem::discriminant(&error) {
+                    let _ = events.drain(start..end);
+                    error = new_error;
+                    minimised = Some(candidate);
+                    num_chunks = (num_chunks - 1).max(2);
+                    reduced = true;
+                    break;
+                }
+            }
+        }
+
+        if !reduced {
+            if chunk_size == 1 {
+                break;
+            }
+            num_chunks = (num_chunks * 2).min(events.len());
+        }
+    }
+
+    let schedule = minimised.unwrap_or_else(|| {
+        schedule.events = events_without(&genesis, &events, 0, 0);
+        schedule
+    });
+    Some((schedule, error))
+}
+
+// Returns the genesis event followed by `events` without those in the range `start..end`.
+fn events_without(
+    genesis: &ScheduleEvent,
+    events: &[ScheduleEvent],
+    start: usize,
+    end: usize,
+) -> Vec<ScheduleEvent> {
+    let mut result = Vec::with_capacity(events.len() + 1 - (end - start));
+    result.push(genesis.clone());
+    result.extend_from_slice(&events[..start]);
+    result.extend_from_slice(&events[end..]);
+    result
+}
+
+// Executes the schedule in a fresh environment, returning the error it fails with, if any.
+fn execute<F>(schedule: &Schedule, new_env: &mut F) -> Option<ConsensusError>
+where
+    F: FnMut() -> Environment,
+{
+    let mut env = new_env();
+    let schedule = schedule.clone();
+    panic::catch_unwind(AssertUnwindSafe(move || env.execute_schedule(schedule)))
+        .ok()
+        .and_then(Result::err)
+}
@@ -13,6 +13,9 @@ mod macros;
 #[cfg(any(test, feature = "testing"))]
 mod dot_parser;
 mod environment;
+mod interleavings;
+mod malicious_behaviour;
+mod minimiser;
 mod misc;
 mod network;
 mod peer;
@@ -22,21 +25,26 @@ pub mod proptest;
 mod pseudo_random;
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 mod record;
+mod report;
 mod schedule;
 
-#[cfg(test)]
-pub(crate) use self::dot_parser::parse_test_dot_file;
 #[cfg(all(test, feature = "mock"))]
 pub(crate) use self::dot_parser::ParsedContents;
+#[cfg(test)]
+pub(crate) use self::dot_parser::{parse_dot_file, parse_test_dot_file};
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 pub use self::record::Record;
 pub use self::{
     environment::Environment,
+    interleavings::{check_interleavings, check_interleavings_with, InterleavingBounds},
+    malicious_behaviour::MaliciousBehaviourType,
+    minimiser::minimise_schedule,
     misc::TestIterator,
     network::{ConsensusError, Network},
     peer::{NetworkView, Peer, PeerStatus},
     peer_statuses::PeerStatuses,
     pseudo_random::{new_common_rng, new_rng, ReplayRng, RngChoice, RngDebug},
+    report::{BlockLatency, PerformanceReport},
     schedule::*,
 };
 
@@ -7,8 +7,10 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
+    malicious_behaviour::{Detection, MaliciousBehaviourType},
     new_rng,
     peer::{NetworkView, Peer, PeerStatus},
+    report::{BlockKey, Measurements, PerformanceReport},
     schedule::{AddPeerType, Schedule, ScheduleEvent, ScheduleOptions},
     Observation,
 };
@@ -22,21 +24,31 @@ use crate::{
     },
 };
 use itertools::Itertools;
+use maidsafe_utilities::serialisation::serialised_size;
 use rand::Rng;
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
-    fmt,
+    fmt, mem,
 };
 
+/// Delivery step of the messages exchanged through `Gossip` events, which are only delivered by
+/// `Deliver` events.
+const HELD: usize = usize::MAX;
+
+#[derive(Clone)]
 enum Message {
     Request(Request<Transaction, PeerId>, usize),
     Response(Response<Transaction, PeerId>),
 }
 
+#[derive(Clone)]
 struct QueueEntry {
     pub sender: PeerId,
     pub message: Message,
     pub deliver_after: usize,
+    /// Whether the message has been delivered twice, in which case the recipient is expected to
+    /// reject whichever copy it handles last.
+    pub duplicate: bool,
 }
 
 pub struct Network {
@@ -44,12 +56,36 @@ pub struct Network {
     genesis: BTreeSet<PeerId>,
     msg_queue: BTreeMap<PeerId, Vec<QueueEntry>>,
     consensus_mode: ConsensusMode,
+    /// Malicious peers which had a message rejected by an honest peer.
+    rejected_peers: BTreeSet<PeerId>,
+    /// The current partition of the network, if any.
+    partition: Option<NetworkPartition>,
+    /// Measurements for the performance report.
+    measurements: Measurements,
+}
+
+struct NetworkPartition {
+    groups: Vec<BTreeSet<PeerId>>,
+    drop_messages: bool,
+    /// Messages sent across the partition, along with their recipients, to be delivered once it
+    /// heals.
+    held_messages: Vec<(PeerId, QueueEntry)>,
+}
+
+impl NetworkPartition {
+    fn group_of(&self, peer_id: &PeerId) -> Option<usize> {
+        self.groups.iter().position(|group| group.contains(peer_id))
+    }
+
+    fn separates(&self, peer_id_1: &PeerId, peer_id_2: &PeerId) -> bool {
+        self.group_of(peer_id_1) != self.group_of(peer_id_2)
+    }
 }
 
 #[derive(Debug)]
 pub struct BlocksOrder {
     peer: PeerId,
-    order: Vec<(Observation, Option<PeerId>)>,
+    order: Vec<BlockKey>,
 }
 
 pub struct DifferingBlocksOrder {
@@ -106,6 +142,20 @@ pub enum ConsensusError {
         accused: PeerId,
         malice: Malice<Transaction, PeerId>,
     },
+    UndetectedMalice {
+        offender: PeerId,
+        behaviour: MaliciousBehaviourType,
+    },
+    /// A property checked by `check_interleavings_with` doesn't hold.
+    PropertyViolated(String),
+    /// Observations which honest peers failed to consensus within
+    /// `ScheduleOptions::liveness_bound_ms`.
+    StuckObservations {
+        time_ms: u64,
+        observations: Vec<Observation>,
+    },
+    /// A peer panicked during an execution checked by `check_interleavings`, with this message.
+    Panicked(String),
 }
 
 impl Network {
@@ -116,6 +166,9 @@ impl Network {
             genesis: BTreeSet::new(),
             msg_queue: BTreeMap::new(),
             consensus_mode,
+            rejected_peers: BTreeSet::new(),
+            partition: None,
+            measurements: Measurements::default(),
         }
     }
 
@@ -123,6 +176,11 @@ impl Network {
         self.consensus_mode
     }
 
+    /// Simulated time of the latest local step, in milliseconds.
+    pub fn now(&self) -> u64 {
+        self.measurements.now()
+    }
+
     fn active_peers(&self) -> impl Iterator<Item = &Peer> {
         self.peers
             .values()
@@ -194,53 +252,175 @@ impl Network {
         unwrap!(self.peers.get_mut(id))
     }
 
-    fn send_message(&mut self, src: PeerId, dst: &PeerId, message: Message, deliver_after: usize) {
+    fn send_message<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        options: &ScheduleOptions,
+        src: PeerId,
+        dst: &PeerId,
+        message: Message,
+        deliver_after: usize,
+    ) {
+        match message {
+            Message::Request(ref req, _) => self.measurements.request_sent(serialised_size(req)),
+            Message::Response(ref resp) => self.measurements.response_sent(serialised_size(resp)),
+        }
         if !self.peer(dst).is_running() {
             return;
         }
+        if options.prob_message_drop > 0.0 && rng.gen::<f64>() < options.prob_message_drop {
+            return;
+        }
+        let duplicate = options.prob_message_duplication > 0.0
+            && rng.gen::<f64>() < options.prob_message_duplication;
+        let entry = QueueEntry {
+            sender: src,
+            message,
+            deliver_after,
+            duplicate,
+        };
+        if duplicate {
+            let mut copy = entry.clone();
+            copy.deliver_after =
+                copy.deliver_after
+                    .saturating_add(options.gen_delay(rng, &copy.sender, dst));
+            self.enqueue_message(dst, copy);
+        }
+        self.enqueue_message(dst, entry);
+    }
+
+    fn enqueue_message(&mut self, dst: &PeerId, entry: QueueEntry) {
+        if let Some(partition) = self.partition.as_mut() {
+            if partition.separates(&entry.sender, dst) {
+                if !partition.drop_messages {
+                    partition.held_messages.push((dst.clone(), entry));
+                }
+                return;
+            }
+        }
         self.msg_queue
             .entry(dst.clone())
             .or_insert_with(Vec::new)
-            .push(QueueEntry {
-                sender: src,
-                message,
-                deliver_after,
-            });
+            .push(entry);
+    }
+
+    /// Starts the given partition.  Messages already queued across it are held or dropped too.
+    fn partition(&mut self, mut partition: NetworkPartition) {
+        for (dst, entries) in &mut self.msg_queue {
+            let (separated, kept): (Vec<_>, Vec<_>) = mem::replace(entries, vec![])
+                .into_iter()
+                .partition(|entry| partition.separates(&entry.sender, dst));
+            *entries = kept;
+            if !partition.drop_messages {
+                partition
+                    .held_messages
+                    .extend(separated.into_iter().map(|entry| (dst.clone(), entry)));
+            }
+        }
+        self.partition = Some(partition);
+    }
+
+    /// Ends the current partition, if any, delivering the held messages.
+    fn heal(&mut self) {
+        if let Some(partition) = self.partition.take() {
+            for (dst, entry) in partition.held_messages {
+                if self.peer(&dst).is_running() {
+                    self.msg_queue
+                        .entry(dst)
+                        .or_insert_with(Vec::new)
+                        .push(entry);
+                }
+            }
+        }
     }
 
     /// Handles incoming requests and responses.
-    fn handle_messages(&mut self, peer: &PeerId, step: usize) {
+    fn handle_messages<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        options: &ScheduleOptions,
+        peer: &PeerId,
+        step: usize,
+    ) {
         if let Some(msgs) = self.msg_queue.remove(peer) {
-            let (to_handle, rest) = msgs
+            let (to_handle, mut rest): (Vec<_>, Vec<_>) = msgs
                 .into_iter()
                 .partition(|entry| entry.deliver_after <= step);
+            // Messages held back are handled after those which become due later.
+            let (to_handle, held_back): (Vec<_>, Vec<_>) = to_handle.into_iter().partition(|_| {
+                options.prob_message_reorder <= 0.0
+                    || rng.gen::<f64>() >= options.prob_message_reorder
+            });
+            rest.extend(held_back);
             let _ = self.msg_queue.insert(peer.clone(), rest);
-            // If this is a malicious peer which has already created a fork, ignore all incoming
-            // messages.
-            if self.peer(peer).has_misbehaved() {
-                return;
-            }
             for entry in to_handle {
-                match entry.message {
-                    Message::Request(req, resp_delay) => {
-                        match self.peer_mut(peer).handle_request(&entry.sender, req) {
-                            Ok(response) => {
-                                self.send_message(
-                                    peer.clone(),
-                                    &entry.sender,
-                                    Message::Response(response),
-                                    step + resp_delay,
-                                );
-                            }
-                            Err(Error::UnknownPeer) | Err(Error::InvalidPeerState { .. }) => (),
-                            Err(e) => panic!("{:?}", e),
-                        }
+                self.handle_message(rng, options, peer, entry, step);
+            }
+        }
+    }
+
+    /// Removes the oldest message in flight from `sender` to `recipient`, if any.
+    fn take_message(&mut self, sender: &PeerId, recipient: &PeerId) -> Option<QueueEntry> {
+        let msgs = self.msg_queue.get_mut(recipient)?;
+        let index = msgs.iter().position(|entry| entry.sender == *sender)?;
+        Some(msgs.remove(index))
+    }
+
+    fn handle_message<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        options: &ScheduleOptions,
+        peer: &PeerId,
+        entry: QueueEntry,
+        step: usize,
+    ) {
+        let result = match entry.message {
+            Message::Request(req, resp_delay) => {
+                match self.peer_mut(peer).handle_request(&entry.sender, req) {
+                    Ok(Some(response)) => {
+                        self.send_message(
+                            rng,
+                            options,
+                            peer.clone(),
+                            &entry.sender,
+                            Message::Response(response),
+                            step.saturating_add(resp_delay),
+                        );
+                        Ok(())
                     }
-                    Message::Response(resp) => {
-                        unwrap!(self.peer_mut(peer).handle_response(&entry.sender, resp))
+                    Ok(None) | Err(Error::UnknownPeer) | Err(Error::InvalidPeerState { .. }) => {
+                        Ok(())
                     }
+                    Err(e) => Err(e),
                 }
             }
+            Message::Response(resp) => self.peer_mut(peer).handle_response(&entry.sender, resp),
+        };
+        match result {
+            Ok(()) => (),
+            // We don't care about malicious peers failing to handle messages.
+            Err(_) if self.peer(peer).is_malicious() => (),
+            // The other copy of a duplicated message has been handled already.
+            Err(Error::DuplicateMessage) if entry.duplicate => (),
+            Err(ref e) if self.peer(&entry.sender).is_expected_rejection(e) => {
+                let _ = self.rejected_peers.insert(entry.sender);
+            }
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    /// Polls the given peer for blocks, recording those it hadn't polled yet.
+    fn poll_peer(&mut self, peer_id: &PeerId) {
+        let num_blocks = self.peer(peer_id).blocks().count();
+        self.peer_mut(peer_id).poll_all();
+        let new_blocks = self
+            .peer(peer_id)
+            .blocks()
+            .skip(num_blocks)
+            .map(|block| self.owned_block_key(block))
+            .collect_vec();
+        for key in new_blocks {
+            self.measurements.block_polled(key, peer_id);
         }
     }
 
@@ -262,28 +442,45 @@ impl Network {
                 break recipient;
             }
         };
+        self.send_gossip_to(rng, options, sender, recipient, step);
+    }
+
+    /// Makes `sender` send gossip to `recipient`.  If `step` is `HELD`, the messages exchanged are
+    /// only delivered by `Deliver` events.
+    fn send_gossip_to<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        options: &ScheduleOptions,
+        sender: &PeerId,
+        recipient: &PeerId,
+        step: usize,
+    ) {
         let valid = self
             .peer(sender)
             .gossip_recipients()
             .any(|valid_recipient| valid_recipient == recipient);
-        let result = if self.peer(sender).is_malicious() && !self.peer(sender).has_misbehaved() {
-            self.peer_mut(sender)
-                .create_gossip_with_fork(recipient, rng)
-        } else {
-            self.peer_mut(sender).create_gossip(recipient)
-        };
+        let result = self.peer_mut(sender).create_gossip(recipient);
 
-        if valid {
-            // Recipient is valid. `create_gossip` must have succeeded.
-            let request = unwrap!(result);
-            let req_delay = options.gen_delay(rng);
-            let resp_delay = options.gen_delay(rng);
-            self.send_message(
-                sender.clone(),
-                recipient,
-                Message::Request(request, resp_delay),
-                step + req_delay,
-            );
+        if valid || (self.peer(sender).is_malicious() && result.is_ok()) {
+            // Recipient is valid, or a malicious sender ignored its invalidity.  `create_gossip`
+            // must have succeeded.
+            for request in unwrap!(result) {
+                let (deliver_after, resp_delay) = if step == HELD {
+                    (HELD, 0)
+                } else {
+                    let req_delay = options.gen_delay(rng, sender, recipient);
+                    let resp_delay = options.gen_delay(rng, recipient, sender);
+                    (step + req_delay, resp_delay)
+                };
+                self.send_message(
+                    rng,
+                    options,
+                    sender.clone(),
+                    recipient,
+                    Message::Request(request, resp_delay),
+                    deliver_after,
+                );
+            }
         } else {
             // Recipient is not valid. `create_gossip` must have failed.
             match result {
@@ -295,6 +492,65 @@ impl Network {
         }
     }
 
+    /// Returns the events which can be executed next to extend the current interleaving of
+    /// explicit `Gossip` and `Deliver` events: any running peer gossiping to any of its valid
+    /// recipients, unless `max_messages_in_flight` messages are in flight already, and the
+    /// delivery of the oldest message in flight over any link.
+    pub(crate) fn interleaving_choices(&self, max_messages_in_flight: usize) -> Vec<ScheduleEvent> {
+        let mut choices = vec![];
+        let num_messages_in_flight: usize = self.msg_queue.values().map(Vec::len).sum();
+        if num_messages_in_flight < max_messages_in_flight {
+            for peer in self.peers.values().filter(|peer| peer.is_running()) {
+                choices.extend(
+                    peer.gossip_recipients().map(|recipient| {
+                        ScheduleEvent::Gossip(peer.id().clone(), recipient.clone())
+                    }),
+                );
+            }
+        }
+        for (recipient, msgs) in &self.msg_queue {
+            let senders: BTreeSet<_> = msgs.iter().map(|entry| &entry.sender).collect();
+            choices.extend(
+                senders
+                    .into_iter()
+                    .map(|sender| ScheduleEvent::Deliver(sender.clone(), recipient.clone())),
+            );
+        }
+        choices
+    }
+
+    /// Returns an error if the blocks of any honest peer conflict with those of the honest peer
+    /// with the most blocks, i.e. they aren't a prefix of them.
+    fn check_no_conflicting_blocks(&self) -> Result<(), ConsensusError> {
+        let longest = if let Some(peer) = self
+            .running_non_malicious_peers()
+            .max_by_key(|peer| peer.blocks().count())
+        {
+            peer
+        } else {
+            return Ok(());
+        };
+        let longest_keys = self.block_keys(longest);
+
+        if let Some(peer) = self
+            .running_non_malicious_peers()
+            .find(|peer| !longest_keys.starts_with(&self.block_keys(peer)))
+        {
+            Err(ConsensusError::DifferingBlocksOrder(DifferingBlocksOrder {
+                order_1: BlocksOrder {
+                    peer: longest.id().clone(),
+                    order: longest_keys,
+                },
+                order_2: BlocksOrder {
+                    peer: peer.id().clone(),
+                    order: self.block_keys(peer),
+                },
+            }))
+        } else {
+            Ok(())
+        }
+    }
+
     fn check_consensus_broken(&self) -> Result<(), ConsensusError> {
         let mut block_order = BTreeMap::new();
         for peer in self.running_non_malicious_peers() {
@@ -321,15 +577,27 @@ impl Network {
         Ok(())
     }
 
-    fn block_keys(&self, peer: &Peer) -> Vec<(Observation, Option<PeerId>)> {
+    fn block_keys(&self, peer: &Peer) -> Vec<BlockKey> {
         peer.blocks()
-            .map(|block| {
-                let (obs, opt_peer_id) = self.block_key(block);
-                (obs.clone(), opt_peer_id.cloned())
-            })
+            .map(|block| self.owned_block_key(block))
             .collect()
     }
 
+    fn owned_block_key(&self, block: &Block<Transaction, PeerId>) -> BlockKey {
+        let (observation, peer_id) = self.block_key(block);
+        (observation.clone(), peer_id.cloned())
+    }
+
+    // Returns the key of the block which will result from the given vote.
+    fn vote_key(&self, voter: &PeerId, observation: &Observation) -> BlockKey {
+        let voter = if observation.is_opaque() && self.consensus_mode == ConsensusMode::Single {
+            Some(voter.clone())
+        } else {
+            None
+        };
+        (observation.clone(), voter)
+    }
+
     fn block_key<'a>(
         &self,
         block: &'a Block<Transaction, PeerId>,
@@ -461,7 +729,12 @@ impl Network {
                     ParsecObservation::Add { ref peer_id, .. } => {
                         let _ = valid_voters.insert(peer_id.clone());
                     }
-                    ParsecObservation::Remove { ref peer_id, .. } => {
+                    // Parsec removes the offender of a consensused accusation too.
+                    ParsecObservation::Remove { ref peer_id, .. }
+                    | ParsecObservation::Accusation {
+                        offender: ref peer_id,
+                        ..
+                    } => {
                         let _ = valid_voters.remove(peer_id);
                     }
                     _ => {}
@@ -471,15 +744,17 @@ impl Network {
         Ok(())
     }
 
-    /// Check that no well-behaved peer has been accused of malice.
+    /// Check that the given peer, if well-behaved, hasn't accused anyone of malice other than what
+    /// the offender's malicious behaviour warrants.
     fn check_unexpected_accusations(&self, peer_id: &PeerId) -> Result<(), ConsensusError> {
+        if self.peer(peer_id).is_malicious() {
+            return Ok(());
+        }
+
         let accusation = self
             .peer(peer_id)
             .unpolled_accusations()
-            .find(|(offender, malice)| match malice {
-                Malice::Fork(..) => !self.peer(offender).has_misbehaved(),
-                _ => true,
-            });
+            .find(|(offender, malice)| !self.peer(offender).is_expected_accusation(malice));
 
         if let Some((offender, malice)) = accusation {
             Err(ConsensusError::UnexpectedAccusation {
@@ -492,28 +767,142 @@ impl Network {
         }
     }
 
-    /// Simulates the network according to the given schedule.
+    /// Check that every malicious peer which has misbehaved has been detected and punished by the
+    /// honest peers, as far as its behaviour is detectable.
+    fn check_malice_detected(&self) -> Result<(), ConsensusError> {
+        for peer in self.peers.values().filter(|peer| peer.has_misbehaved()) {
+            let behaviour = unwrap!(peer.malicious_behaviour());
+            let detected = match behaviour.detection() {
+                Detection::Accusation => self.running_non_malicious_peers().all(|honest_peer| {
+                    honest_peer.blocks().any(|block| match block.payload() {
+                        ParsecObservation::Accusation { offender, .. } => offender == peer.id(),
+                        _ => false,
+                    })
+                }),
+                Detection::Rejection => self.rejected_peers.contains(peer.id()),
+                Detection::None => true,
+            };
+
+            if !detected {
+                return Err(ConsensusError::UndetectedMalice {
+                    offender: peer.id().clone(),
+                    behaviour,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every observation voted for by more than two thirds of the running honest peers
+    /// (or by any of them, for opaque observations in `ConsensusMode::Single`) more than `bound`
+    /// steps before `step` has been consensused by every running honest peer, unless at least a
+    /// third of the running peers are malicious.
+    fn check_liveness(&self, step: usize, bound: usize) -> Result<(), ConsensusError> {
+        let running: BTreeSet<_> = self.running_peers_ids().into_iter().collect();
+        let num_malicious = running
+            .iter()
+            .filter(|peer_id| self.peer(peer_id).is_malicious())
+            .count();
+        if 3 * num_malicious >= running.len() {
+            return Ok(());
+        }
+
+        let honest = self
+            .running_non_malicious_peers()
+            .map(|peer| peer.id().clone())
+            .collect();
+        let observations = self.measurements.unpolled_votes(step - bound, &honest);
+        if observations.is_empty() {
+            Ok(())
+        } else {
+            Err(ConsensusError::StuckObservations {
+                time_ms: self.now(),
+                observations,
+            })
+        }
+    }
+
+    /// Simulates the network according to the given schedule, returning measurements of its
+    /// performance if consensus was reached correctly.
     pub fn execute_schedule<R: Rng>(
         &mut self,
         rng: &mut R,
         rng2: &mut R,
         schedule: Schedule,
+    ) -> Result<PerformanceReport, ConsensusError> {
+        let peers = schedule.peers.clone();
+        let min_observations = schedule.min_observations;
+        let max_observations = schedule.max_observations;
+        self.execute_events(rng, rng2, schedule)?;
+
+        for peer_id in self.running_peers_ids() {
+            self.check_unexpected_accusations(&peer_id)?;
+        }
+        self.check_malice_detected()?;
+
+        self.check_consensus(&peers, min_observations, max_observations)?;
+        self.check_blocks_signatories()?;
+
+        Ok(self.measurements.report(self.peers.values()))
+    }
+
+    /// Simulates the network according to the given schedule, only checking that the blocks
+    /// consensused so far are consistent, i.e. without requiring consensus to be complete by the
+    /// end of the schedule.
+    pub fn execute_schedule_prefix<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        rng2: &mut R,
+        schedule: Schedule,
+    ) -> Result<(), ConsensusError> {
+        self.execute_events(rng, rng2, schedule)?;
+
+        for peer_id in self.running_peers_ids() {
+            self.check_unexpected_accusations(&peer_id)?;
+        }
+        self.check_consensus_broken()?;
+        self.check_blocks_signatories()
+    }
+
+    fn execute_events<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        rng2: &mut R,
+        schedule: Schedule,
     ) -> Result<(), ConsensusError> {
         let Schedule {
             peers,
-            min_observations,
             max_observations,
             events,
             additional_steps,
             options,
+            ..
         } = schedule;
         let mut queue: VecDeque<_> = events.into_iter().collect();
         let mut retry = Vec::new();
         let mut additional_steps = additional_steps;
         let mut additional_step = || additional_steps.next().map(ScheduleEvent::LocalStep);
+        // The first global step after the last fault, if faults have stopped.
+        let mut faults_stopped = None;
 
         while let Some(event) = queue.pop_front().or_else(&mut additional_step) {
             if self.execute_event(rng, rng2, &options, event.clone())? {
+                if let (ScheduleEvent::LocalStep(step), Some(bound_ms)) =
+                    (&event, options.liveness_bound_ms)
+                {
+                    let bound = options.clock.steps(bound_ms);
+                    if faults_stopped.is_none()
+                        && self.partition.is_none()
+                        && !queue.iter().chain(&retry).any(ScheduleEvent::is_fault)
+                    {
+                        faults_stopped = Some(*step);
+                    }
+                    if faults_stopped.map_or(false, |faults_stopped| *step > faults_stopped + bound)
+                    {
+                        self.check_liveness(*step, bound)?;
+                    }
+                }
+
                 for event in retry.drain(..).rev() {
                     queue.push_front(event)
                 }
@@ -521,6 +910,9 @@ impl Network {
                 if options.intermediate_consistency_checks {
                     self.check_consensus_broken()?;
                 }
+                if self.partition.is_some() {
+                    self.check_no_conflicting_blocks()?;
+                }
 
                 if self.consensus_complete(&peers, max_observations) {
                     break;
@@ -529,13 +921,7 @@ impl Network {
                 retry.push(event);
             }
         }
-
-        for peer_id in self.running_peers_ids() {
-            self.check_unexpected_accusations(&peer_id)?;
-        }
-
-        self.check_consensus(&peers, min_observations, max_observations)?;
-        self.check_blocks_signatories()
+        Ok(())
     }
 
     // Returns 'Ok(true)' when event got executed, or 'Ok(false)' when the event needs to be delayed
@@ -568,12 +954,15 @@ impl Network {
                     .collect_vec();
                 let malicious_peers = genesis
                     .ids_of_malicious_peers()
-                    .map(|id| {
+                    .enumerate()
+                    .map(|(index, id)| {
                         Peer::malicious_from_genesis(
                             id.clone(),
                             &genesis_ids,
                             self.consensus_mode,
                             new_rng(rng2),
+                            options.malicious_behaviour(index),
+                            new_rng(rng2),
                         )
                     })
                     .collect_vec();
@@ -598,6 +987,9 @@ impl Network {
                 self.genesis = genesis_ids;
                 // Do a full reset while we're at it.
                 self.msg_queue.clear();
+                self.rejected_peers.clear();
+                self.partition = None;
+                self.measurements = Measurements::new(options.clock);
             }
             ScheduleEvent::AddPeer(peer_id, add_type) => {
                 if add_type == AddPeerType::Voter && !self.allow_addition_of_peer() {
@@ -629,11 +1021,21 @@ impl Network {
                     return Ok(false);
                 }
             }
+            ScheduleEvent::Partition(groups) => {
+                self.heal();
+                self.partition(NetworkPartition {
+                    groups,
+                    drop_messages: options.drop_partitioned_messages,
+                    held_messages: vec![],
+                });
+            }
+            ScheduleEvent::Heal => self.heal(),
             ScheduleEvent::LocalStep(step) => {
+                self.measurements.local_step(step);
                 for peer_id in self.running_peers_ids() {
                     self.peer_mut(&peer_id).make_votes();
-                    self.handle_messages(&peer_id, step);
-                    self.peer_mut(&peer_id).poll_all();
+                    self.handle_messages(rng, options, &peer_id, step);
+                    self.poll_peer(&peer_id);
                     if options.intermediate_consistency_checks {
                         self.check_unexpected_accusations(&peer_id)?;
                     }
@@ -641,11 +1043,29 @@ impl Network {
                 Peer::update_network_views(&mut self.peers);
                 let running_peers_ids = self.running_peers_ids();
                 for peer_id in &running_peers_ids {
-                    if rng.gen::<f64>() < options.prob_gossip {
+                    if rng.gen::<f64>() < options.prob_gossip() {
                         self.send_gossip(rng, options, peer_id, &running_peers_ids, step);
                     }
                 }
             }
+            ScheduleEvent::Gossip(sender, recipient) => {
+                if self.peers.get(&sender).map_or(false, Peer::is_running)
+                    && self.peers.contains_key(&recipient)
+                {
+                    self.peer_mut(&sender).make_votes();
+                    self.send_gossip_to(rng, options, &sender, &recipient, HELD);
+                }
+            }
+            ScheduleEvent::Deliver(sender, recipient) => {
+                if let Some(entry) = self.take_message(&sender, &recipient) {
+                    self.peer_mut(&recipient).make_votes();
+                    self.handle_message(rng, options, &recipient, entry, HELD);
+                    self.poll_peer(&recipient);
+                    if options.intermediate_consistency_checks {
+                        self.check_unexpected_accusations(&recipient)?;
+                    }
+                }
+            }
             ScheduleEvent::VoteFor(voting_peer_id, observation) => {
                 if let Some(voter) = self.peers.get(&voting_peer_id) {
                     // Skip voting by removed/failed peers.
@@ -675,6 +1095,8 @@ impl Network {
                     _ => (),
                 }
 
+                let key = self.vote_key(&voting_peer_id, &observation);
+                self.measurements.vote(key, &voting_peer_id);
                 self.peer_mut(&voting_peer_id).vote_for(&observation);
             }
         }
@@ -6,25 +6,27 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::Observation;
+use super::{
+    malicious_behaviour::{MaliciousBehaviour, MaliciousBehaviourType},
+    Observation,
+};
 use crate::{
     block::{Block, BlockGroup},
-    error::Result,
-    gossip::{Cause, Event, EventIndex, Request, Response},
+    error::{Error, Result},
+    gossip::{Request, Response},
+    metrics::Metrics,
     mock::{PeerId, Transaction},
     observation::{
         is_more_than_two_thirds, ConsensusMode, Malice, Observation as ParsecObservation,
     },
     parsec::{Parsec, TestParsec},
-    peer_list::PeerIndex,
 };
-use itertools::Itertools;
 use rand::Rng;
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt::{self, Debug, Formatter},
-    iter,
     ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
 };
 
 /// This represents the peer's own view of its current status.
@@ -44,7 +46,7 @@ use std::{
 ///         Need to kill peer
 ///             |-> `Removed`
 ///             |-> `Failed`
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum PeerStatus {
     Pending,
     Active,
@@ -87,112 +89,22 @@ pub enum NetworkView {
     Left,
 }
 
-struct ForkedEvent {
-    // The EventIndex of the first event we created with the same self-parent as this forked event.
-    // The partner will have been added to our graph.
-    partner_event_index: EventIndex,
-    // The second event we created with the same self-parent as the partner event.
-    event: Event<PeerId>,
-}
-
 struct MaliciousComponents {
     test_parsec: TestParsec<Transaction, PeerId>,
-    // A forked event, mapped to the EventIndex of the first event we created with the same
-    // self-parent.  This forked event is not added to our graph when we create it.
-    forked_event: Option<ForkedEvent>,
+    behaviour_type: MaliciousBehaviourType,
+    behaviour: Box<dyn MaliciousBehaviour>,
 }
 
 impl MaliciousComponents {
-    fn create_gossip_with_fork<R: Rng>(
-        &mut self,
-        recipient_id: &PeerId,
-        rng: &mut R,
-    ) -> Result<Request<Transaction, PeerId>> {
-        assert!(self.forked_event.is_none());
-        let recipient_index = self.test_parsec.get_peer_index(recipient_id)?;
-        self.test_parsec
-            .confirm_allowed_to_gossip_to(recipient_index)?;
-
-        let self_id = self.test_parsec.our_pub_id().clone();
-        let common_self_parent_index = self.test_parsec.our_last_event_index();
-
-        // Add a `Requesting` event to our graph to a dummy recipient.
-        let dummy_recipient_id = {
-            let mut gossip_recipients = self
-                .test_parsec
-                .peer_list()
-                .gossip_recipients()
-                .collect_vec();
-            rng.shuffle(&mut gossip_recipients);
-
-            unwrap!(gossip_recipients
-                .iter()
-                .filter(|(_, peer)| peer.id() != &self_id && peer.id() != recipient_id)
-                .map(|(_, peer)| peer.id().clone())
-                .next())
-        };
-        let _ = unwrap!(self.test_parsec.create_gossip(&dummy_recipient_id));
-
-        // Create the forking event.
-        let event = unwrap!(Event::new_from_requesting(
-            common_self_parent_index,
-            recipient_id,
-            self.test_parsec.event_context()
-        ));
-        let forked_event = ForkedEvent {
-            partner_event_index: self.test_parsec.our_last_event_index(),
-            event,
-        };
-        self.forked_event = Some(forked_event);
-
-        let events = self.events_to_gossip_to_peer(recipient_index);
-
-        Ok(Request::new(
-            events
-                .into_iter()
-                .map(|event| unwrap!(event.pack(self.test_parsec.event_context())))
-                .collect_vec(),
-        ))
-    }
-
-    // If the forked event should be sent to `recipient_id`, then we return the list of events which
-    // would normally be sent to the peer (those returned by `Parsec::events_to_gossip_to_peer`),
-    // but replace all from the first forked one with the forked event.
-    //
-    // If the forked event doesn't relate to this recipient, the normal list of events is returned.
-    fn events_to_gossip_to_peer(&self, recipient_index: PeerIndex) -> Vec<&Event<PeerId>> {
-        let events = if self
-            .test_parsec
-            .peer_list()
-            .last_event(recipient_index)
-            .is_some()
-        {
-            unwrap!(self.test_parsec.events_to_gossip_to_peer(recipient_index))
-        } else {
-            self.test_parsec.graph().iter().map(|e| e.inner()).collect()
-        };
-
-        let use_fork = self
-            .forked_event
-            .as_ref()
-            .map(|forked_event| match forked_event.event.cause() {
-                Cause::Requesting { recipient, .. } => recipient == &recipient_index,
-                _ => false,
-            })
-            .unwrap_or(false);
-
-        if use_fork {
-            let forked_event = unwrap!(self.forked_event.as_ref());
-            events
-                .into_iter()
-                .take_while(|event| {
-                    unwrap!(self.test_parsec.graph().get_index(event.hash()))
-                        != forked_event.partner_event_index
-                })
-                .chain(iter::once(&forked_event.event))
-                .collect()
-        } else {
-            events
+    fn new(
+        test_parsec: TestParsec<Transaction, PeerId>,
+        behaviour_type: MaliciousBehaviourType,
+        rng: Box<dyn Rng + Send>,
+    ) -> Self {
+        Self {
+            test_parsec,
+            behaviour_type,
+            behaviour: behaviour_type.create(rng),
         }
     }
 }
@@ -226,8 +138,29 @@ impl DerefMut for WrappedParsec {
     }
 }
 
+// Measurements reported by the `Parsec` instance of a peer through its `Metrics`.
+#[derive(Default)]
+struct ParsecMeasurements {
+    graph_size: usize,
+    meta_election_rounds: Vec<usize>,
+}
+
+#[derive(Clone, Default)]
+struct MetricsRecorder(Arc<Mutex<ParsecMeasurements>>);
+
+impl Metrics for MetricsRecorder {
+    fn event_added(&self, graph_size: usize) {
+        unwrap!(self.0.lock()).graph_size = graph_size;
+    }
+
+    fn consensus_reached(&self, rounds: usize, _num_payloads: usize) {
+        unwrap!(self.0.lock()).meta_election_rounds.push(rounds);
+    }
+}
+
 pub struct Peer {
     parsec: WrappedParsec,
+    metrics: MetricsRecorder,
     /// The blocks returned by `parsec.poll()`, held in the order in which they were returned.
     grouped_blocks: Vec<BlockGroup<Transaction, PeerId>>,
     status: PeerStatus,
@@ -244,7 +177,7 @@ impl Peer {
         id: PeerId,
         genesis_group: &BTreeSet<PeerId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn Rng>,
+        secure_rng: Box<dyn Rng + Send>,
     ) -> Self {
         Self::new(WrappedParsec::Good(Parsec::from_genesis(
             id,
@@ -259,12 +192,15 @@ impl Peer {
         id: PeerId,
         genesis_group: &BTreeSet<PeerId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn Rng>,
+        secure_rng: Box<dyn Rng + Send>,
+        behaviour_type: MaliciousBehaviourType,
+        behaviour_rng: Box<dyn Rng + Send>,
     ) -> Self {
-        Self::new(WrappedParsec::Malicious(MaliciousComponents {
-            test_parsec: TestParsec::from_genesis(id, genesis_group, consensus_mode, secure_rng),
-            forked_event: None,
-        }))
+        Self::new(WrappedParsec::Malicious(MaliciousComponents::new(
+            TestParsec::from_genesis(id, genesis_group, consensus_mode, secure_rng),
+            behaviour_type,
+            behaviour_rng,
+        )))
     }
 
     pub fn from_existing(
@@ -272,7 +208,7 @@ impl Peer {
         genesis_group: &BTreeSet<PeerId>,
         current_group: &BTreeSet<PeerId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn Rng>,
+        secure_rng: Box<dyn Rng + Send>,
     ) -> Self {
         Self::new(WrappedParsec::Good(Parsec::from_existing(
             id,
@@ -288,28 +224,28 @@ impl Peer {
         genesis_group: &BTreeSet<PeerId>,
         current_group: &BTreeSet<PeerId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn Rng>,
+        secure_rng: Box<dyn Rng + Send>,
+        behaviour_type: MaliciousBehaviourType,
+        behaviour_rng: Box<dyn Rng + Send>,
     ) -> Self {
-        Self::new(WrappedParsec::Malicious(MaliciousComponents {
-            test_parsec: TestParsec::from_existing(
-                id,
-                genesis_group,
-                current_group,
-                consensus_mode,
-                secure_rng,
-            ),
-            forked_event: None,
-        }))
-    }
-
-    fn new(parsec: WrappedParsec) -> Self {
+        Self::new(WrappedParsec::Malicious(MaliciousComponents::new(
+            TestParsec::from_existing(id, genesis_group, current_group, consensus_mode, secure_rng),
+            behaviour_type,
+            behaviour_rng,
+        )))
+    }
+
+    fn new(mut parsec: WrappedParsec) -> Self {
         let (status, network_view) = if parsec.can_vote() {
             (PeerStatus::Active, NetworkView::Joined)
         } else {
             (PeerStatus::Pending, NetworkView::Joining)
         };
+        let metrics = MetricsRecorder::default();
+        parsec.set_metrics(Box::new(metrics.clone()));
         Self {
             parsec,
+            metrics,
             grouped_blocks: vec![],
             status,
             network_view,
@@ -333,16 +269,35 @@ impl Peer {
         self.parsec.gossip_recipients()
     }
 
-    pub fn create_gossip(&mut self, peer_id: &PeerId) -> Result<Request<Transaction, PeerId>> {
-        self.parsec.create_gossip(peer_id)
+    /// Returns the requests to send to `peer_id`.  A malicious peer may return any number of them,
+    /// even if `peer_id` isn't a valid gossip recipient.
+    pub fn create_gossip(&mut self, peer_id: &PeerId) -> Result<Vec<Request<Transaction, PeerId>>> {
+        match self.parsec {
+            WrappedParsec::Good(ref mut parsec) => {
+                parsec.create_gossip(peer_id).map(|req| vec![req])
+            }
+            WrappedParsec::Malicious(MaliciousComponents {
+                ref mut test_parsec,
+                ref mut behaviour,
+                ..
+            }) => behaviour.create_gossip(test_parsec, peer_id),
+        }
     }
 
+    /// Returns the response to send back to `src`, if any.
     pub fn handle_request(
         &mut self,
         src: &PeerId,
         req: Request<Transaction, PeerId>,
-    ) -> Result<Response<Transaction, PeerId>> {
-        self.parsec.handle_request(src, req)
+    ) -> Result<Option<Response<Transaction, PeerId>>> {
+        match self.parsec {
+            WrappedParsec::Good(ref mut parsec) => parsec.handle_request(src, req).map(Some),
+            WrappedParsec::Malicious(MaliciousComponents {
+                ref mut test_parsec,
+                ref mut behaviour,
+                ..
+            }) => behaviour.handle_request(test_parsec, src, req),
+        }
     }
 
     pub fn handle_response(
@@ -350,7 +305,14 @@ impl Peer {
         src: &PeerId,
         resp: Response<Transaction, PeerId>,
     ) -> Result<()> {
-        self.parsec.handle_response(src, resp)
+        match self.parsec {
+            WrappedParsec::Good(ref mut parsec) => parsec.handle_response(src, resp),
+            WrappedParsec::Malicious(MaliciousComponents {
+                ref mut test_parsec,
+                ref mut behaviour,
+                ..
+            }) => behaviour.handle_response(test_parsec, src, resp),
+        }
     }
 
     fn make_active_if_added(&mut self, block: &Block<Transaction, PeerId>) {
@@ -396,6 +358,16 @@ impl Peer {
         self.grouped_blocks.iter().flatten()
     }
 
+    /// Number of events in the gossip graph, as of the last one added.
+    pub fn graph_size(&self) -> usize {
+        unwrap!(self.metrics.0.lock()).graph_size
+    }
+
+    /// Number of meta-election rounds taken by each decision so far.
+    pub fn meta_election_rounds(&self) -> Vec<usize> {
+        unwrap!(self.metrics.0.lock()).meta_election_rounds.clone()
+    }
+
     pub fn status(&self) -> PeerStatus {
         self.status
     }
@@ -518,44 +490,56 @@ impl Peer {
         }
     }
 
+    /// Returns the behaviour of this peer if it is malicious.
+    pub fn malicious_behaviour(&self) -> Option<MaliciousBehaviourType> {
+        match self.parsec {
+            WrappedParsec::Good(..) => None,
+            WrappedParsec::Malicious(ref malicious_components) => {
+                Some(malicious_components.behaviour_type)
+            }
+        }
+    }
+
     pub fn has_misbehaved(&self) -> bool {
         match self.parsec {
             WrappedParsec::Good(..) => false,
             WrappedParsec::Malicious(ref malicious_components) => {
-                malicious_components.forked_event.is_some()
+                malicious_components.behaviour.has_misbehaved()
             }
         }
     }
 
-    /// This will create a `Request` where the final `Requesting` sync event is a fork.
-    ///
-    /// First we create a `Requesting` event to a random peer other than `recipient_id`, then we
-    /// create a `Requesting` fork to `recipient_id`.
-    ///
-    /// Panics if this peer is not malicious.
-    pub fn create_gossip_with_fork<R: Rng>(
-        &mut self,
-        recipient_id: &PeerId,
-        rng: &mut R,
-    ) -> Result<Request<Transaction, PeerId>> {
-        self.malicious_components_mut()
-            .create_gossip_with_fork(recipient_id, rng)
+    /// Returns whether honest peers are right to accuse this peer of `malice`.
+    pub fn is_expected_accusation(&self, malice: &Malice<Transaction, PeerId>) -> bool {
+        match self.parsec {
+            WrappedParsec::Good(..) => false,
+            WrappedParsec::Malicious(ref malicious_components) => {
+                malicious_components.behaviour.has_misbehaved()
+                    && malicious_components
+                        .behaviour
+                        .is_expected_accusation(malice)
+            }
+        }
     }
 
-    fn malicious_components_mut(&mut self) -> &mut MaliciousComponents {
+    /// Returns whether honest peers are right to reject this peer's messages with `error`.
+    pub fn is_expected_rejection(&self, error: &Error) -> bool {
         match self.parsec {
-            WrappedParsec::Good(..) => panic!("{:?} is not a malicious test peer", self.id()),
-            WrappedParsec::Malicious(ref mut malicious_components) => malicious_components,
+            WrappedParsec::Good(..) => false,
+            WrappedParsec::Malicious(ref malicious_components) => {
+                malicious_components.behaviour.has_misbehaved()
+                    && malicious_components.behaviour.is_expected_rejection(error)
+            }
         }
     }
 }
 
 impl Debug for Peer {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        let malicious = if self.is_malicious() {
-            "(MALICIOUS) "
+        let malicious = if let Some(behaviour) = self.malicious_behaviour() {
+            format!("(MALICIOUS: {:?}) ", behaviour)
         } else {
-            ""
+            String::new()
         };
         write!(
             formatter,
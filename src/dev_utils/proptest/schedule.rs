@@ -73,7 +73,7 @@ impl Default for ScheduleOptionsStrategy {
             recv_trans: Just(0.05).into(),
             failure: Just(0.0).into(),
             vote_duplication: Just(0.0).into(),
-            delay_distr: Just(DelayDistribution::Poisson(4.0)).into(),
+            delay_distr: Just(DelayDistribution::Poisson(40.0)).into(),
         }
     }
 }
@@ -37,7 +37,7 @@ pub fn new_common_rng(seed: RngChoice) -> Box<dyn RngDebug> {
 }
 
 /// Create a new RNG using a seed generated from random data provided by `rng`.
-pub fn new_rng<R: Rng>(rng: &mut R) -> Box<dyn Rng> {
+pub fn new_rng<R: Rng>(rng: &mut R) -> Box<dyn Rng + Send> {
     let new_seed = [
         rng.next_u32().wrapping_add(rng.next_u32()),
         rng.next_u32().wrapping_add(rng.next_u32()),
@@ -0,0 +1,237 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{peer::Peer, schedule::VirtualClock, Observation};
+use crate::{mock::PeerId, observation::is_more_than_two_thirds};
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Identifies a block across peers: its payload, along with its voter in `ConsensusMode::Single`.
+pub(crate) type BlockKey = (Observation, Option<PeerId>);
+
+/// Measurements taken while executing a schedule, returned by
+/// `Environment::execute_schedule` on success.  Time is measured in simulated milliseconds, as per
+/// `ScheduleOptions::clock`.
+#[derive(Clone, Debug, Default)]
+pub struct PerformanceReport {
+    /// The blocks consensused by any peer, in the order in which they were first polled.
+    pub blocks: Vec<BlockLatency>,
+    /// Number of gossip requests sent.
+    pub requests: usize,
+    /// Number of gossip responses sent.
+    pub responses: usize,
+    /// Total size of the requests sent, once serialised.
+    pub request_bytes: u64,
+    /// Total size of the responses sent, once serialised.
+    pub response_bytes: u64,
+    /// Number of events in the gossip graph of each peer at the end of the simulation.
+    pub graph_sizes: BTreeMap<PeerId, usize>,
+    /// Number of meta-election rounds taken by each decision of each peer, in order.  Zero means
+    /// the decision didn't require any meta-voting.
+    pub meta_election_rounds: BTreeMap<PeerId, Vec<usize>>,
+    /// Simulated time of the last local step, in milliseconds.
+    pub end_time_ms: u64,
+}
+
+impl PerformanceReport {
+    /// Mean time in milliseconds between the first vote for a block's payload and its consensus, over
+    /// all the blocks voted for and all the peers which polled them, or `None` if there are no
+    /// such blocks.
+    pub fn mean_latency(&self) -> Option<f64> {
+        let latencies: Vec<_> = self
+            .blocks
+            .iter()
+            .flat_map(|block| block.latencies().map(|(_, latency)| latency))
+            .collect();
+        if latencies.is_empty() {
+            None
+        } else {
+            Some(latencies.iter().sum::<u64>() as f64 / latencies.len() as f64)
+        }
+    }
+
+    /// Number of blocks consensused per simulated second, or `None` if no time has elapsed.
+    pub fn blocks_per_second(&self) -> Option<f64> {
+        if self.end_time_ms == 0 {
+            None
+        } else {
+            Some(self.blocks.len() as f64 * 1000.0 / self.end_time_ms as f64)
+        }
+    }
+
+    /// Total size of the messages sent, once serialised.
+    pub fn total_bytes(&self) -> u64 {
+        self.request_bytes + self.response_bytes
+    }
+}
+
+/// Latency of a single block, as part of a [PerformanceReport](struct.PerformanceReport.html).
+#[derive(Clone, Debug)]
+pub struct BlockLatency {
+    /// Payload of the block.
+    pub payload: Observation,
+    /// Voter of the block in `ConsensusMode::Single`, if its payload is opaque.
+    pub voter: Option<PeerId>,
+    /// Time in milliseconds at which the first vote for the payload was made, or `None` if it
+    /// isn't voted for by the schedule, e.g. for genesis or accusations.
+    pub first_vote_time_ms: Option<u64>,
+    /// Time in milliseconds at which each peer polled the block.
+    pub consensus_times_ms: BTreeMap<PeerId, u64>,
+}
+
+impl BlockLatency {
+    /// Time in milliseconds between the first vote and the consensus, for each peer which polled
+    /// the block.  Empty if the block isn't voted for by the schedule.
+    pub fn latencies(&self) -> impl Iterator<Item = (&PeerId, u64)> {
+        let first_vote_time_ms = self.first_vote_time_ms;
+        self.consensus_times_ms
+            .iter()
+            .filter_map(move |(peer_id, time_ms)| {
+                first_vote_time_ms
+                    .map(|first_vote_time_ms| (peer_id, time_ms.saturating_sub(first_vote_time_ms)))
+            })
+    }
+}
+
+// Accumulates the measurements made by the test network until the report is produced.
+#[derive(Default)]
+pub(crate) struct Measurements {
+    clock: VirtualClock,
+    current_step: usize,
+    // Votes scheduled since the last local step, in which they will be made.
+    pending_votes: Vec<(BlockKey, PeerId)>,
+    first_votes: BTreeMap<BlockKey, usize>,
+    // The step in which each voter voted for each block.
+    votes: BTreeMap<BlockKey, BTreeMap<PeerId, usize>>,
+    blocks: Vec<BlockLatency>,
+    block_indices: BTreeMap<BlockKey, usize>,
+    requests: usize,
+    responses: usize,
+    request_bytes: u64,
+    response_bytes: u64,
+}
+
+impl Measurements {
+    pub fn new(clock: VirtualClock) -> Self {
+        Measurements {
+            clock,
+            ..Default::default()
+        }
+    }
+
+    pub fn local_step(&mut self, step: usize) {
+        self.current_step = step;
+        for (key, voter) in self.pending_votes.drain(..) {
+            let _ = self.first_votes.entry(key.clone()).or_insert(step);
+            let _ = self
+                .votes
+                .entry(key)
+                .or_insert_with(BTreeMap::new)
+                .entry(voter)
+                .or_insert(step);
+        }
+    }
+
+    // Simulated time of the latest local step, in milliseconds.
+    pub fn now(&self) -> u64 {
+        self.clock.time_at(self.current_step)
+    }
+
+    pub fn vote(&mut self, key: BlockKey, voter: &PeerId) {
+        self.pending_votes.push((key, voter.clone()));
+    }
+
+    // Returns the observations which more than two thirds of `honest` peers had voted for before
+    // `step`, or any of them for blocks with a single voter, but which some of them haven't polled
+    // yet.  Internal observations are skipped, as they never make it into blocks.
+    pub fn unpolled_votes(&self, step: usize, honest: &BTreeSet<PeerId>) -> Vec<Observation> {
+        let unpolled_keys = self
+            .votes
+            .iter()
+            .filter(|((observation, _), _)| !observation.is_internal())
+            .filter(|((_, single_voter), voters)| {
+                let num_voted = voters
+                    .iter()
+                    .filter(|(peer_id, vote_step)| **vote_step < step && honest.contains(*peer_id))
+                    .count();
+                if single_voter.is_some() {
+                    num_voted > 0
+                } else {
+                    is_more_than_two_thirds(num_voted, honest.len())
+                }
+            })
+            .map(|(key, _)| key)
+            .filter(|key| {
+                self.block_indices.get(*key).map_or(true, |index| {
+                    honest.iter().any(|peer_id| {
+                        !self.blocks[*index].consensus_times_ms.contains_key(peer_id)
+                    })
+                })
+            });
+        unpolled_keys
+            .map(|(observation, _)| observation.clone())
+            .dedup()
+            .collect()
+    }
+
+    pub fn block_polled(&mut self, key: BlockKey, peer_id: &PeerId) {
+        let index = if let Some(index) = self.block_indices.get(&key) {
+            *index
+        } else {
+            let (payload, voter) = key.clone();
+            self.blocks.push(BlockLatency {
+                first_vote_time_ms: self
+                    .first_votes
+                    .get(&key)
+                    .map(|step| self.clock.time_at(*step)),
+                payload,
+                voter,
+                consensus_times_ms: BTreeMap::new(),
+            });
+            let _ = self.block_indices.insert(key, self.blocks.len() - 1);
+            self.blocks.len() - 1
+        };
+        let now = self.now();
+        let _ = self.blocks[index]
+            .consensus_times_ms
+            .insert(peer_id.clone(), now);
+    }
+
+    pub fn request_sent(&mut self, bytes: u64) {
+        self.requests += 1;
+        self.request_bytes += bytes;
+    }
+
+    pub fn response_sent(&mut self, bytes: u64) {
+        self.responses += 1;
+        self.response_bytes += bytes;
+    }
+
+    pub fn report<'a, I: IntoIterator<Item = &'a Peer>>(&self, peers: I) -> PerformanceReport {
+        let (graph_sizes, meta_election_rounds) = peers
+            .into_iter()
+            .map(|peer| {
+                (
+                    (peer.id().clone(), peer.graph_size()),
+                    (peer.id().clone(), peer.meta_election_rounds()),
+                )
+            })
+            .unzip();
+
+        PerformanceReport {
+            blocks: self.blocks.clone(),
+            requests: self.requests,
+            responses: self.responses,
+            request_bytes: self.request_bytes,
+            response_bytes: self.response_bytes,
+            graph_sizes,
+            meta_election_rounds,
+            end_time_ms: self.now(),
+        }
+    }
+}
@@ -6,21 +6,30 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{Environment, Observation, PeerStatus, PeerStatuses};
+use super::malicious_behaviour::Detection;
+use super::{Environment, MaliciousBehaviourType, Observation, PeerStatus, PeerStatuses};
 #[cfg(feature = "dump-graphs")]
 use crate::dump_graph::DIR;
 use crate::{
     mock::{PeerId, Transaction, NAMES},
-    observation::{ConsensusMode, Observation as ParsecObservation},
+    observation::{is_more_than_two_thirds, ConsensusMode, Observation as ParsecObservation},
 };
 use itertools::Itertools;
 use rand::{seq, Rng};
+#[cfg(feature = "json")]
+use serde_json::{json, Map, Value};
+#[cfg(feature = "dump-graphs")]
+use std::fs::File;
+#[cfg(all(feature = "dump-graphs", feature = "json"))]
+use std::io::BufWriter;
+#[cfg(any(feature = "dump-graphs", feature = "json"))]
+use std::io::Write;
+#[cfg(feature = "json")]
+use std::io::{self, Read};
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt, iter, mem,
 };
-#[cfg(feature = "dump-graphs")]
-use std::{fs::File, io::Write};
 
 /// This struct holds the data necessary to make a simulated request when a node executes a local
 /// step.
@@ -34,7 +43,7 @@ pub struct Request {
     pub resp_delay: usize,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Genesis {
     ids_of_good_peers: BTreeSet<PeerId>,
     ids_of_malicious_peers: BTreeSet<PeerId>,
@@ -99,7 +108,7 @@ impl Genesis {
 }
 
 /// Role of new peer: voter or DKG.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AddPeerType {
     Voter,
     Dkg,
@@ -109,7 +118,7 @@ pub enum AddPeerType {
 /// The simulation proceeds in steps. During every global step, every node has some probability
 /// of being scheduled to perform a local step, consisting of receiving messages that reached it
 /// by this time, generating appropriate responses and optionally sending a gossip request.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[allow(clippy::large_enum_variant)]
 pub enum ScheduleEvent {
     /// Event storing the names of the initial nodes
@@ -128,6 +137,19 @@ pub enum ScheduleEvent {
     /// It is similar to Fail in that the peer will stop responding; however, this will also
     /// cause the other peers to vote for removal
     RemovePeer(PeerId),
+    /// Splits the network into the given groups of peers, which can only communicate within their
+    /// group.  Peers not listed in any group form another group together.  Messages across the
+    /// partition, including those already in flight, are held until it heals, or dropped if
+    /// `ScheduleOptions::drop_partitioned_messages` is set.
+    Partition(Vec<BTreeSet<PeerId>>),
+    /// Ends the current partition, if any.
+    Heal,
+    /// Makes the first peer send a gossip request to the second one.  Unlike those sent during
+    /// local steps, the request is only delivered by a matching `Deliver` event.
+    Gossip(PeerId, PeerId),
+    /// Makes the second peer handle the oldest message in flight to it from the first one, if
+    /// any.  Responses it sends are only delivered by a matching `Deliver` event in turn.
+    Deliver(PeerId, PeerId),
 }
 
 impl ScheduleEvent {
@@ -139,6 +161,16 @@ impl ScheduleEvent {
         }
     }
 
+    /// Returns true if the event makes a peer stop responding or splits the network.
+    pub fn is_fault(&self) -> bool {
+        match *self {
+            ScheduleEvent::Fail(_) | ScheduleEvent::RemovePeer(_) | ScheduleEvent::Partition(_) => {
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub fn get_peer(&self) -> &PeerId {
         match *self {
             ScheduleEvent::LocalStep(_) => panic!("ScheduleEvent::get_peer called on LocalStep!"),
@@ -147,6 +179,12 @@ impl ScheduleEvent {
             ScheduleEvent::AddPeer(ref peer, _) => peer,
             ScheduleEvent::RemovePeer(ref peer) => peer,
             ScheduleEvent::Genesis(_) => panic!("ScheduleEvent::get_peer called on Genesis!"),
+            ScheduleEvent::Partition(_) => {
+                panic!("ScheduleEvent::get_peer called on Partition!")
+            }
+            ScheduleEvent::Heal => panic!("ScheduleEvent::get_peer called on Heal!"),
+            ScheduleEvent::Gossip(ref sender, _) => sender,
+            ScheduleEvent::Deliver(_, ref recipient) => recipient,
         }
     }
 }
@@ -226,14 +264,139 @@ impl PendingObservations {
 }
 
 /// Available options for the distribution of message delays
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum DelayDistribution {
     Poisson(f64),
     Constant(usize),
 }
 
+impl DelayDistribution {
+    /// Generates a delay according to this distribution
+    pub fn gen<R: Rng>(self, rng: &mut R) -> usize {
+        match self {
+            DelayDistribution::Poisson(lambda) => poisson(rng, lambda),
+            DelayDistribution::Constant(x) => x,
+        }
+    }
+}
+
+/// Latencies between peers spread across regions, as configured by `ScheduleOptions`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Topology {
+    /// Groups of peers located in the same region.  Peers not listed in any region form another
+    /// region together.
+    pub regions: Vec<BTreeSet<PeerId>>,
+    /// The distribution of delays of messages between peers in the same region
+    pub intra_region_delay: DelayDistribution,
+    /// The distribution of delays of messages between peers in different regions
+    pub inter_region_delay: DelayDistribution,
+    /// The distributions of delays of messages between specific pairs of peers, in both
+    /// directions, overriding those of their regions
+    #[serde(with = "pairs")]
+    pub links: BTreeMap<(PeerId, PeerId), DelayDistribution>,
+}
+
+impl Topology {
+    /// Creates a topology where the delays only depend on whether the peers share a region.
+    pub fn with_regions(
+        regions: Vec<BTreeSet<PeerId>>,
+        intra_region_delay: DelayDistribution,
+        inter_region_delay: DelayDistribution,
+    ) -> Self {
+        Topology {
+            regions,
+            intra_region_delay,
+            inter_region_delay,
+            links: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the distribution of delays of messages between the two given peers.
+    pub fn link_delay(&self, src: &PeerId, dst: &PeerId) -> DelayDistribution {
+        if let Some(delay) = self
+            .links
+            .get(&(src.clone(), dst.clone()))
+            .or_else(|| self.links.get(&(dst.clone(), src.clone())))
+        {
+            *delay
+        } else if self.region_of(src) == self.region_of(dst) {
+            self.intra_region_delay
+        } else {
+            self.inter_region_delay
+        }
+    }
+
+    fn region_of(&self, peer_id: &PeerId) -> Option<usize> {
+        self.regions
+            .iter()
+            .position(|region| region.contains(peer_id))
+    }
+}
+
+/// Maps global steps to simulated time, so that the simulation can be configured and its results
+/// reported in milliseconds rather than steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VirtualClock {
+    /// Simulated duration of a global step, in milliseconds
+    pub step_duration_ms: u64,
+}
+
+impl VirtualClock {
+    /// Creates a clock for which every global step lasts `step_duration_ms`.
+    pub fn new(step_duration_ms: u64) -> Self {
+        assert!(step_duration_ms > 0, "Global steps must last some time");
+        VirtualClock { step_duration_ms }
+    }
+
+    /// Simulated time at the start of the given global step, in milliseconds.
+    pub(crate) fn time_at(&self, step: usize) -> u64 {
+        step as u64 * self.step_duration_ms
+    }
+
+    /// Number of global steps needed for the given duration to elapse, rounded up.
+    pub(crate) fn steps(&self, duration_ms: u64) -> usize {
+        ((duration_ms + self.step_duration_ms - 1) / self.step_duration_ms) as usize
+    }
+
+    /// Probability per global step that a peer gossips, for it to gossip every `period_ms` on
+    /// average.  Peers can't gossip more often than once per step.
+    pub(crate) fn gossip_probability(&self, period_ms: u64) -> f64 {
+        (self.step_duration_ms as f64 / period_ms as f64).min(1.0)
+    }
+
+    /// Converts a distribution of message delays in milliseconds into one in global steps.
+    pub(crate) fn delay(&self, distribution_ms: DelayDistribution) -> DelayDistribution {
+        match distribution_ms {
+            DelayDistribution::Poisson(mean_ms) => {
+                DelayDistribution::Poisson(mean_ms / self.step_duration_ms as f64)
+            }
+            DelayDistribution::Constant(delay_ms) => {
+                DelayDistribution::Constant(self.steps(delay_ms as u64))
+            }
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new(10)
+    }
+}
+
+/// A network partition, as scheduled by `ScheduleOptions`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Partition {
+    /// The simulated time at which the network splits, in milliseconds
+    pub start_time_ms: u64,
+    /// The simulated time at which the network heals, in milliseconds
+    pub heal_time_ms: u64,
+    /// Groups of peers which can only communicate within their group.  Peers not listed in any
+    /// group form another group together.
+    pub groups: Vec<BTreeSet<PeerId>>,
+}
+
 /// A struct aggregating the options controlling schedule generation
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ScheduleOptions {
     /// Size of the genesis group
     pub genesis_size: usize,
@@ -243,12 +406,20 @@ pub struct ScheduleOptions {
     pub prob_failure: f64,
     /// Probability that a vote will get repeated
     pub prob_vote_duplication: f64,
-    /// A map: step number → num of nodes to fail
-    pub deterministic_failures: BTreeMap<usize, usize>,
-    /// The distribution of message delays
+    /// A map: simulated time in milliseconds → num of nodes to fail
+    pub deterministic_failures: BTreeMap<u64, usize>,
+    /// The distribution of message delays in milliseconds, for all pairs of peers unless
+    /// `topology` is set
     pub delay_distr: DelayDistribution,
-    /// The probability that a node will gossip during its local step
-    pub prob_gossip: f64,
+    /// The latencies in milliseconds between each pair of peers, if they aren't all the same
+    pub topology: Option<Topology>,
+    /// The mean time between two gossips of a node, in milliseconds
+    pub gossip_period_ms: u64,
+    /// If set, the probability per global step that a node gossips, overriding
+    /// `gossip_period_ms`.  Schedules saved before the virtual clock are loaded with it set, as
+    /// their probabilities can't generally be expressed as a whole period.
+    #[serde(default)]
+    pub prob_gossip: Option<f64>,
     /// When true, nodes will first insert all votes into the graph, then start gossiping
     pub votes_before_gossip: bool,
     /// Number of opaque observations to make
@@ -283,15 +454,58 @@ pub struct ScheduleOptions {
     pub genesis_restrict_consensus_to: Option<BTreeSet<PeerId>>,
     /// Allows for voting for the same OpaquePayload. This applies only when `ConsensusMode::Single`
     pub vote_for_same: bool,
+    /// Behaviours of the malicious genesis peers, in the order of their IDs.  Malicious peers
+    /// beyond the end of the list fork.
+    ///
+    /// Without the `malice-detection` feature, Parsec only detects `Spam`, by rejecting the
+    /// duplicate messages.  Every other behaviour is then expected to go undetected, so the check
+    /// that malice was detected only checks something for `Spam`, and the other behaviours are only
+    /// checked not to prevent consensus.
+    pub malicious_behaviours: Vec<MaliciousBehaviourType>,
+    /// Network partitions, which must not overlap
+    pub partitions: Vec<Partition>,
+    /// When true, messages sent across a partition are dropped rather than held until it heals
+    pub drop_partitioned_messages: bool,
+    /// Probability that a request or response will get lost
+    pub prob_message_drop: f64,
+    /// Probability that a request or response will get delivered twice
+    pub prob_message_duplication: f64,
+    /// Probability per step that a message due for delivery will be held back, letting messages
+    /// sent after it overtake it
+    pub prob_message_reorder: f64,
+    /// The duration of global steps, into which the durations of the other options are converted
+    #[serde(default)]
+    pub clock: VirtualClock,
+    /// If set, the maximum time in milliseconds within which every observation voted for by more
+    /// than two thirds of the honest peers (or by any of them, for opaque observations in
+    /// `ConsensusMode::Single`) has to be consensused by every honest peer once faults have
+    /// stopped, or once it got these votes if that's later
+    pub liveness_bound_ms: Option<u64>,
 }
 
 impl ScheduleOptions {
-    /// Generates a delay according to the delay distribution
-    pub fn gen_delay<R: Rng>(&self, rng: &mut R) -> usize {
-        match self.delay_distr {
-            DelayDistribution::Poisson(lambda) => poisson(rng, lambda),
-            DelayDistribution::Constant(x) => x,
-        }
+    /// Generates the delay of a message from `src` to `dst` according to the delay distribution
+    /// of their link
+    pub fn gen_delay<R: Rng>(&self, rng: &mut R, src: &PeerId, dst: &PeerId) -> usize {
+        let delay_distr = self
+            .topology
+            .as_ref()
+            .map_or(self.delay_distr, |topology| topology.link_delay(src, dst));
+        self.clock.delay(delay_distr).gen(rng)
+    }
+
+    /// The probability that a node will gossip during its local step
+    pub(crate) fn prob_gossip(&self) -> f64 {
+        self.prob_gossip
+            .unwrap_or_else(|| self.clock.gossip_probability(self.gossip_period_ms))
+    }
+
+    /// Returns the behaviour of the malicious genesis peer with the given position.
+    pub fn malicious_behaviour(&self, index: usize) -> MaliciousBehaviourType {
+        self.malicious_behaviours
+            .get(index)
+            .cloned()
+            .unwrap_or(MaliciousBehaviourType::Fork)
     }
 }
 
@@ -308,10 +522,13 @@ impl Default for ScheduleOptions {
             prob_vote_duplication: 0.0,
             // no deterministic failures
             deterministic_failures: BTreeMap::new(),
-            // randomised delays, 4 steps on average
-            delay_distr: DelayDistribution::Poisson(4.0),
-            // gossip every so often
-            prob_gossip: 0.05,
+            // randomised delays, 40ms on average
+            delay_distr: DelayDistribution::Poisson(40.0),
+            // same delay distribution between all peers
+            topology: None,
+            // gossip every 200ms on average
+            gossip_period_ms: 200,
+            prob_gossip: None,
             // vote while gossiping
             votes_before_gossip: false,
             // add 5 opaque observations
@@ -341,6 +558,21 @@ impl Default for ScheduleOptions {
             intermediate_consistency_checks: true,
             genesis_restrict_consensus_to: None,
             vote_for_same: false,
+            malicious_behaviours: vec![],
+            // no partitions
+            partitions: vec![],
+            // hold messages across partitions until they heal
+            drop_partitioned_messages: false,
+            // no message loss
+            prob_message_drop: 0.0,
+            // no message duplication
+            prob_message_duplication: 0.0,
+            // no message reordering
+            prob_message_reorder: 0.0,
+            // 10ms per step
+            clock: VirtualClock::default(),
+            // no liveness checks
+            liveness_bound_ms: None,
         }
     }
 }
@@ -436,11 +668,12 @@ impl ObservationSchedule {
                 }
             }
             // then handle deterministic failures
-            let num_deterministic_fails = options
+            let num_deterministic_fails: usize = options
                 .deterministic_failures
-                .get(&step)
-                .cloned()
-                .unwrap_or(0);
+                .iter()
+                .filter(|(time_ms, _)| options.clock.steps(**time_ms) == step)
+                .map(|(_, count)| count)
+                .sum();
 
             for _ in 0..num_deterministic_fails {
                 if let Some(id) = peers.fail_random_peer(rng, options.min_peers) {
@@ -479,14 +712,14 @@ impl ObservationSchedule {
             .count()
     }
 
-    fn count_expected_accusations(&self) -> usize {
-        if cfg!(feature = "malice-detection") {
-            // One accusation per malicious peer as currently the malicious peers commit only one
-            // malice each.
-            self.genesis.ids_of_malicious_peers.len()
-        } else {
-            0
-        }
+    fn count_expected_accusations(&self, options: &ScheduleOptions) -> usize {
+        // One accusation per malicious peer whose behaviour is punished by an accusation, as
+        // currently the malicious peers commit only one malice each.
+        (0..self.genesis.ids_of_malicious_peers.len())
+            .filter(|index| {
+                options.malicious_behaviour(*index).detection() == Detection::Accusation
+            })
+            .count()
     }
 }
 
@@ -504,14 +737,18 @@ impl StepObservationSchedule {
 }
 
 /// Stores the list of network events to be simulated.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Schedule {
+    #[serde(with = "pairs")]
     pub peers: BTreeMap<PeerId, PeerStatus>,
     pub min_observations: usize,
     pub max_observations: usize,
     pub events: Vec<ScheduleEvent>,
     pub additional_steps: std::ops::Range<usize>,
     pub options: ScheduleOptions,
+    /// Seed of the RNGs used to execute the schedule, which decide which peers gossip to which
+    /// and the delays of their messages.
+    pub seed: [u32; 4],
 }
 
 impl fmt::Debug for Schedule {
@@ -525,6 +762,76 @@ impl fmt::Debug for Schedule {
     }
 }
 
+/// Version of the format in which `Schedule::write` saves schedules.  Schedules saved without a
+/// version predate the virtual clock, and express all durations in global steps.
+#[cfg(feature = "json")]
+const FORMAT_VERSION: u64 = 1;
+
+#[cfg(feature = "json")]
+impl Schedule {
+    /// Writes the schedule in JSON, e.g. to a file, so that it can be replayed by passing the
+    /// result of `load` to `Environment::execute_schedule`.  The schedule includes the seed of
+    /// its execution, so the replay makes the same gossip and delay decisions as the original.
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(fields) = value.as_object_mut() {
+            let _ = fields.insert("format_version".to_string(), FORMAT_VERSION.into());
+        }
+        serde_json::to_writer_pretty(writer, &value).map_err(io::Error::from)
+    }
+
+    /// Loads a schedule previously written by `write`, including by earlier versions of this
+    /// crate.
+    pub fn load<R: Read>(reader: R) -> serde_json::Result<Self> {
+        let mut value: Value = serde_json::from_reader(reader)?;
+        let version = value
+            .as_object_mut()
+            .and_then(|fields| fields.remove("format_version"))
+            .map_or(Some(0), |version| version.as_u64());
+        match version {
+            Some(0) => upgrade_from_steps(&mut value),
+            Some(FORMAT_VERSION) => (),
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "unsupported schedule format version {:?}",
+                    version
+                )));
+            }
+        }
+        serde_json::from_value(value)
+    }
+}
+
+// Converts a schedule saved before the virtual clock into the current format.  With steps of 1ms,
+// the durations in steps carry over unchanged, while the probability of gossiping per step is
+// kept as is rather than converted into a period.
+#[cfg(feature = "json")]
+fn upgrade_from_steps(schedule: &mut Value) {
+    let options = match schedule.get_mut("options").and_then(Value::as_object_mut) {
+        Some(options) => options,
+        None => return,
+    };
+    let _ = options.insert("clock".to_string(), json!({ "step_duration_ms": 1 }));
+    if let Some(prob_gossip) = options.get("prob_gossip").and_then(Value::as_f64) {
+        let gossip_period_ms = (1.0 / prob_gossip).round().max(1.0) as u64;
+        let _ = options.insert("gossip_period_ms".to_string(), gossip_period_ms.into());
+    }
+    rename_field(options, "liveness_bound", "liveness_bound_ms");
+    if let Some(partitions) = options.get_mut("partitions").and_then(Value::as_array_mut) {
+        for partition in partitions.iter_mut().filter_map(Value::as_object_mut) {
+            rename_field(partition, "start_step", "start_time_ms");
+            rename_field(partition, "heal_step", "heal_time_ms");
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn rename_field(fields: &mut Map<String, Value>, old_name: &str, new_name: &str) {
+    if let Some(value) = fields.remove(old_name) {
+        let _ = fields.insert(new_name.to_string(), value);
+    }
+}
+
 impl Schedule {
     #[cfg(feature = "dump-graphs")]
     fn save(&self, options: &ScheduleOptions) {
@@ -539,16 +846,37 @@ impl Schedule {
         } else {
             println!("Failed to create {:?}", path);
         }
+
+        #[cfg(feature = "json")]
+        {
+            let path = DIR.with(|dir| dir.join("schedule.json"));
+            if let Ok(file) = File::create(&path) {
+                unwrap!(self.write(BufWriter::new(file)));
+            } else {
+                println!("Failed to create {:?}", path);
+            }
+        }
     }
 
     fn perform_step(
         step: usize,
+        options: &ScheduleOptions,
         peers: &mut PeerStatuses,
         // mut required to be able to use the inner reference in a loop
         mut pending: Option<&mut PendingObservations>,
         schedule: &mut Vec<ScheduleEvent>,
     ) {
-        // First let the peers vote for scheduled observations...
+        // First split or heal the network if scheduled...
+        for partition in &options.partitions {
+            if options.clock.steps(partition.heal_time_ms) == step {
+                schedule.push(ScheduleEvent::Heal);
+            }
+            if options.clock.steps(partition.start_time_ms) == step {
+                schedule.push(ScheduleEvent::Partition(partition.groups.clone()));
+            }
+        }
+
+        // ...then let the peers vote for scheduled observations...
         if let Some(pending) = pending.as_mut() {
             for peer in peers.all_peers() {
                 for observation in pending.pop_at_step(peer, step) {
@@ -561,11 +889,150 @@ impl Schedule {
         schedule.push(ScheduleEvent::LocalStep(step));
     }
 
+    /// Creates a schedule in which the genesis peers all vote for the given observations and then
+    /// don't do anything else, to be extended with `Gossip` and `Deliver` events, e.g. by
+    /// `check_interleavings`.
+    pub fn from_votes(
+        env: &mut Environment,
+        genesis: Genesis,
+        observations: &[Observation],
+        options: &ScheduleOptions,
+    ) -> Schedule {
+        let peers = PeerStatuses::new(&genesis.all_ids());
+        let voters = peers.all_peers().cloned().collect_vec();
+        let observation_multiplier = if env.network.consensus_mode() == ConsensusMode::Single {
+            voters.len()
+        } else {
+            1
+        };
+        // the +1 below is to account for genesis
+        let num_observations = observations.len() * observation_multiplier + 1;
+
+        let mut events = vec![ScheduleEvent::Genesis(genesis)];
+        for observation in observations {
+            for voter in &voters {
+                events.push(ScheduleEvent::VoteFor(voter.clone(), observation.clone()));
+            }
+        }
+
+        Schedule {
+            peers: peers.into(),
+            min_observations: num_observations,
+            max_observations: num_observations,
+            events,
+            additional_steps: 0..0,
+            options: options.clone(),
+            seed: env.rng.gen(),
+        }
+    }
+
     pub fn new(env: &mut Environment, options: &ScheduleOptions) -> Schedule {
         let obs_schedule = ObservationSchedule::gen(&mut env.rng, options);
         Self::from_observation_schedule(env, options, obs_schedule)
     }
 
+    /// Recomputes the expected peers and numbers of blocks from the events of the schedule, e.g.
+    /// after some of them have been removed.
+    ///
+    /// An observation is only required to be consensused if more than two thirds of the active
+    /// peers voted for it, or, for opaque payloads in `ConsensusMode::Single`, if its voter is
+    /// still active.  Added peers are only expected to become active once their `Add` observation
+    /// is required to be consensused.
+    pub fn recompute_expectations(&mut self, consensus_mode: ConsensusMode) {
+        let mut peers = BTreeMap::new();
+        let mut num_accusations = 0;
+        let mut votes: Vec<(&Observation, BTreeSet<&PeerId>)> = vec![];
+        for event in &self.events {
+            match event {
+                ScheduleEvent::Genesis(genesis) => {
+                    peers = genesis
+                        .all_ids()
+                        .into_iter()
+                        .map(|peer_id| (peer_id, PeerStatus::Active))
+                        .collect();
+                    num_accusations = (0..genesis.ids_of_malicious_peers.len())
+                        .filter(|index| {
+                            self.options.malicious_behaviour(*index).detection()
+                                == Detection::Accusation
+                        })
+                        .count();
+                }
+                ScheduleEvent::AddPeer(peer_id, _) => {
+                    let _ = peers.entry(peer_id.clone()).or_insert(PeerStatus::Pending);
+                }
+                ScheduleEvent::RemovePeer(peer_id) => {
+                    if let Some(status) = peers.get_mut(peer_id) {
+                        *status = PeerStatus::Removed;
+                    }
+                }
+                ScheduleEvent::Fail(peer_id) => {
+                    if let Some(status) = peers.get_mut(peer_id) {
+                        *status = PeerStatus::Failed;
+                    }
+                }
+                ScheduleEvent::VoteFor(peer_id, observation) => {
+                    match votes.iter_mut().find(|(voted, _)| *voted == observation) {
+                        Some((_, voters)) => {
+                            let _ = voters.insert(peer_id);
+                        }
+                        None => votes.push((observation, iter::once(peer_id).collect())),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let is_active = |peers: &BTreeMap<PeerId, PeerStatus>, peer_id: &PeerId| {
+            peers.get(peer_id) == Some(&PeerStatus::Active)
+        };
+        let is_required = |peers: &BTreeMap<PeerId, PeerStatus>, voters: &BTreeSet<&PeerId>| {
+            let num_active = peers.keys().filter(|id| is_active(peers, id)).count();
+            let num_voted = voters.iter().filter(|id| is_active(peers, id)).count();
+            is_more_than_two_thirds(num_voted, num_active)
+        };
+
+        // Each peer becoming active may make further observations, and so additions, required.
+        loop {
+            let added = votes
+                .iter()
+                .find_map(|(observation, voters)| match observation {
+                    ParsecObservation::Add { peer_id, .. }
+                        if peers.get(peer_id) == Some(&PeerStatus::Pending)
+                            && is_required(&peers, voters) =>
+                    {
+                        Some(peer_id.clone())
+                    }
+                    _ => None,
+                });
+            match added {
+                Some(peer_id) => {
+                    let _ = peers.insert(peer_id, PeerStatus::Active);
+                }
+                None => break,
+            }
+        }
+
+        // The genesis block and the accusations are always expected.
+        let mut min_observations = 1 + num_accusations;
+        let mut max_observations = 1 + num_accusations;
+        for (observation, voters) in &votes {
+            if consensus_mode == ConsensusMode::Single && observation.is_opaque() {
+                // Each vote is a separate block.
+                max_observations += voters.len();
+                min_observations += voters.iter().filter(|id| is_active(&peers, id)).count();
+            } else {
+                max_observations += 1;
+                if is_required(&peers, voters) {
+                    min_observations += 1;
+                }
+            }
+        }
+
+        self.peers = peers;
+        self.min_observations = min_observations;
+        self.max_observations = max_observations;
+    }
+
     /// Creates a new pseudo-random schedule based on the given options
     ///
     /// The `let_and_return` clippy lint is allowed since it is actually necessary to create the
@@ -586,7 +1053,7 @@ impl Schedule {
             };
         // the +1 below is to account for genesis
         let max_observations = obs_schedule.count_observations() * observation_multiplier
-            + obs_schedule.count_expected_accusations()
+            + obs_schedule.count_expected_accusations(options)
             + 1;
 
         let mut peers = PeerStatuses::new(&obs_schedule.genesis.all_ids());
@@ -743,12 +1210,20 @@ impl Schedule {
                     }
                 }
             }
-            Self::perform_step(step, &mut peers, Some(&mut pending), &mut schedule);
+            Self::perform_step(step, options, &mut peers, Some(&mut pending), &mut schedule);
             step += 1;
         }
 
-        while !pending.queues_empty(peers.all_peers()) {
-            Self::perform_step(step, &mut peers, Some(&mut pending), &mut schedule);
+        // Make sure all the partitions are healed before the additional steps.
+        let last_heal_step = options
+            .partitions
+            .iter()
+            .map(|partition| options.clock.steps(partition.heal_time_ms))
+            .max();
+        while !pending.queues_empty(peers.all_peers())
+            || last_heal_step.map_or(false, |heal_step| step <= heal_step)
+        {
+            Self::perform_step(step, options, &mut peers, Some(&mut pending), &mut schedule);
             step += 1;
         }
 
@@ -756,7 +1231,7 @@ impl Schedule {
         // The constant (adjustment_coeff) is for making the number big enough.
         let non_zero_ln = 2;
         let n = std::cmp::max(peers.present_peers().count(), non_zero_ln) as f64;
-        let adjustment_coeff = 250.0 / options.prob_gossip;
+        let adjustment_coeff = 250.0 / options.prob_gossip();
         let additional_steps = (adjustment_coeff * n.ln()) as usize;
 
         // Peers scheduled for removal / failure might not get a chance to vote for their scheduled
@@ -782,6 +1257,7 @@ impl Schedule {
             events: schedule,
             additional_steps: step..(step + additional_steps),
             options: options.clone(),
+            seed: env.rng.gen(),
         };
         #[cfg(feature = "dump-graphs")]
         result.save(options);
@@ -789,6 +1265,30 @@ impl Schedule {
     }
 }
 
+// Serialises maps as lists of pairs, as JSON only supports string keys.
+mod pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, V, S>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
+    }
+}
+
 // A function generating a Poisson-distributed random number.
 fn poisson<R: Rng>(rng: &mut R, lambda: f64) -> usize {
     let mut result = 0;
@@ -814,7 +1314,7 @@ fn binomial<R: Rng>(rng: &mut R, n: usize, p: f64) -> usize {
     successes
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Sampling {
     // Sample the given amount of items.
     Constant(usize),
@@ -16,7 +16,45 @@ use crate::{
     parsec::KeyGenId,
     peer_list::PeerList,
 };
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, io};
+
+/// Format of the graphs written by [Parsec::write_graph](struct.Parsec.html#method.write_graph).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GraphFormat {
+    /// The dot format understood by Graphviz.  The state of the peer and the details of the events
+    /// are written as comments.
+    Dot,
+    /// JSON serialisation of a [JsonGraph](struct.JsonGraph.html), which can be loaded back with
+    /// [JsonGraph::load](struct.JsonGraph.html#method.load).
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// Which parts of the state of a `Parsec` instance to include in the graphs written by
+/// [Parsec::write_graph](struct.Parsec.html#method.write_graph), besides the gossip graph itself.
+/// Everything is included by default.
+///
+/// Only graphs including everything can be loaded back by the test utilities.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GraphDumpOptions {
+    /// Include the peer list and the state of each peer.
+    pub peer_list: bool,
+    /// Include the meta-elections: interesting content, observers and meta votes.
+    pub meta_elections: bool,
+    /// Include the payloads of the observations.  If not included, observations are only
+    /// identified by their hash.
+    pub observations: bool,
+}
+
+impl Default for GraphDumpOptions {
+    fn default() -> Self {
+        GraphDumpOptions {
+            peer_list: true,
+            meta_elections: true,
+            observations: true,
+        }
+    }
+}
 
 /// Use this to initialise the folder into which the dot files will be dumped.  This allows the
 /// folder's path to be displayed at the start of a run, rather than at the arbitrary point when
@@ -33,18 +71,38 @@ pub enum DumpGraphContext {
     DroppingParsec,
 }
 
-pub(crate) struct ToFileInfo<'a, T: NetworkEvent, S: SecretId> {
-    pub owner_id: &'a S::PublicId,
+pub(crate) struct GraphInfo<'a, T: NetworkEvent, S: SecretId> {
     pub consensus_mode: ConsensusMode,
     pub gossip_graph: &'a Graph<S::PublicId>,
     pub meta_election: &'a MetaElection,
     pub peer_list: &'a PeerList<S>,
     pub observations: &'a ObservationStore<T, S::PublicId>,
+}
+
+pub(crate) struct ToFileInfo<'a, T: NetworkEvent, S: SecretId> {
+    pub owner_id: &'a S::PublicId,
+    pub graph: GraphInfo<'a, T, S>,
     pub secure_rng: &'a ParsecRng,
     pub key_gens_and_next_id: (&'a BTreeMap<KeyGenId, KeyGen<S>>, &'a KeyGenId),
     pub info: &'a DumpGraphContext,
 }
 
+/// Writes the graph in the given format.  Unlike `to_file`, this never includes secrets such as
+/// the values generated by the secure RNG or the state of the key generations, so it is safe to
+/// use in production.
+pub(crate) fn write<T: NetworkEvent, S: SecretId, W: io::Write>(
+    info: &GraphInfo<T, S>,
+    format: GraphFormat,
+    options: GraphDumpOptions,
+    writer: W,
+) -> io::Result<()> {
+    match format {
+        GraphFormat::Dot => dot::write(info, options, writer),
+        #[cfg(feature = "json")]
+        GraphFormat::Json => json::write(info, options, writer),
+    }
+}
+
 /// This function will dump the graphs from the specified peer in dot format to a random folder in
 /// the system's temp dir.  It will also try to create an SVG from each such dot file, but will not
 /// fail or report failure if the SVG files can't be created.  The location of this folder will be
@@ -58,31 +116,34 @@ pub(crate) fn to_file<T: NetworkEvent, S: SecretId>(info: ToFileInfo<T, S>) {
 #[cfg(not(feature = "dump-graphs"))]
 pub(crate) fn to_file<T: NetworkEvent, S: SecretId>(_: ToFileInfo<T, S>) {}
 
+#[cfg(feature = "json")]
+mod json;
+
 #[cfg(feature = "dump-graphs")]
 pub use self::detail::{DumpGraphMode, DIR, DUMP_MODE};
+#[cfg(feature = "json")]
+pub use self::json::{
+    JsonCause, JsonEvent, JsonGraph, JsonMetaElection, JsonMetaEvent, JsonObservation,
+    JsonObserver, JsonPeer,
+};
 
 #[cfg(feature = "dump-graphs")]
 mod detail {
-    use super::{DumpGraphContext, ToFileInfo};
+    use super::{dot, DumpGraphContext, ToFileInfo};
     use crate::{
-        gossip::{Cause, Event, EventIndex, Graph, GraphSnapshot, IndexedEventRef},
-        id::{PublicId, SecretId},
-        key_gen::{parsec_rng::ParsecRng, KeyGen},
-        meta_voting::{MetaElection, MetaElectionSnapshot, MetaEvent, MetaVote, Observer},
+        gossip::{Graph, GraphSnapshot},
+        id::SecretId,
+        meta_voting::{MetaElection, MetaElectionSnapshot},
         network_event::NetworkEvent,
-        observation::{ConsensusMode, Malice, Observation, ObservationKey, ObservationStore},
-        parsec::KeyGenId,
-        peer_list::{PeerIndex, PeerIndexMap, PeerIndexSet, PeerList},
+        peer_list::PeerList,
         serialise,
     };
     use itertools::Itertools;
     use rand::{self, Rng};
     use std::{
         cell::RefCell,
-        cmp,
-        collections::{BTreeMap, BTreeSet},
+        collections::BTreeMap,
         env,
-        fmt::{self, Debug, Formatter},
         fs::{self, File},
         io::{self, BufWriter, Write},
         path::{Path, PathBuf},
@@ -195,7 +256,7 @@ mod detail {
             return;
         }
 
-        let id = sanitise_string(format!("{:?}", info.owner_id));
+        let id = dot::sanitise_string(format!("{:?}", info.owner_id));
 
         if let Some(ref filter_peers) = *FILTER_PEERS {
             if !filter_peers.contains(&id) {
@@ -212,36 +273,20 @@ mod detail {
         let file_path = DIR.with(|dir| dir.join(format!("{}-{:03}.dot", id, call_count)));
         catch_dump(
             file_path.clone(),
-            info.gossip_graph,
-            info.peer_list,
-            info.meta_election,
+            info.graph.gossip_graph,
+            info.graph.peer_list,
+            info.graph.meta_election,
         );
 
-        let peer_ids = sanitise_peer_ids(info.peer_list);
-        let short_peer_ids = short_peer_id_names(&peer_ids);
-
         match File::create(&file_path) {
             Ok(file) => {
-                let mut dot_writer = DotWriter {
-                    file: BufWriter::new(file),
-                    consensus_mode: info.consensus_mode,
-                    gossip_graph: info.gossip_graph,
-                    meta_election: info.meta_election,
-                    peer_list: info.peer_list,
-                    secure_rng: info.secure_rng,
-                    key_gens_and_next_id: info.key_gens_and_next_id,
-                    observations: &DotObservation::from_observations(
-                        &info.observations,
-                        info.gossip_graph,
-                        &info.peer_list,
-                        &short_peer_ids,
-                    ),
-                    peer_ids: &peer_ids,
-                    short_peer_ids: &short_peer_ids,
-                    indent: 0,
-                };
-
-                if let Err(error) = dot_writer.write() {
+                let result = dot::write_with_secrets(
+                    &info.graph,
+                    info.secure_rng,
+                    info.key_gens_and_next_id,
+                    BufWriter::new(file),
+                );
+                if let Err(error) = result {
                     println!("Error writing to {:?}: {:?}", file_path, error);
                 }
             }
@@ -300,6 +345,31 @@ mod detail {
     fn remove_symlink_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
         fs::remove_dir(path)
     }
+}
+
+mod dot {
+    use super::{GraphDumpOptions, GraphInfo};
+    use crate::{
+        gossip::{Cause, Event, EventIndex, Graph, IndexedEventRef},
+        id::{PublicId, SecretId},
+        meta_voting::{MetaElection, MetaEvent, MetaVote, Observer},
+        network_event::NetworkEvent,
+        observation::{ConsensusMode, Malice, Observation, ObservationKey, ObservationStore},
+        peer_list::{PeerIndex, PeerIndexMap, PeerIndexSet, PeerList},
+        serialise,
+    };
+    #[cfg(feature = "dump-graphs")]
+    use crate::{
+        key_gen::{parsec_rng::ParsecRng, KeyGen},
+        parsec::KeyGenId,
+    };
+    use itertools::Itertools;
+    use std::{
+        cmp,
+        collections::{BTreeMap, BTreeSet},
+        fmt::{self, Debug, Formatter},
+        io::{self, Write},
+    };
 
     fn dump_meta_votes(
         short_peer_ids: &PeerIndexMap<String>,
@@ -321,7 +391,9 @@ mod detail {
         }
         let meta_votes = meta_votes
             .iter()
-            .map(|(peer_index, meta_votes)| (unwrap!(short_peer_ids.get(peer_index)), meta_votes))
+            .filter_map(|(peer_index, meta_votes)| {
+                Some((short_peer_ids.get(peer_index)?, meta_votes))
+            })
             .sorted_by(|(lhs_short_id, _), (rhs_short_id, _)| Ord::cmp(lhs_short_id, rhs_short_id));
 
         for (short_peer_id, meta_votes) in meta_votes {
@@ -353,23 +425,107 @@ mod detail {
         lines
     }
 
-    struct DotWriter<'a, S: SecretId + 'a> {
-        file: BufWriter<File>,
+    pub(super) fn write<T: NetworkEvent, S: SecretId, W: Write>(
+        info: &GraphInfo<T, S>,
+        options: GraphDumpOptions,
+        writer: W,
+    ) -> io::Result<()> {
+        let names = Names::new(info, options);
+        DotWriter::new(info, options, &names, writer).write()
+    }
+
+    #[cfg(feature = "dump-graphs")]
+    pub(super) fn write_with_secrets<T: NetworkEvent, S: SecretId, W: Write>(
+        info: &GraphInfo<T, S>,
+        secure_rng: &ParsecRng,
+        key_gens_and_next_id: (&BTreeMap<KeyGenId, KeyGen<S>>, &KeyGenId),
+        writer: W,
+    ) -> io::Result<()> {
+        let options = GraphDumpOptions::default();
+        let names = Names::new(info, options);
+        let mut dot_writer = DotWriter::new(info, options, &names, writer);
+        dot_writer.secure_rng = Some(secure_rng);
+        dot_writer.key_gens_and_next_id = Some(key_gens_and_next_id);
+        dot_writer.write()
+    }
+
+    // How peers and observations are displayed in the dot file.
+    struct Names {
+        peer_ids: PeerIndexMap<DotPeerId>,
+        short_peer_ids: PeerIndexMap<String>,
+        observations: DotObservationStore,
+    }
+
+    impl Names {
+        fn new<T: NetworkEvent, S: SecretId>(
+            info: &GraphInfo<T, S>,
+            options: GraphDumpOptions,
+        ) -> Self {
+            let peer_ids = sanitise_peer_ids(info.peer_list);
+            let short_peer_ids = short_peer_id_names(&peer_ids);
+            let observations = if options.observations {
+                DotObservation::from_observations(
+                    info.observations,
+                    info.gossip_graph,
+                    info.peer_list,
+                    &short_peer_ids,
+                )
+            } else {
+                DotObservation::hidden(info.observations)
+            };
+
+            Names {
+                peer_ids,
+                short_peer_ids,
+                observations,
+            }
+        }
+    }
+
+    struct DotWriter<'a, S: SecretId + 'a, W: Write> {
+        writer: W,
+        options: GraphDumpOptions,
         consensus_mode: ConsensusMode,
         gossip_graph: &'a Graph<S::PublicId>,
         meta_election: &'a MetaElection,
         peer_list: &'a PeerList<S>,
-        secure_rng: &'a ParsecRng,
-        key_gens_and_next_id: (&'a BTreeMap<KeyGenId, KeyGen<S>>, &'a KeyGenId),
+        #[cfg(feature = "dump-graphs")]
+        secure_rng: Option<&'a ParsecRng>,
+        #[cfg(feature = "dump-graphs")]
+        key_gens_and_next_id: Option<(&'a BTreeMap<KeyGenId, KeyGen<S>>, &'a KeyGenId)>,
         observations: &'a DotObservationStore,
         peer_ids: &'a PeerIndexMap<DotPeerId>,
         short_peer_ids: &'a PeerIndexMap<String>,
         indent: usize,
     }
 
-    impl<'a, S: SecretId + 'a> DotWriter<'a, S> {
+    impl<'a, S: SecretId + 'a, W: Write> DotWriter<'a, S, W> {
         const COMMENT: &'static str = "/// ";
 
+        fn new<T: NetworkEvent>(
+            info: &GraphInfo<'a, T, S>,
+            options: GraphDumpOptions,
+            names: &'a Names,
+            writer: W,
+        ) -> Self {
+            DotWriter {
+                writer,
+                options,
+                consensus_mode: info.consensus_mode,
+                gossip_graph: info.gossip_graph,
+                meta_election: info.meta_election,
+                peer_list: info.peer_list,
+                #[cfg(feature = "dump-graphs")]
+                secure_rng: None,
+                #[cfg(feature = "dump-graphs")]
+                key_gens_and_next_id: None,
+                observations: &names.observations,
+                peer_ids: &names.peer_ids,
+                short_peer_ids: &names.short_peer_ids,
+                indent: 0,
+            }
+        }
+
         fn indentation(&self) -> String {
             " ".repeat(self.indent)
         }
@@ -393,14 +549,19 @@ mod detail {
         }
 
         fn writeln(&mut self, args: fmt::Arguments) -> io::Result<()> {
-            writeln!(self.file, "{}", args)
+            writeln!(self.writer, "{}", args)
         }
 
         fn write(&mut self) -> io::Result<()> {
-            self.write_peer_list()?;
+            if self.options.peer_list {
+                self.write_peer_list()?;
+            }
             self.write_consensus_mode()?;
-            self.write_secure_rng()?;
-            self.write_key_gens()?;
+            #[cfg(feature = "dump-graphs")]
+            {
+                self.write_secure_rng()?;
+                self.write_key_gens()?;
+            }
 
             self.writeln(format_args!("digraph GossipGraph {{"))?;
             self.writeln(format_args!("  splines=false"))?;
@@ -420,9 +581,11 @@ mod detail {
             }
             self.writeln(format_args!("}}\n"))?;
 
-            self.write_meta_elections()?;
+            if self.options.meta_elections {
+                self.write_meta_elections()?;
+            }
 
-            Ok(())
+            self.writer.flush()
         }
 
         fn write_peer_list(&mut self) -> io::Result<()> {
@@ -463,33 +626,39 @@ mod detail {
             ))
         }
 
+        #[cfg(feature = "dump-graphs")]
         fn write_secure_rng(&mut self) -> io::Result<()> {
-            if self.secure_rng.generated_values().is_empty() {
-                return Ok(());
-            }
+            let secure_rng = match self.secure_rng {
+                Some(secure_rng) if !secure_rng.generated_values().is_empty() => secure_rng,
+                _ => return Ok(()),
+            };
 
             let indent = self.indentation();
             self.writeln(format_args!(
                 "{}{}secure_rng: {:?}",
                 Self::COMMENT,
                 indent,
-                self.secure_rng.generated_values()
+                secure_rng.generated_values()
             ))
         }
 
+        #[cfg(feature = "dump-graphs")]
         fn write_key_gens(&mut self) -> io::Result<()> {
-            if self.key_gens_and_next_id.0.is_empty()
-                && *self.key_gens_and_next_id.1 == KeyGenId::default()
-            {
-                return Ok(());
-            }
+            let key_gens_and_next_id = match self.key_gens_and_next_id {
+                Some((key_gens, next_id))
+                    if !key_gens.is_empty() || *next_id != KeyGenId::default() =>
+                {
+                    (key_gens, next_id)
+                }
+                _ => return Ok(()),
+            };
 
             let indent = self.indentation();
             self.writeln(format_args!(
                 "{}{}key_gens_and_next_id: {:?}",
                 Self::COMMENT,
                 indent,
-                serialise(&self.key_gens_and_next_id)
+                serialise(&key_gens_and_next_id)
             ))
         }
 
@@ -637,7 +806,9 @@ mod detail {
                     let attr = EventAttributes::new(
                         event.inner(),
                         self.event_to_short_name(event),
-                        meta_events.get(&event_index),
+                        meta_events
+                            .get(&event_index)
+                            .filter(|_| self.options.meta_elections),
                         self.observations,
                         &self.short_peer_ids,
                     );
@@ -651,7 +822,7 @@ mod detail {
 
                     let last_ancestors = event.last_ancestors().collect();
                     let last_ancestors = self.convert_peer_index_map(&last_ancestors);
-                    writeln!(&mut self.file, "/// last_ancestors: {:?}", last_ancestors)?;
+                    writeln!(&mut self.writer, "/// last_ancestors: {:?}", last_ancestors)?;
 
                     self.writeln(format_args!(""))?;
                 }
@@ -694,7 +865,7 @@ mod detail {
                 Cause::Initial => "Initial",
             };
 
-            writeln!(&mut self.file, "/// cause: {}", cause)
+            writeln!(&mut self.writer, "/// cause: {}", cause)
         }
 
         fn write_meta_elections(&mut self) -> io::Result<()> {
@@ -815,7 +986,7 @@ mod detail {
                 let interesting_content = mev
                     .interesting_content
                     .iter()
-                    .map(|obs_key| unwrap!(self.observations.get(obs_key)))
+                    .filter_map(|obs_key| self.observations.get(obs_key))
                     .collect::<Vec<_>>();
                 lines.push(format!(
                     "{}{}interesting_content: {:?}",
@@ -910,7 +1081,7 @@ mod detail {
                     let interesting_content = meta_event
                         .interesting_content
                         .iter()
-                        .map(|obs_key| unwrap!(observations.get(obs_key)))
+                        .filter_map(|obs_key| observations.get(obs_key))
                         .collect::<Vec<_>>();
                     attr.label = format!(
                         "{}<tr><td colspan=\"6\">{:?}</td></tr>",
@@ -1007,6 +1178,22 @@ mod detail {
                 .collect()
         }
 
+        // Identify the observations only by their hash.
+        fn hidden<T: NetworkEvent, P: PublicId>(
+            observations: &ObservationStore<T, P>,
+        ) -> DotObservationStore {
+            observations
+                .keys()
+                .map(|key| {
+                    let observation = DotObservation {
+                        value: format!("{:?}", key.hash()),
+                        additional_info: String::new(),
+                    };
+                    (*key, observation)
+                })
+                .collect()
+        }
+
         fn new<T: NetworkEvent, S: SecretId>(
             key: &ObservationKey,
             observation: &Observation<T, S::PublicId>,
@@ -1050,6 +1237,7 @@ mod detail {
                     }
                     format!("OpaquePayload({})", payload_str)
                 }
+                Observation::Upgrade { version, .. } => format!("Upgrade({})", version.0),
             };
 
             let additional_info = match observation {
@@ -1073,11 +1261,10 @@ mod detail {
         short_peer_ids: &PeerIndexMap<String>,
     ) -> String {
         let get_short_name_by_hash = |event_hash| {
-            sanitise_event_short_name(
-                unwrap!(graph.get_by_hash(event_hash)),
-                peer_list,
-                short_peer_ids,
-            )
+            graph
+                .get_by_hash(event_hash)
+                .map(|event| sanitise_event_short_name(event, peer_list, short_peer_ids))
+                .unwrap_or_else(|| "???".to_string())
         };
         match malice {
             Malice::Fork(event_hash) => format!("Fork({})", get_short_name_by_hash(event_hash)),
@@ -1089,7 +1276,7 @@ mod detail {
                 get_short_name_by_hash(event_hash),
                 write_malice_to_string(boxed_malice, graph, peer_list, short_peer_ids),
             ),
-            _ => panic!("unsupported yet"),
+            _ => format!("{:?}", malice),
         }
     }
 
@@ -1145,7 +1332,7 @@ mod detail {
         DotPeerId { value }
     }
 
-    fn sanitise_string(mut value: String) -> String {
+    pub(super) fn sanitise_string(mut value: String) -> String {
         value.retain(|c| c.is_ascii() && c.is_alphanumeric());
         value
     }
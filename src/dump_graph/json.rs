@@ -0,0 +1,342 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{GraphDumpOptions, GraphInfo};
+use crate::{
+    consensus_trace::MetaVoteTrace,
+    gossip::{Cause, EventIndex, Graph, IndexedEventRef},
+    id::{PublicId, SecretId},
+    meta_voting::{MetaEvent, Observer},
+    network_event::NetworkEvent,
+    observation::{ConsensusMode, Observation, ObservationKey},
+    peer_list::{Peer, PeerIndex, PeerIndexSet, PeerList, PeerState},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Read, Write},
+};
+
+/// Gossip graph and state of a peer, as written by
+/// [Parsec::write_graph](struct.Parsec.html#method.write_graph) in `GraphFormat::Json`.
+///
+/// Events and observations are identified by their full hashes in hexadecimal.  Maps keyed by
+/// peer are written as lists of pairs, as JSON only supports string keys.
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonGraph<T: NetworkEvent, P: PublicId> {
+    /// ID of the peer which wrote the graph.
+    pub our_id: P,
+    /// Consensus mode of the peer.
+    pub consensus_mode: ConsensusMode,
+    /// All the events of the gossip graph, in topological order.
+    pub events: Vec<JsonEvent<P>>,
+    /// The peer list, or `None` if not included.
+    pub peers: Option<Vec<JsonPeer<P>>>,
+    /// All the observations voted for, or `None` if not included.
+    pub observations: Option<Vec<JsonObservation<T, P>>>,
+    /// The current meta-election, or `None` if not included.
+    pub meta_election: Option<JsonMetaElection<P>>,
+}
+
+impl<T: NetworkEvent, P: PublicId> JsonGraph<T, P> {
+    /// Loads a graph previously written in `GraphFormat::Json`.
+    pub fn load<R: Read>(reader: R) -> serde_json::Result<Self> {
+        serde_json::from_reader(reader)
+    }
+}
+
+/// Event of the gossip graph, as part of a [JsonGraph](struct.JsonGraph.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonEvent<P: PublicId> {
+    /// Hash of the event.
+    pub hash: String,
+    /// Creator of the event.
+    pub creator: P,
+    /// Index of the event among the events of its creator.
+    pub index_by_creator: usize,
+    /// Hash of the self-parent, if any.
+    pub self_parent: Option<String>,
+    /// Hash of the other-parent, if any.
+    pub other_parent: Option<String>,
+    /// Why the event was created.
+    pub cause: JsonCause<P>,
+    /// Index-by-creator of the last ancestor of the event by each peer.
+    pub last_ancestors: Vec<(P, usize)>,
+}
+
+/// Cause of an event, as part of a [JsonEvent](struct.JsonEvent.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum JsonCause<P: PublicId> {
+    /// The creator sent a request to `recipient`.
+    Requesting {
+        /// Recipient of the request.
+        recipient: P,
+    },
+    /// The creator received a request.
+    Request,
+    /// The creator received a response.
+    Response,
+    /// The creator voted for the observation with the given hash.
+    Observation {
+        /// Hash of the observation.
+        observation: String,
+    },
+    /// Initial event of the creator.
+    Initial,
+}
+
+/// Peer, as part of a [JsonGraph](struct.JsonGraph.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonPeer<P: PublicId> {
+    /// ID of the peer.
+    pub id: P,
+    /// State of the peer.
+    pub state: PeerState,
+}
+
+/// Observation voted for, as part of a [JsonGraph](struct.JsonGraph.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonObservation<T: NetworkEvent, P: PublicId> {
+    /// Hash of the observation.
+    pub hash: String,
+    /// The peer who voted for the observation if it is consensused separately for each voter,
+    /// i.e. in `ConsensusMode::Single`.
+    pub voter: Option<P>,
+    /// The observation itself.
+    pub observation: Observation<T, P>,
+    /// Whether the observation is consensused.
+    pub consensused: bool,
+}
+
+/// Current meta-election, as part of a [JsonGraph](struct.JsonGraph.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonMetaElection<P: PublicId> {
+    /// Hashes of the payloads of the blocks consensused so far, in consensus order.
+    pub consensus_history: Vec<String>,
+    /// Peers participating in the meta-election.
+    pub voters: BTreeSet<P>,
+    /// Hashes of the events of each peer carrying interesting content.
+    pub interesting_events: Vec<(P, Vec<String>)>,
+    /// Hashes of the events carrying a payload that isn't consensused yet.
+    pub unconsensused_events: Vec<String>,
+    /// Meta-events, by hash of their event.
+    pub meta_events: BTreeMap<String, JsonMetaEvent<P>>,
+}
+
+/// Meta-event, as part of a [JsonMetaElection](struct.JsonMetaElection.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonMetaEvent<P: PublicId> {
+    /// Whether the event is an observer.
+    pub observer: JsonObserver<P>,
+    /// Hashes of the payloads deemed interesting by the event.
+    pub interesting_content: Vec<String>,
+    /// Progression of the meta votes about each voter.
+    pub meta_votes: Vec<(P, Vec<MetaVoteTrace>)>,
+}
+
+/// Observer status of an event, as part of a [JsonMetaEvent](struct.JsonMetaEvent.html).
+#[serde(bound = "")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum JsonObserver<P: PublicId> {
+    /// The event is an observer, with the given observees.
+    This(BTreeSet<P>),
+    /// The event isn't an observer, but one of its self-ancestors is.
+    Ancestor,
+    /// Neither the event nor any of its self-ancestors is an observer.
+    None,
+}
+
+pub(super) fn write<T: NetworkEvent, S: SecretId, W: Write>(
+    info: &GraphInfo<T, S>,
+    options: GraphDumpOptions,
+    writer: W,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(writer, &JsonGraph::new(info, options)).map_err(io::Error::from)
+}
+
+impl<T: NetworkEvent, P: PublicId> JsonGraph<T, P> {
+    fn new<S: SecretId<PublicId = P>>(info: &GraphInfo<T, S>, options: GraphDumpOptions) -> Self {
+        let names = Names::new(info);
+
+        let events = info
+            .gossip_graph
+            .iter()
+            .filter_map(|event| names.event(event))
+            .collect();
+
+        let peers = if options.peer_list {
+            Some(
+                info.peer_list
+                    .iter()
+                    .map(|(_, peer)| JsonPeer {
+                        id: peer.id().clone(),
+                        state: peer.state(),
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        let observations = if options.observations {
+            Some(
+                info.observations
+                    .iter()
+                    .map(|(key, info)| JsonObservation {
+                        hash: observation_hash(key),
+                        voter: key.peer_index().and_then(|index| names.peer_id(index)),
+                        observation: info.observation.clone(),
+                        consensused: info.consensused,
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        let meta_election = if options.meta_elections {
+            let meta_election = info.meta_election;
+            Some(JsonMetaElection {
+                consensus_history: meta_election
+                    .consensus_history()
+                    .iter()
+                    .map(observation_hash)
+                    .collect(),
+                voters: names.peer_ids(meta_election.voters()),
+                interesting_events: meta_election
+                    .interesting_events()
+                    .filter_map(|(peer_index, event_indices)| {
+                        let hashes = event_indices
+                            .iter()
+                            .filter_map(|index| names.event_hash(*index))
+                            .collect();
+                        Some((names.peer_id(peer_index)?, hashes))
+                    })
+                    .collect(),
+                unconsensused_events: meta_election
+                    .unconsensused_events(None)
+                    .filter_map(|index| names.event_hash(index))
+                    .collect(),
+                meta_events: meta_election
+                    .meta_events()
+                    .iter()
+                    .filter_map(|(index, meta_event)| {
+                        Some((names.event_hash(*index)?, names.meta_event(meta_event)))
+                    })
+                    .collect(),
+            })
+        } else {
+            None
+        };
+
+        JsonGraph {
+            our_id: info.peer_list.our_pub_id().clone(),
+            consensus_mode: info.consensus_mode,
+            events,
+            peers,
+            observations,
+            meta_election,
+        }
+    }
+}
+
+// Converts the indices used internally to IDs and hashes.
+struct Names<'a, S: SecretId> {
+    gossip_graph: &'a Graph<S::PublicId>,
+    peer_list: &'a PeerList<S>,
+}
+
+impl<'a, S: SecretId> Names<'a, S> {
+    fn new<T: NetworkEvent>(info: &GraphInfo<'a, T, S>) -> Self {
+        Names {
+            gossip_graph: info.gossip_graph,
+            peer_list: info.peer_list,
+        }
+    }
+
+    fn peer_id(&self, index: PeerIndex) -> Option<S::PublicId> {
+        self.peer_list.get(index).map(Peer::id).cloned()
+    }
+
+    fn peer_ids(&self, indices: &PeerIndexSet) -> BTreeSet<S::PublicId> {
+        indices
+            .iter()
+            .filter_map(|index| self.peer_id(index))
+            .collect()
+    }
+
+    fn event_hash(&self, index: EventIndex) -> Option<String> {
+        self.gossip_graph
+            .get(index)
+            .map(|event| event.hash().full_display().to_string())
+    }
+
+    fn event(&self, event: IndexedEventRef<S::PublicId>) -> Option<JsonEvent<S::PublicId>> {
+        let cause = match event.cause() {
+            Cause::Requesting { recipient, .. } => JsonCause::Requesting {
+                recipient: self.peer_id(*recipient)?,
+            },
+            Cause::Request { .. } => JsonCause::Request,
+            Cause::Response { .. } => JsonCause::Response,
+            Cause::Observation { vote, .. } => JsonCause::Observation {
+                observation: observation_hash(vote.payload_key()),
+            },
+            Cause::Initial => JsonCause::Initial,
+        };
+
+        Some(JsonEvent {
+            hash: event.hash().full_display().to_string(),
+            creator: self.peer_id(event.creator())?,
+            index_by_creator: event.index_by_creator(),
+            self_parent: event.self_parent().and_then(|index| self.event_hash(index)),
+            other_parent: event
+                .other_parent()
+                .and_then(|index| self.event_hash(index)),
+            cause,
+            last_ancestors: event
+                .last_ancestors()
+                .filter_map(|(peer_index, index_by_creator)| {
+                    Some((self.peer_id(peer_index)?, index_by_creator))
+                })
+                .collect(),
+        })
+    }
+
+    fn meta_event(&self, meta_event: &MetaEvent) -> JsonMetaEvent<S::PublicId> {
+        JsonMetaEvent {
+            observer: match meta_event.observer {
+                Observer::This(ref observees) => JsonObserver::This(self.peer_ids(observees)),
+                Observer::Ancestor => JsonObserver::Ancestor,
+                Observer::None => JsonObserver::None,
+            },
+            interesting_content: meta_event
+                .interesting_content
+                .iter()
+                .map(observation_hash)
+                .collect(),
+            meta_votes: meta_event
+                .meta_votes
+                .iter()
+                .filter_map(|(peer_index, meta_votes)| {
+                    let meta_votes = meta_votes.iter().map(MetaVoteTrace::new).collect();
+                    Some((self.peer_id(peer_index)?, meta_votes))
+                })
+                .collect(),
+        }
+    }
+}
+
+fn observation_hash(key: &ObservationKey) -> String {
+    key.hash().0.full_display().to_string()
+}
@@ -6,7 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::peer_list::PeerState;
+use crate::{
+    gossip::{ProtocolVersion, SupportedVersions},
+    peer_list::PeerState,
+};
 use std::{
     fmt::{self, Display, Formatter},
     result,
@@ -53,8 +56,32 @@ pub enum Error {
     InvalidMessage,
     /// The request or response has already been handled by us.
     DuplicateMessage,
+    /// The request or response uses a protocol version we don't support.
+    IncompatibleProtocolVersion {
+        /// Version the message is encoded with
+        version: ProtocolVersion,
+        /// Versions supported by the sender
+        theirs: SupportedVersions,
+        /// Versions supported by our node
+        ours: SupportedVersions,
+    },
+    /// The requested protocol upgrade is either unsupported by us or not newer than the version
+    /// currently in effect.
+    InvalidUpgrade,
+    /// The given range of supported protocol versions isn't supported by this crate, or doesn't
+    /// contain the version currently in effect.
+    InvalidSupportedVersions,
     /// Faild DKG process
     FailedDkg,
+    /// Failed to encrypt or decrypt the secret key share of an exported DKG result.
+    DkgResultEncryption,
+    /// The exported DKG result doesn't match the given public key set.
+    MismatchedPublicKeySet,
+    /// Failed to encode or decode a message using the chosen codec.
+    CodecFailure,
+    /// The decoded message doesn't match the canonical encoding it was sent with, meaning the
+    /// codec doesn't faithfully round-trip it.
+    CodecMismatch,
     /// Logic error.
     Logic,
 }
@@ -100,7 +127,38 @@ impl Display for Error {
             ),
             Error::InvalidMessage => write!(f, "This non-empty message is invalid."),
             Error::DuplicateMessage => write!(f, "This message has already been handled."),
+            Error::IncompatibleProtocolVersion {
+                version,
+                theirs,
+                ours,
+            } => write!(
+                f,
+                "The message uses protocol {} (sender supports {}-{}, we support {}-{}).",
+                version, theirs.min, theirs.max, ours.min, ours.max
+            ),
+            Error::InvalidUpgrade => write!(
+                f,
+                "The protocol upgrade is unsupported or doesn't move to a newer version."
+            ),
+            Error::InvalidSupportedVersions => write!(
+                f,
+                "The supported protocol versions are unsupported by this crate or exclude the \
+                 version in effect."
+            ),
             Error::FailedDkg => write!(f, "The requested DKG could not proceed."),
+            Error::DkgResultEncryption => write!(
+                f,
+                "The secret key share of the DKG result could not be encrypted or decrypted."
+            ),
+            Error::MismatchedPublicKeySet => write!(
+                f,
+                "The exported DKG result doesn't match the given public key set."
+            ),
+            Error::CodecFailure => write!(f, "The message could not be encoded or decoded."),
+            Error::CodecMismatch => write!(
+                f,
+                "The decoded message doesn't match its canonical encoding."
+            ),
             Error::Logic => write!(
                 f,
                 "This is a logic error and represents a flaw in the code."
@@ -6,23 +6,49 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+#[cfg(feature = "json")]
+use crate::dump_graph::JsonGraph;
 use crate::{
     block::Block,
-    dev_utils::{new_common_rng, new_rng, parse_test_dot_file, Record, RngChoice, TestIterator},
+    consensus_trace::MetaVoteStep,
+    dev_utils::{
+        new_common_rng, new_rng, parse_dot_file, parse_test_dot_file, Record, RngChoice,
+        TestIterator,
+    },
+    dump_graph::{GraphDumpOptions, GraphFormat},
     error::Error,
-    gossip::{Event, Graph, GraphSnapshot},
-    id::{Proof, PublicId},
+    gossip::{Event, Graph, GraphSnapshot, ProtocolVersion, Response, SupportedVersions},
+    hash::{HashAlgorithm, Hasher, Sha3, HASH_LEN},
+    id::{Proof, PublicId, SecretId},
     meta_voting::MetaElectionSnapshot,
+    metrics::Metrics,
     mock::{self, PeerId, Transaction},
-    observation::{ConsensusMode, Observation},
-    parsec::TestParsec,
+    network_event::NetworkEvent,
+    observation::{
+        is_more_than_two_thirds, ConsensusMode, Observation, ObservationHash, ObservationStatus,
+    },
+    parsec::{Parsec, TestParsec},
     peer_list::{PeerListSnapshot, PeerState},
+    section_status::SectionStatus,
+};
+use maidsafe_utilities::serialisation::deserialise;
+use std::{
+    cmp,
+    collections::BTreeSet,
+    env,
+    fs::{self, File},
+    iter, process,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use std::collections::BTreeSet;
 
 // Use Fixed seed for functional tests: No randomization.
 static SEED: RngChoice = RngChoice::SeededXor([1, 2, 3, 4]);
 
+// Alice's graph after `Add(Eric)` got consensused, shared by the tests of the queries about past
+// and pending consensus.
+const ALICE_AFTER_ADD_ERIC: &str = "input_graphs/functional_tests_observation_status/alice.dot";
+
 type TestPeer = TestParsec<Transaction, PeerId>;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -419,6 +445,194 @@ fn unpolled_observations() {
     assert!(unpolled_observations.next().is_none());
 }
 
+#[test]
+fn observation_status() {
+    let mut common_rng = new_common_rng(SEED);
+    let mut alice_contents = unwrap!(parse_dot_file(ALICE_AFTER_ADD_ERIC));
+    let d_21 = unwrap!(alice_contents.remove_last_event());
+    let mut alice = TestParsec::from_parsed_contents(alice_contents, new_rng(&mut common_rng));
+
+    let add_eric = Observation::Add {
+        peer_id: PeerId::new("Eric"),
+        related_info: vec![],
+    };
+    let voters = ObservationStatus::Voted {
+        voters: mock::create_ids(4)
+            .into_iter()
+            .map(|id| id.public_id().clone())
+            .collect(),
+    };
+    assert_eq!(alice.observation_status(&add_eric), voters);
+
+    // D_21 is the event reaching consensus on `Add(Eric)`.
+    unwrap!(alice.add_event(d_21));
+    assert_eq!(
+        alice.observation_status(&add_eric),
+        ObservationStatus::Consensused { height: 1 }
+    );
+
+    while alice.poll().is_some() {}
+    assert_eq!(
+        alice.observation_status(&add_eric),
+        ObservationStatus::Polled
+    );
+
+    // An observation nobody voted for yet is unknown until we vote for it.
+    let vote = Observation::OpaquePayload(Transaction::new("ABCD"));
+    assert_eq!(alice.observation_status(&vote), ObservationStatus::Unknown);
+
+    unwrap!(alice.vote_for(vote.clone()));
+    assert_eq!(
+        alice.observation_status(&vote),
+        ObservationStatus::Voted {
+            voters: iter::once(alice.our_pub_id().clone()).collect()
+        }
+    );
+}
+
+#[test]
+fn explain_consensus() {
+    let mut common_rng = new_common_rng(SEED);
+    let mut alice_contents = unwrap!(parse_dot_file(ALICE_AFTER_ADD_ERIC));
+    let d_21 = unwrap!(alice_contents.remove_last_event());
+    let d_21_hash = *d_21.hash();
+    let mut alice = TestParsec::from_parsed_contents(alice_contents, new_rng(&mut common_rng));
+    alice.set_record_decisions(true);
+    assert!(alice.explain_consensus(1).is_none());
+
+    // D_21 is the event reaching consensus on `Add(Eric)`, at height 1.
+    unwrap!(alice.add_event(d_21));
+    let trace = unwrap!(alice.explain_consensus(1));
+    assert_eq!(trace.heights, 1..2);
+    assert_eq!(trace.decided_by, Some(d_21_hash));
+
+    let voters: BTreeSet<_> = mock::create_ids(4)
+        .into_iter()
+        .map(|id| id.public_id().clone())
+        .collect();
+    assert_eq!(
+        trace.meta_votes.keys().cloned().collect::<BTreeSet<_>>(),
+        voters
+    );
+    for meta_votes in trace.meta_votes.values() {
+        assert!(unwrap!(meta_votes.last()).decision.is_some());
+    }
+    assert_eq!(
+        trace.observers.keys().cloned().collect::<BTreeSet<_>>(),
+        voters
+    );
+    for observer in trace.observers.values() {
+        assert!(is_more_than_two_thirds(
+            observer.observees.len(),
+            voters.len()
+        ));
+    }
+    for (voter, coin_tosses) in &trace.coin_tosses {
+        let flips = trace.meta_votes[voter]
+            .iter()
+            .filter(|meta_vote| meta_vote.step == MetaVoteStep::GenuineFlip)
+            .count();
+        assert_eq!(coin_tosses.len(), flips);
+    }
+
+    assert!(alice.explain_consensus(2).is_none());
+
+    // Nothing is recorded unless enabled.
+    let mut alice_contents = unwrap!(parse_dot_file(ALICE_AFTER_ADD_ERIC));
+    let d_21 = unwrap!(alice_contents.remove_last_event());
+    let mut alice = TestParsec::from_parsed_contents(alice_contents, new_rng(&mut common_rng));
+    unwrap!(alice.add_event(d_21));
+    assert!(alice.explain_consensus(1).is_none());
+}
+
+#[test]
+fn write_graph() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice = TestParsec::from_parsed_contents(
+        unwrap!(parse_dot_file(ALICE_AFTER_ADD_ERIC)),
+        new_rng(&mut common_rng),
+    );
+
+    // Everything written by default can be parsed back.
+    let path = env::temp_dir().join(format!("parsec_write_graph_{}.dot", process::id()));
+    let file = unwrap!(File::create(&path));
+    unwrap!(alice.write_graph(GraphFormat::Dot, GraphDumpOptions::default(), file));
+    let parsed_contents = unwrap!(parse_dot_file(&path));
+    let _ = fs::remove_file(&path);
+    assert_eq!(parsed_contents.graph, *alice.graph());
+    assert_eq!(parsed_contents.meta_election, *alice.meta_election());
+
+    let mut output = vec![];
+    let options = GraphDumpOptions {
+        peer_list: false,
+        meta_elections: false,
+        observations: false,
+    };
+    unwrap!(alice.write_graph(GraphFormat::Dot, options, &mut output));
+    let output = unwrap!(String::from_utf8(output));
+    assert!(output.contains("digraph GossipGraph"));
+    assert!(!output.contains("peer_list:"));
+    assert!(!output.contains("meta-elections"));
+    assert!(!output.contains("Add(Eric)"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn write_graph_json() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice = TestParsec::from_parsed_contents(
+        unwrap!(parse_dot_file(ALICE_AFTER_ADD_ERIC)),
+        new_rng(&mut common_rng),
+    );
+
+    let mut output = vec![];
+    unwrap!(alice.write_graph(GraphFormat::Json, GraphDumpOptions::default(), &mut output));
+    let graph: JsonGraph<Transaction, PeerId> = unwrap!(JsonGraph::load(&output[..]));
+    assert_eq!(graph.our_id, *alice.our_pub_id());
+    assert_eq!(graph.events.len(), alice.graph().len());
+
+    // Events are in topological order, so parents always come first.
+    let mut hashes = BTreeSet::new();
+    for event in &graph.events {
+        for parent in event.self_parent.iter().chain(event.other_parent.iter()) {
+            assert!(hashes.contains(parent));
+        }
+        assert!(hashes.insert(event.hash.clone()));
+    }
+
+    let peers = unwrap!(graph.peers);
+    assert_eq!(peers.len(), alice.peer_list().all_ids().count());
+
+    let add_eric = Observation::Add {
+        peer_id: PeerId::new("Eric"),
+        related_info: vec![],
+    };
+    let observations = unwrap!(graph.observations);
+    assert!(observations
+        .iter()
+        .any(|observation| observation.observation == add_eric));
+
+    let meta_election = unwrap!(graph.meta_election);
+    assert_eq!(
+        meta_election.consensus_history.len(),
+        alice.meta_election().consensus_history().len()
+    );
+    assert_eq!(meta_election.voters.len(), 4);
+
+    let mut output = vec![];
+    let options = GraphDumpOptions {
+        peer_list: false,
+        meta_elections: false,
+        observations: false,
+    };
+    unwrap!(alice.write_graph(GraphFormat::Json, options, &mut output));
+    let graph: JsonGraph<Transaction, PeerId> = unwrap!(JsonGraph::load(&output[..]));
+    assert_eq!(graph.events.len(), alice.graph().len());
+    assert!(graph.peers.is_none());
+    assert!(graph.observations.is_none());
+    assert!(graph.meta_election.is_none());
+}
+
 #[test]
 fn our_unpolled_observations_with_consensus_mode_single() {
     let mut alice = Record::from(parse_test_dot_file("alice.dot")).play();
@@ -513,6 +727,603 @@ fn gossip_after_fork() {
     assert!(bob.graph().contains(&a_2_fork_hash));
 }
 
+#[test]
+fn handle_duplicate_messages() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let carol_id = PeerId::new("Carol");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone(), carol_id.clone()];
+
+    let mut alice: TestPeer = TestParsec::from_genesis(
+        alice_id.clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+    let mut bob: TestPeer = TestParsec::from_genesis(
+        bob_id.clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+    let mut carol: TestPeer = TestParsec::from_genesis(
+        carol_id.clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+
+    // Bob receives the same request twice, and only answers the first copy.
+    let req = unwrap!(alice.create_gossip(&bob_id));
+    let res = unwrap!(bob.handle_request(&alice_id, req.clone()));
+    let bob_graph_len = bob.graph().len();
+    assert_eq!(
+        bob.handle_request(&alice_id, req),
+        Err(Error::DuplicateMessage)
+    );
+    assert_eq!(bob.graph().len(), bob_graph_len);
+
+    // Same for Alice receiving the response twice.
+    unwrap!(alice.handle_response(&bob_id, res.clone()));
+    let alice_graph_len = alice.graph().len();
+    assert_eq!(
+        alice.handle_response(&bob_id, res),
+        Err(Error::DuplicateMessage)
+    );
+    assert_eq!(alice.graph().len(), alice_graph_len);
+
+    // A request whose events Bob already knows isn't a duplicate.
+    let req = unwrap!(alice.create_gossip(&bob_id));
+    let res = unwrap!(bob.handle_request(&alice_id, req));
+    unwrap!(alice.handle_response(&bob_id, res));
+
+    // Nor is one whose last event Bob first got from Carol.
+    let req_to_bob = unwrap!(alice.create_gossip(&bob_id));
+    let requesting_bob = unwrap!(req_to_bob.packed_events.last()).compute_hash();
+    let req_to_carol = unwrap!(alice.create_gossip(&carol_id));
+    let _ = unwrap!(carol.handle_request(&alice_id, req_to_carol));
+    let req = unwrap!(carol.create_gossip(&bob_id));
+    let _ = unwrap!(bob.handle_request(&carol_id, req));
+    assert!(bob.graph().contains(&requesting_bob));
+    let _ = unwrap!(bob.handle_request(&alice_id, req_to_bob));
+}
+
+#[test]
+fn gossip_with_incompatible_protocol_version() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone()];
+
+    let mut alice = TestParsec::<Transaction, _>::from_genesis(
+        alice_id.clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+    let mut bob = TestParsec::<Transaction, _>::from_genesis(
+        bob_id.clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+
+    let next = ProtocolVersion(ProtocolVersion::CURRENT.0 + 1);
+    let only_next = SupportedVersions {
+        min: next,
+        max: next,
+    };
+
+    // A request encoded with a version Bob doesn't support is rejected without being applied.
+    let mut req = unwrap!(alice.create_gossip(&bob_id));
+    assert_eq!(req.version(), ProtocolVersion::MIN_SUPPORTED);
+    assert_eq!(req.supported_versions(), SupportedVersions::OURS);
+    req.version = next;
+    req.supported_versions = only_next;
+
+    let bob_snapshot = Snapshot::new(&bob);
+    assert_eq!(
+        bob.handle_request(&alice_id, req),
+        Err(Error::IncompatibleProtocolVersion {
+            version: next,
+            theirs: only_next,
+            ours: SupportedVersions::OURS,
+        })
+    );
+    assert_eq!(bob_snapshot, Snapshot::new(&bob));
+
+    // Same for a response received over the wire from a peer which also supports the versions up to
+    // the next one, and whose events are in a format unknown to Alice.
+    let bytes = unwrap!(fs::read(
+        "input_graphs/gossip_messages_tests_wire_format/request_v3.bin"
+    ));
+    let resp: Response<Transaction, PeerId> = unwrap!(deserialise(&bytes));
+    assert_eq!(resp.version(), next);
+    match alice.handle_response(&bob_id, resp) {
+        Err(Error::IncompatibleProtocolVersion { version, .. }) => assert_eq!(version, next),
+        result => panic!("Unexpected result {:?}", result),
+    }
+
+    // Bob also advertised support for the version in effect, so Alice keeps using it.
+    let req = unwrap!(alice.create_gossip(&bob_id));
+    assert_eq!(req.version(), ProtocolVersion::MIN_SUPPORTED);
+    let resp = unwrap!(bob.handle_request(&alice_id, req));
+    unwrap!(alice.handle_response(&bob_id, resp));
+}
+
+#[test]
+fn upgrade_protocol_version() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone()];
+
+    let next = ProtocolVersion(ProtocolVersion::CURRENT.0 + 1);
+    let supported_versions = SupportedVersions {
+        min: ProtocolVersion::MIN_SUPPORTED,
+        max: next,
+    };
+
+    let mut peers: Vec<_> = vec![alice_id.clone(), bob_id.clone()]
+        .into_iter()
+        .map(|id| {
+            let mut peer = TestParsec::<Transaction, _>::from_genesis(
+                id,
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                new_rng(&mut common_rng),
+            );
+            peer.set_supported_versions_unchecked(supported_versions);
+            peer
+        })
+        .collect();
+    let mut bob = unwrap!(peers.pop());
+    let mut alice = unwrap!(peers.pop());
+    assert_eq!(alice.protocol_version(), ProtocolVersion::MIN_SUPPORTED);
+
+    let upgrade = |version| Observation::Upgrade {
+        version,
+        related_info: vec![],
+    };
+
+    // Only upgrades to a newer version we support are allowed.
+    assert_eq!(
+        alice.vote_for(upgrade(ProtocolVersion::MIN_SUPPORTED)),
+        Err(Error::InvalidUpgrade)
+    );
+    assert_eq!(
+        alice.vote_for(upgrade(ProtocolVersion(next.0 + 1))),
+        Err(Error::InvalidUpgrade)
+    );
+    unwrap!(alice.vote_for(upgrade(next)));
+    unwrap!(bob.vote_for(upgrade(next)));
+
+    // Both peers support the next version, but keep gossiping with the one in effect until the
+    // upgrade is consensused.
+    let mut upgraded = false;
+    for _ in 0..100 {
+        let req = unwrap!(alice.create_gossip(&bob_id));
+        assert_eq!(req.version(), alice.protocol_version());
+        let resp = unwrap!(bob.handle_request(&alice_id, req));
+        unwrap!(alice.handle_response(&bob_id, resp));
+
+        let req = unwrap!(bob.create_gossip(&alice_id));
+        assert_eq!(req.version(), bob.protocol_version());
+        let resp = unwrap!(alice.handle_request(&bob_id, req));
+        unwrap!(bob.handle_response(&alice_id, resp));
+
+        if alice.protocol_version() == next && bob.protocol_version() == next {
+            upgraded = true;
+            break;
+        }
+    }
+    assert!(upgraded);
+
+    // The upgrade is output as a regular block.
+    assert!(alice
+        .consensused_blocks()
+        .any(|block| *block.payload() == upgrade(next)));
+
+    // Upgrading again to the version in effect is now refused.
+    assert_eq!(
+        alice.vote_for(Observation::Upgrade {
+            version: next,
+            related_info: vec![1],
+        }),
+        Err(Error::InvalidUpgrade)
+    );
+    let req = unwrap!(alice.create_gossip(&bob_id));
+    assert_eq!(req.version(), next);
+    let _ = unwrap!(bob.handle_request(&alice_id, req));
+}
+
+#[test]
+fn restrict_supported_versions() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone()];
+    let mut alice = TestParsec::<Transaction, _>::from_genesis(
+        alice_id,
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+
+    let oldest = ProtocolVersion::MIN_SUPPORTED;
+    let next = ProtocolVersion(ProtocolVersion::CURRENT.0 + 1);
+    let range = |min, max| SupportedVersions { min, max };
+    let upgrade = Observation::Upgrade {
+        version: ProtocolVersion::CURRENT,
+        related_info: vec![],
+    };
+    assert_eq!(alice.supported_versions(), SupportedVersions::OURS);
+
+    // Versions this crate doesn't support, or ranges excluding the version in effect, are refused.
+    assert_eq!(
+        alice.set_supported_versions(range(oldest, next)),
+        Err(Error::InvalidSupportedVersions)
+    );
+    assert_eq!(
+        alice.set_supported_versions(range(ProtocolVersion::CURRENT, ProtocolVersion::CURRENT)),
+        Err(Error::InvalidSupportedVersions)
+    );
+    assert_eq!(alice.supported_versions(), SupportedVersions::OURS);
+
+    // Until Alice supports the newest version, she doesn't advertise it nor vote to upgrade to it.
+    unwrap!(alice.set_supported_versions(range(oldest, oldest)));
+    assert_eq!(
+        unwrap!(alice.create_gossip(&bob_id)).supported_versions(),
+        range(oldest, oldest)
+    );
+    assert_eq!(alice.vote_for(upgrade.clone()), Err(Error::InvalidUpgrade));
+
+    unwrap!(alice.set_supported_versions(SupportedVersions::OURS));
+    unwrap!(alice.vote_for(upgrade));
+}
+
+#[test]
+fn upgrade_to_unsupported_protocol_version() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids: Vec<_> = ["Alice", "Bob", "Carol", "Dave"]
+        .iter()
+        .map(|name| PeerId::new(name))
+        .collect();
+    let genesis_group = ids.iter().cloned().collect();
+
+    let next = ProtocolVersion(ProtocolVersion::CURRENT.0 + 1);
+    let upgrade = Observation::Upgrade {
+        version: next,
+        related_info: vec![],
+    };
+
+    // Everyone but Alice supports the next version and votes for it.
+    let mut peers: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            let mut peer = TestParsec::<Transaction, _>::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                new_rng(&mut common_rng),
+            );
+            if *id != ids[0] {
+                peer.set_supported_versions_unchecked(SupportedVersions {
+                    min: ProtocolVersion::MIN_SUPPORTED,
+                    max: next,
+                });
+                unwrap!(peer.vote_for(upgrade.clone()));
+            }
+            peer
+        })
+        .collect();
+
+    // Alice never hears from Dave directly, so she doesn't know which versions he supports.
+    let pairs = [
+        (0, 1),
+        (1, 0),
+        (0, 2),
+        (2, 0),
+        (1, 2),
+        (2, 1),
+        (3, 1),
+        (3, 2),
+    ];
+    for _ in 0..100 {
+        if peers[0].protocol_version() == next {
+            break;
+        }
+        for &(src, dst) in &pairs {
+            let req = unwrap!(peers[src].create_gossip(&ids[dst]));
+            let resp = unwrap!(peers[dst].handle_request(&ids[src], req));
+            unwrap!(peers[src].handle_response(&ids[dst], resp));
+        }
+    }
+    assert_eq!(peers[0].protocol_version(), next);
+
+    // Alice follows the section, but keeps gossiping with the newest version she can decode.
+    let req = unwrap!(peers[0].create_gossip(&ids[3]));
+    assert_eq!(req.version(), ProtocolVersion::CURRENT);
+    let req = unwrap!(peers[0].create_gossip(&ids[1]));
+    assert_eq!(req.version(), ProtocolVersion::CURRENT);
+}
+
+#[test]
+fn custom_hash_algorithm() {
+    // SHA3 with the bytes of the digest reversed, to get hashes different from the default ones.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    struct ReversedSha3;
+
+    impl HashAlgorithm for ReversedSha3 {
+        fn digest(inputs: &[&[u8]]) -> [u8; HASH_LEN] {
+            let mut digest = Sha3::digest(inputs);
+            digest.reverse();
+            digest
+        }
+    }
+
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone()];
+
+    let mut peers: Vec<_> = vec![alice_id.clone(), bob_id.clone()]
+        .into_iter()
+        .map(|id| {
+            Parsec::<Transaction, _, ReversedSha3>::from_genesis(
+                id,
+                &genesis_group,
+                vec![],
+                ConsensusMode::Supermajority,
+                new_rng(&mut common_rng),
+            )
+        })
+        .collect();
+    let mut bob = unwrap!(peers.pop());
+    let mut alice = unwrap!(peers.pop());
+
+    let observation = Observation::OpaquePayload(Transaction::new("one"));
+    unwrap!(alice.vote_for(observation.clone()));
+    unwrap!(bob.vote_for(observation.clone()));
+
+    let mut polled = (false, false);
+    for _ in 0..100 {
+        let req = unwrap!(alice.create_gossip(&bob_id));
+        let resp = unwrap!(bob.handle_request(&alice_id, req));
+        unwrap!(alice.handle_response(&bob_id, resp));
+
+        let req = unwrap!(bob.create_gossip(&alice_id));
+        let resp = unwrap!(alice.handle_request(&bob_id, req));
+        unwrap!(bob.handle_response(&alice_id, resp));
+
+        while let Some(block) = alice.poll() {
+            polled.0 |= *block.payload() == observation;
+        }
+        while let Some(block) = bob.poll() {
+            polled.1 |= *block.payload() == observation;
+        }
+        if polled == (true, true) {
+            break;
+        }
+    }
+    assert_eq!(polled, (true, true));
+
+    // Check that the hashes actually come from `ReversedSha3` rather than the default algorithm.
+    let reversed_sha3 = Hasher::new::<ReversedSha3>();
+    let sha3 = Hasher::new::<Sha3>();
+
+    let events = alice.hashed_packed_events();
+    assert!(!events.is_empty());
+    for (hash, packed_event) in events {
+        assert_eq!(hash, packed_event.compute_hash_with(reversed_sha3));
+        assert_ne!(hash, packed_event.compute_hash_with(sha3));
+    }
+
+    let consensused: Vec<_> = alice
+        .consensus_history()
+        .iter()
+        .map(|key| *key.hash())
+        .collect();
+    assert!(consensused.contains(&ObservationHash::new(reversed_sha3, &observation)));
+    assert!(!consensused.contains(&ObservationHash::new(sha3, &observation)));
+}
+
+#[test]
+fn section_status() {
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone()];
+
+    let mut alice = Parsec::<Transaction, _>::from_genesis(
+        alice_id.clone(),
+        &genesis_group,
+        vec![],
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+    let mut bob = Parsec::<Transaction, _>::from_genesis(
+        bob_id.clone(),
+        &genesis_group,
+        vec![],
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+
+    // Alice only knows her initial and genesis events, none of which Bob has seen.
+    let status = alice.section_status();
+    let peer_status = |status: &SectionStatus<PeerId>, id: &PeerId| {
+        let peer = unwrap!(status.peers.iter().find(|peer| peer.id == *id));
+        (peer.state, peer.last_event_index, peer.lag)
+    };
+    assert_eq!(
+        peer_status(&status, &alice_id),
+        (PeerState::active(), Some(1), 0)
+    );
+    assert_eq!(
+        peer_status(&status, &bob_id),
+        (PeerState::active(), None, 2)
+    );
+    assert_eq!(status.unconsensused_observations, 1);
+    assert_eq!(status.consensused_blocks, 0);
+    assert_eq!(status.meta_election_round, None);
+
+    // Bob receives Alice's three events and answers with his own three. Alice's response event is
+    // then the only one Bob hasn't seen.
+    let req = unwrap!(alice.create_gossip(&bob_id));
+    let resp = unwrap!(bob.handle_request(&alice_id, req));
+    unwrap!(alice.handle_response(&bob_id, resp));
+
+    let status = alice.section_status();
+    assert_eq!(peer_status(&status, &alice_id).2, 0);
+    assert_eq!(peer_status(&status, &bob_id).1, Some(2));
+    assert_eq!(peer_status(&status, &bob_id).2, 1);
+
+    let status = bob.section_status();
+    assert_eq!(peer_status(&status, &alice_id).1, Some(2));
+    assert_eq!(peer_status(&status, &alice_id).2, 3);
+    assert_eq!(peer_status(&status, &bob_id).2, 0);
+
+    // Keep gossiping: the meta-election starts, then reaches consensus on the genesis observation.
+    let mut meta_election_started = false;
+    for _ in 0..100 {
+        let req = unwrap!(alice.create_gossip(&bob_id));
+        let resp = unwrap!(bob.handle_request(&alice_id, req));
+        unwrap!(alice.handle_response(&bob_id, resp));
+
+        let status = alice.section_status();
+        meta_election_started |= status.meta_election_round.is_some();
+        if status.consensused_blocks > 0 {
+            break;
+        }
+    }
+
+    let status = alice.section_status();
+    assert!(meta_election_started);
+    assert_eq!(status.consensused_blocks, 1);
+    assert_eq!(status.unconsensused_observations, 0);
+}
+
+#[test]
+fn metrics() {
+    #[derive(Debug, Default)]
+    struct Counts {
+        graph_size: usize,
+        gossip_received: usize,
+        events_received: usize,
+        gossip_rejected: Vec<String>,
+        events_processed: usize,
+        max_rounds: usize,
+        payloads_consensused: usize,
+        blocks_created: usize,
+    }
+
+    struct CountingMetrics(Arc<Mutex<Counts>>);
+
+    impl Metrics for CountingMetrics {
+        fn event_added(&self, graph_size: usize) {
+            unwrap!(self.0.lock()).graph_size = graph_size;
+        }
+
+        fn gossip_received(&self, num_events: usize) {
+            let mut counts = unwrap!(self.0.lock());
+            counts.gossip_received += 1;
+            counts.events_received += num_events;
+        }
+
+        fn gossip_rejected(&self, error: &Error) {
+            unwrap!(self.0.lock())
+                .gossip_rejected
+                .push(format!("{:?}", error));
+        }
+
+        fn event_processed(&self, _duration: Duration) {
+            unwrap!(self.0.lock()).events_processed += 1;
+        }
+
+        fn consensus_reached(&self, rounds: usize, num_payloads: usize) {
+            let mut counts = unwrap!(self.0.lock());
+            counts.max_rounds = cmp::max(counts.max_rounds, rounds);
+            counts.payloads_consensused += num_payloads;
+        }
+
+        fn blocks_created(&self, num_blocks: usize) {
+            unwrap!(self.0.lock()).blocks_created += num_blocks;
+        }
+    }
+
+    let mut common_rng = new_common_rng(SEED);
+    let alice_id = PeerId::new("Alice");
+    let bob_id = PeerId::new("Bob");
+    let genesis_group = btree_set![alice_id.clone(), bob_id.clone()];
+
+    let mut alice = TestPeer::from_genesis(
+        alice_id.clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+    let mut bob = Parsec::<Transaction, _>::from_genesis(
+        bob_id.clone(),
+        &genesis_group,
+        vec![],
+        ConsensusMode::Supermajority,
+        new_rng(&mut common_rng),
+    );
+
+    let counts = Arc::new(Mutex::new(Counts::default()));
+    alice.set_metrics(Box::new(CountingMetrics(Arc::clone(&counts))));
+
+    let observation = Observation::OpaquePayload(Transaction::new("ABCD"));
+    unwrap!(alice.vote_for(observation.clone()));
+    unwrap!(bob.vote_for(observation.clone()));
+
+    let mut num_messages = 0;
+    let mut num_events = 0;
+    while alice.poll().is_none() {
+        assert!(num_messages < 200, "Consensus not reached");
+
+        let req = unwrap!(alice.create_gossip(&bob_id));
+        let resp = unwrap!(bob.handle_request(&alice_id, req));
+        num_events += resp.packed_events.len();
+        unwrap!(alice.handle_response(&bob_id, resp));
+
+        let req = unwrap!(bob.create_gossip(&alice_id));
+        num_events += req.packed_events.len();
+        let resp = unwrap!(alice.handle_request(&bob_id, req));
+        unwrap!(bob.handle_response(&alice_id, resp));
+
+        num_messages += 2;
+    }
+
+    {
+        let counts = unwrap!(counts.lock());
+        assert_eq!(counts.graph_size, alice.graph().len());
+        assert_eq!(counts.gossip_received, num_messages);
+        assert_eq!(counts.events_received, num_events);
+        assert!(counts.gossip_rejected.is_empty());
+        // The initial event isn't processed and the genesis vote was processed before the metrics
+        // were set.
+        assert_eq!(counts.events_processed, alice.graph().len() - 2);
+        assert!(counts.max_rounds > 0);
+        assert_eq!(counts.payloads_consensused, 2);
+        assert_eq!(counts.blocks_created, 2);
+    }
+
+    // A request from an unknown peer is rejected.
+    let carol_id = PeerId::new("Carol");
+    let req = unwrap!(bob.create_gossip(&alice_id));
+    assert_eq!(
+        alice.handle_request(&carol_id, req).map(|_| ()),
+        Err(Error::UnknownPeer)
+    );
+    assert_eq!(
+        unwrap!(counts.lock()).gossip_rejected,
+        vec![format!("{:?}", Error::UnknownPeer)]
+    );
+}
+
 #[test]
 fn sees() {
     let mut common_rng = new_common_rng(SEED);
@@ -551,6 +1362,25 @@ fn sees() {
     assert!(!a3.sees(c2_1));
 }
 
+#[test]
+fn parsec_is_send() {
+    fn assert_send<T: Send>() {}
+
+    // Checked for any `T`, `S` and `H`, not just the mock ones and the default hash algorithm.
+    fn assert_parsec_is_send<T, S, H>()
+    where
+        T: NetworkEvent + Send,
+        S: SecretId + Send,
+        S::PublicId: Send,
+        <S::PublicId as PublicId>::Signature: Send,
+        H: HashAlgorithm,
+    {
+        assert_send::<Parsec<T, S, H>>();
+    }
+
+    assert_parsec_is_send::<Transaction, PeerId, Sha3>();
+}
+
 #[cfg(feature = "malice-detection")]
 mod handle_malice {
     use super::*;
@@ -783,6 +1613,29 @@ mod handle_malice {
         assert_peer_has_accused(&bob, vec![(alice.our_pub_id(), &expected_malice)]);
     }
 
+    #[test]
+    fn malice_detected_metrics() {
+        struct MaliceMetrics(Arc<Mutex<Vec<&'static str>>>);
+
+        impl Metrics for MaliceMetrics {
+            fn malice_detected(&self, kind: &'static str) {
+                unwrap!(self.0.lock()).push(kind);
+            }
+        }
+
+        let (mut alice, mut bob) =
+            unwrap!(initialise_genesis_parsecs(2).into_iter().collect_tuple());
+        let detected = Arc::new(Mutex::new(vec![]));
+        bob.set_metrics(Box::new(MaliceMetrics(Arc::clone(&detected))));
+
+        // Have Alice send a request without her genesis vote, as in `missing_genesis_event`.
+        let _ = unwrap!(alice.remove_last_event());
+        let request = unwrap!(alice.create_gossip(bob.our_pub_id()));
+        unwrap!(bob.handle_request(alice.our_pub_id(), request));
+
+        assert_eq!(*unwrap!(detected.lock()), vec!["MissingGenesis"]);
+    }
+
     #[test]
     fn incorrect_genesis_event() {
         let (mut alice, mut bob, mut carol) =
@@ -889,7 +1742,7 @@ mod handle_malice {
         let invalid_req_hash = invalid_req.compute_hash();
         let mut packed_events = take_packed_events(&bob, bob.graph().len());
         packed_events.push(invalid_req.clone());
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
 
         let expected_malice = Malice::InvalidRequest(Box::new(invalid_req));
 
@@ -937,7 +1790,7 @@ mod handle_malice {
 
         let mut packed_events = take_packed_events(&bob, 2);
         packed_events.push(invalid_req.clone());
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
 
         assert_handling_invalid_response(
             &mut bob,
@@ -950,7 +1803,7 @@ mod handle_malice {
         packed_events = take_packed_events(&bob, 5);
         packed_events.push(invalid_req);
         // Knowledge of Alice and Bob, and the invalid_req.
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
 
         assert_handling_invalid_response(
             &mut bob,
@@ -989,7 +1842,7 @@ mod handle_malice {
 
         let mut packed_events = take_packed_events(&bob, bob.graph().len());
         packed_events.push(invalid_req);
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
         assert_handling_invalid_response(
             &mut bob,
             &mut carol,
@@ -1019,7 +1872,7 @@ mod handle_malice {
         // If the response be sent to Carol, a response event shall not be created.
         let packed_events = take_packed_events(&bob, bob.graph().len());
         assert_eq!(
-            carol.handle_response(bob.our_pub_id(), Response { packed_events }),
+            carol.handle_response(bob.our_pub_id(), Response::new(packed_events)),
             Err(Error::InvalidMessage)
         );
         assert!(!carol.graph().iter().any(|event| event.is_response()));
@@ -1037,7 +1890,7 @@ mod handle_malice {
         let mut packed_events = take_packed_events(&carol, 2);
         packed_events.push(bob_request);
         packed_events.push(invalid_resp.clone());
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
         assert_handling_invalid_response(
             &mut carol,
             &mut bob,
@@ -1049,7 +1902,7 @@ mod handle_malice {
         packed_events = take_packed_events(&bob, 8);
         packed_events.push(invalid_resp);
         // Knowledge of Alice, Bob and Carol, and the invalid_resp.
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
         assert_handling_invalid_response(
             &mut carol,
             &mut dave,
@@ -1081,9 +1934,7 @@ mod handle_malice {
         let invalid_resp_hash = invalid_resp.compute_hash();
         let expected_malice = Malice::InvalidResponse(Box::new(invalid_resp.clone()));
 
-        let invalid_response_msg = Response {
-            packed_events: vec![invalid_resp.clone()],
-        };
+        let invalid_response_msg = Response::new(vec![invalid_resp.clone()]);
         assert_handling_invalid_response(
             &mut alice,
             &mut bob,
@@ -1095,7 +1946,7 @@ mod handle_malice {
         let mut packed_events = take_packed_events(&bob, 6);
         packed_events.push(invalid_resp);
         // Knowledge of Alice and Bob, and the invalid_resp.
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
         assert_handling_invalid_response(
             &mut alice,
             &mut carol,
@@ -1128,9 +1979,7 @@ mod handle_malice {
         let invalid_resp_hash = invalid_resp.compute_hash();
         let expected_malice = Malice::InvalidResponse(Box::new(invalid_resp.clone()));
 
-        let invalid_response_msg = Response {
-            packed_events: vec![valid_resp, invalid_resp.clone()],
-        };
+        let invalid_response_msg = Response::new(vec![valid_resp, invalid_resp.clone()]);
         assert_handling_invalid_response(
             &mut alice,
             &mut bob,
@@ -1141,7 +1990,7 @@ mod handle_malice {
 
         let mut packed_events = take_packed_events(&alice, alice.graph().len());
         packed_events.push(invalid_resp);
-        let invalid_response_msg = Response { packed_events };
+        let invalid_response_msg = Response::new(packed_events);
         assert_handling_invalid_response(
             &mut alice,
             &mut carol,
@@ -1420,9 +2269,7 @@ mod handle_malice {
         let a_1 = unwrap!(nth_event(alice.graph(), 1).pack(alice.event_context()));
         let a_2_0 =
             PackedEvent::new_requesting(alice_id.clone(), bob_id.clone(), a_1.compute_hash());
-        let mut request = Request {
-            packed_events: vec![a_0.clone(), a_1.clone(), a_2_0.clone()],
-        };
+        let mut request = Request::new(vec![a_0.clone(), a_1.clone(), a_2_0.clone()]);
         unwrap!(bob.handle_request(&alice_id, request.clone()));
 
         // [A_0, A_1, A_2,1, A_3,0] will be sent to Carol.
@@ -1517,9 +2364,8 @@ mod handle_malice {
             PackedEvent::new_requesting(alice_id.clone(), bob_id.clone(), a_2_0.compute_hash());
         let a_3_1 =
             PackedEvent::new_requesting(alice_id.clone(), bob_id.clone(), a_2_1.compute_hash());
-        let mut request = Request {
-            packed_events: vec![a_0.clone(), a_1.clone(), a_2_0.clone(), a_3_0.clone()],
-        };
+        let mut request =
+            Request::new(vec![a_0.clone(), a_1.clone(), a_2_0.clone(), a_3_0.clone()]);
         unwrap!(bob.handle_request(&alice_id, request.clone()));
 
         // [A_0, A_1, A_2,1, A_3,1] will be sent to Bob second.  Bob should accuse A_1.
@@ -1582,9 +2428,7 @@ mod handle_malice {
         let a_1 = unwrap!(nth_event(alice.graph(), 1).pack(alice.event_context()));
         let a_2_0 =
             PackedEvent::new_requesting(alice_id.clone(), bob_id.clone(), a_1.compute_hash());
-        let mut request = Request {
-            packed_events: vec![a_0.clone(), a_1.clone(), a_2_0.clone()],
-        };
+        let mut request = Request::new(vec![a_0.clone(), a_1.clone(), a_2_0.clone()]);
         unwrap!(bob.handle_request(&alice_id, request.clone()));
 
         // [A_0, A_1, A_2,1] will be sent to Carol.
@@ -1654,13 +2498,11 @@ mod handle_malice {
         }
 
         // Send Bob's message to Alice.  B_2 should be rejected as invalid.
-        let message = Request {
-            packed_events: vec![
-                unwrap!(b_0.pack(bob.event_context())),
-                unwrap!(b_1.pack(bob.event_context())),
-                b_2_packed.clone(),
-            ],
-        };
+        let message = Request::new(vec![
+            unwrap!(b_0.pack(bob.event_context())),
+            unwrap!(b_1.pack(bob.event_context())),
+            b_2_packed.clone(),
+        ]);
         assert_eq!(
             alice.handle_request(bob.our_pub_id(), message),
             Err(Error::InvalidEvent)
@@ -23,6 +23,7 @@ use crate::{
 };
 #[cfg(any(test, feature = "testing"))]
 use crate::{
+    hash::Hasher,
     mock::{PeerId, Transaction},
     observation::{ConsensusMode, ObservationStore},
 };
@@ -47,6 +48,45 @@ pub(crate) enum Cause<V, E, P> {
     Initial,
 }
 
+impl<V, E, P> Cause<V, E, P> {
+    // Converts the parent identifiers and the recipient of this cause into another representation.
+    pub(super) fn try_map<F, Q, Err>(
+        self,
+        mut map_parent: impl FnMut(E) -> Result<F, Err>,
+        map_peer: impl FnOnce(P) -> Result<Q, Err>,
+    ) -> Result<Cause<V, F, Q>, Err> {
+        let cause = match self {
+            Cause::Requesting {
+                self_parent,
+                recipient,
+            } => Cause::Requesting {
+                self_parent: map_parent(self_parent)?,
+                recipient: map_peer(recipient)?,
+            },
+            Cause::Request {
+                self_parent,
+                other_parent,
+            } => Cause::Request {
+                self_parent: map_parent(self_parent)?,
+                other_parent: map_parent(other_parent)?,
+            },
+            Cause::Response {
+                self_parent,
+                other_parent,
+            } => Cause::Response {
+                self_parent: map_parent(self_parent)?,
+                other_parent: map_parent(other_parent)?,
+            },
+            Cause::Observation { self_parent, vote } => Cause::Observation {
+                self_parent: map_parent(self_parent)?,
+                vote,
+            },
+            Cause::Initial => Cause::Initial,
+        };
+        Ok(cause)
+    }
+}
+
 impl<P: PublicId> Cause<VoteKey<P>, EventIndex, PeerIndex> {
     pub(crate) fn unpack<T: NetworkEvent, S: SecretId<PublicId = P>>(
         packed_cause: Cause<Vote<T, P>, EventHash, P>,
@@ -87,7 +127,8 @@ impl<P: PublicId> Cause<VoteKey<P>, EventIndex, PeerIndex> {
             Cause::Observation { self_parent, vote } => {
                 let self_parent = self_parent_index(ctx.graph, &self_parent)?;
 
-                let (vote_key, observation) = VoteKey::new(vote, creator, ctx.consensus_mode);
+                let (vote_key, observation) =
+                    VoteKey::new(vote, creator, ctx.consensus_mode, ctx.hasher);
                 let payload_key = *vote_key.payload_key();
 
                 (
@@ -209,7 +250,8 @@ impl Cause<VoteKey<PeerId>, EventIndex, PeerIndex> {
                 other_parent,
             },
             Cause::Observation { vote, .. } => {
-                let (vote_key, observation) = VoteKey::new(vote, creator, consensus_mode);
+                let (vote_key, observation) =
+                    VoteKey::new(vote, creator, consensus_mode, Hasher::default());
                 let _ = observations
                     .entry(*vote_key.payload_key())
                     .or_insert_with(|| ObservationInfo::new(observation));
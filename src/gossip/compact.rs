@@ -0,0 +1,196 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{content::Content, event_hash::EventHash, packed_event::PackedEvent};
+use crate::{error::Error, hash::Hasher, id::PublicId, network_event::NetworkEvent, vote::Vote};
+use std::{
+    collections::btree_map::{BTreeMap, Entry},
+    convert::Infallible,
+};
+
+/// Compact encoding of the events of a gossip message.  Peer IDs are replaced by their position in
+/// a dictionary sent once per message, and parents which are part of the same message are referred
+/// to by their position rather than by their hash.  Decoding yields the exact same `PackedEvent`s,
+/// so event hashes and signatures are unaffected.
+#[serde(bound = "")]
+#[derive(Serialize, Deserialize)]
+pub(super) struct CompactEvents<T: NetworkEvent, P: PublicId> {
+    peers: Vec<P>,
+    events: Vec<CompactEvent<T, P>>,
+}
+
+impl<T: NetworkEvent, P: PublicId> CompactEvents<T, P> {
+    pub fn encode(packed_events: &[PackedEvent<T, P>], hasher: Hasher) -> Self {
+        let mut peers = PeerDictionary::default();
+        let mut positions = BTreeMap::new();
+        let mut events = Vec::with_capacity(packed_events.len());
+
+        for (position, packed_event) in packed_events.iter().enumerate() {
+            let content = packed_event.content.clone();
+            let creator = peers.insert(content.creator);
+            let cause = content.cause.try_map::<_, _, Infallible>(
+                |hash| {
+                    Ok(positions
+                        .get(&hash)
+                        .map_or(ParentRef::Hash(hash), |&position| {
+                            ParentRef::Position(position)
+                        }))
+                },
+                |recipient| Ok(peers.insert(recipient)),
+            );
+            let cause = match cause {
+                Ok(cause) => cause,
+                Err(never) => match never {},
+            };
+
+            let _ = positions.insert(packed_event.compute_hash_with(hasher), position as u32);
+            events.push(CompactEvent {
+                content: Content { creator, cause },
+                signature: packed_event.signature.clone(),
+            });
+        }
+
+        Self {
+            peers: peers.peers,
+            events,
+        }
+    }
+
+    // Returns the decoded events along with their hashes, which had to be computed anyway.
+    pub fn decode(self, hasher: Hasher) -> Result<(Vec<PackedEvent<T, P>>, Vec<EventHash>), Error> {
+        let peers = self.peers;
+        let peer = |index: u32| {
+            peers
+                .get(index as usize)
+                .cloned()
+                .ok_or(Error::InvalidMessage)
+        };
+
+        let mut hashes: Vec<EventHash> = Vec::with_capacity(self.events.len());
+        let mut packed_events = Vec::with_capacity(self.events.len());
+
+        for CompactEvent { content, signature } in self.events {
+            let creator = peer(content.creator)?;
+            // Only events preceding this one in the message can be referred to by position.
+            let cause = content.cause.try_map(
+                |parent| match parent {
+                    ParentRef::Position(position) => hashes
+                        .get(position as usize)
+                        .cloned()
+                        .ok_or(Error::InvalidMessage),
+                    ParentRef::Hash(hash) => Ok(hash),
+                },
+                peer,
+            )?;
+
+            let packed_event = PackedEvent {
+                content: Content { creator, cause },
+                signature,
+            };
+            hashes.push(packed_event.compute_hash_with(hasher));
+            packed_events.push(packed_event);
+        }
+
+        Ok((packed_events, hashes))
+    }
+}
+
+#[serde(bound = "")]
+#[derive(Serialize, Deserialize)]
+struct CompactEvent<T: NetworkEvent, P: PublicId> {
+    content: Content<Vote<T, P>, ParentRef, u32>,
+    signature: P::Signature,
+}
+
+// Reference to a parent event from within a compactly encoded message.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum ParentRef {
+    // Position of the parent among the events of the same message.
+    Position(u32),
+    // Hash of a parent which isn't part of the message.
+    Hash(EventHash),
+}
+
+struct PeerDictionary<P> {
+    peers: Vec<P>,
+    positions: BTreeMap<P, u32>,
+}
+
+impl<P: PublicId> PeerDictionary<P> {
+    // Returns the position of the given peer, adding it to the dictionary if needed.
+    fn insert(&mut self, peer_id: P) -> u32 {
+        match self.positions.entry(peer_id) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let position = self.peers.len() as u32;
+                self.peers.push(entry.key().clone());
+                *entry.insert(position)
+            }
+        }
+    }
+}
+
+impl<P> Default for PeerDictionary<P> {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            positions: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        gossip::Cause,
+        mock::{PeerId, Transaction},
+    };
+
+    #[test]
+    fn invalid_references() {
+        let alice = PeerId::new("Alice");
+        let a_0 = PackedEvent::<Transaction, _>::new_initial(alice.clone());
+        let a_1 =
+            PackedEvent::new_requesting(alice.clone(), PeerId::new("Bob"), a_0.compute_hash());
+        let packed_events = vec![a_0, a_1];
+
+        let compact = CompactEvents::encode(&packed_events, Hasher::default());
+        assert_eq!(compact.peers.len(), 2);
+        let hashes: Vec<_> = packed_events
+            .iter()
+            .map(PackedEvent::compute_hash)
+            .collect();
+        assert_eq!(
+            unwrap!(compact.decode(Hasher::default())),
+            (packed_events.clone(), hashes)
+        );
+
+        // Parents can only refer to preceding events.
+        let mut compact = CompactEvents::encode(&packed_events, Hasher::default());
+        if let Cause::Requesting {
+            ref mut self_parent,
+            ..
+        } = compact.events[1].content.cause
+        {
+            *self_parent = ParentRef::Position(1);
+        }
+        assert_eq!(
+            compact.decode(Hasher::default()).err(),
+            Some(Error::InvalidMessage)
+        );
+
+        // Peers must be in the dictionary.
+        let mut compact = CompactEvents::encode(&packed_events, Hasher::default());
+        compact.events[1].content.creator = 2;
+        assert_eq!(
+            compact.decode(Hasher::default()).err(),
+            Some(Error::InvalidMessage)
+        );
+    }
+}
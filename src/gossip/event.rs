@@ -19,7 +19,7 @@ use super::{
 use crate::observation::MaliceInput;
 use crate::{
     error::Error,
-    hash::Hash,
+    hash::Hasher,
     id::{PublicId, SecretId},
     network_event::NetworkEvent,
     observation::{Observation, ObservationForStore, ObservationKey, ObservationStore},
@@ -56,7 +56,8 @@ impl<P: PublicId> Event<P> {
                 recipient: recipient.clone(),
             },
         };
-        let (hash, signature) = compute_event_hash_and_signature(&content, ctx.peer_list.our_id());
+        let (hash, signature) =
+            compute_event_hash_and_signature(&content, ctx.peer_list.our_id(), ctx.hasher);
         let content = Content {
             creator: PeerIndex::OUR,
             cause: Cause::Requesting {
@@ -87,7 +88,8 @@ impl<P: PublicId> Event<P> {
                 other_parent: cause::other_parent_hash(ctx.graph, other_parent)?,
             },
         };
-        let (hash, signature) = compute_event_hash_and_signature(&content, ctx.peer_list.our_id());
+        let (hash, signature) =
+            compute_event_hash_and_signature(&content, ctx.peer_list.our_id(), ctx.hasher);
 
         let content = Content {
             creator: PeerIndex::OUR,
@@ -119,7 +121,8 @@ impl<P: PublicId> Event<P> {
                 other_parent: cause::other_parent_hash(ctx.graph, other_parent)?,
             },
         };
-        let (hash, signature) = compute_event_hash_and_signature(&content, ctx.peer_list.our_id());
+        let (hash, signature) =
+            compute_event_hash_and_signature(&content, ctx.peer_list.our_id(), ctx.hasher);
 
         let content = Content {
             creator: PeerIndex::OUR,
@@ -153,7 +156,8 @@ impl<P: PublicId> Event<P> {
                 vote,
             },
         };
-        let (hash, signature) = compute_event_hash_and_signature(&content, ctx.peer_list.our_id());
+        let (hash, signature) =
+            compute_event_hash_and_signature(&content, ctx.peer_list.our_id(), ctx.hasher);
         let graph = ctx.graph;
         let peer_list = ctx.peer_list;
         let (content, observation_for_store) = Content::unpack(content, ctx)?;
@@ -172,7 +176,8 @@ impl<P: PublicId> Event<P> {
             creator: ctx.peer_list.our_pub_id().clone(),
             cause: Cause::Initial,
         };
-        let (hash, signature) = compute_event_hash_and_signature(&content, ctx.peer_list.our_id());
+        let (hash, signature) =
+            compute_event_hash_and_signature(&content, ctx.peer_list.our_id(), ctx.hasher);
 
         let content = Content {
             creator: PeerIndex::OUR,
@@ -197,22 +202,29 @@ impl<P: PublicId> Event<P> {
         }
     }
 
-    // Creates an event from a `PackedEvent`.
+    // Creates an event from a `PackedEvent`, after verifying its signature.
+    //
+    // Returns the same as `unpack_verified`, or `Err(Error::SignatureFailure)` if signature
+    // validation fails.
+    #[cfg(test)]
+    pub fn unpack<T: NetworkEvent, S: SecretId<PublicId = P>>(
+        packed_event: PackedEvent<T, P>,
+        ctx: EventContextRef<T, S>,
+    ) -> Result<Option<UnpackedEvent<T, P>>, Error> {
+        Self::unpack_verified(VerifiedEvent::verify(packed_event, ctx.hasher)?, ctx)
+    }
+
+    // Creates an event from a `PackedEvent` whose signature has been verified.
     //
     // Returns:
     //   - `Ok(None)` if the event already exists
-    //   - `Err(Error::SignatureFailure)` if signature validation fails
     //   - `Err(Error::UnknownParent)` if the event indicates it should have an ancestor, but the
     //     ancestor isn't in `events`.
-    pub fn unpack<T: NetworkEvent, S: SecretId<PublicId = P>>(
-        packed_event: PackedEvent<T, P>,
+    pub fn unpack_verified<T: NetworkEvent, S: SecretId<PublicId = P>>(
+        verified_event: VerifiedEvent<T, P>,
         ctx: EventContextRef<T, S>,
     ) -> Result<Option<UnpackedEvent<T, P>>, Error> {
-        let hash = compute_event_hash_and_verify_signature(
-            &packed_event.content,
-            &packed_event.signature,
-        )?;
-
+        let VerifiedEvent { packed_event, hash } = verified_event;
         if ctx.graph.contains(&hash) {
             return Ok(None);
         }
@@ -440,7 +452,6 @@ impl<P: PublicId> Event<P> {
         }
     }
 
-    #[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
     pub fn cause(&self) -> &Cause<VoteKey<P>, EventIndex, PeerIndex> {
         &self.content.cause
     }
@@ -531,7 +542,8 @@ impl Event<PeerId> {
             creator: creator.clone(),
             cause,
         };
-        let (hash, signature) = compute_event_hash_and_signature(&content, creator);
+        let (hash, signature) =
+            compute_event_hash_and_signature(&content, creator, Hasher::default());
 
         let creator = unwrap!(peer_list.get_index(creator));
         let cause = Cause::unpack_from_dot_input(
@@ -577,6 +589,83 @@ impl Event<PeerId> {
     }
 }
 
+// A `PackedEvent` whose signature has been verified, along with its hash.
+pub(crate) struct VerifiedEvent<T: NetworkEvent, P: PublicId> {
+    packed_event: PackedEvent<T, P>,
+    hash: EventHash,
+}
+
+impl<T: NetworkEvent, P: PublicId> VerifiedEvent<T, P> {
+    #[cfg(test)]
+    pub fn verify(packed_event: PackedEvent<T, P>, hasher: Hasher) -> Result<Self, Error> {
+        let hash = packed_event.compute_hash_with(hasher);
+        let (mut verified_events, result) = Self::verify_all(vec![(packed_event, hash)], |_| false);
+        result.and_then(|()| verified_events.pop().ok_or(Error::Logic))
+    }
+
+    // Verifies the signatures of all the given events, each along with its hash, as a single
+    // batch.  Events for which `is_known` returns `true` are left out without being verified.
+    //
+    // Returns the remaining events preceding the first one with an invalid signature, along with
+    // `Err(Error::SignatureFailure)` if there is such an event.
+    pub fn verify_all<F>(
+        hashed_events: Vec<(PackedEvent<T, P>, EventHash)>,
+        is_known: F,
+    ) -> (Vec<Self>, Result<(), Error>)
+    where
+        F: Fn(&EventHash) -> bool,
+    {
+        let hashed_events: Vec<_> = hashed_events
+            .into_iter()
+            .filter(|(_, hash)| !is_known(hash))
+            .collect();
+        let serialised_contents: Vec<_> = hashed_events
+            .iter()
+            .map(|(packed_event, _)| serialise(&packed_event.content))
+            .collect();
+
+        let valid_count = {
+            let batch: Vec<_> = hashed_events
+                .iter()
+                .zip(&serialised_contents)
+                .map(|((packed_event, _), serialised_content)| {
+                    (
+                        &packed_event.content.creator,
+                        &packed_event.signature,
+                        serialised_content.as_slice(),
+                    )
+                })
+                .collect();
+
+            if batch.is_empty() || P::verify_signatures(&batch) {
+                batch.len()
+            } else {
+                // Find the culprit.  Should the signatures all turn out to be valid on their own,
+                // trust the individual verification.
+                batch
+                    .iter()
+                    .position(|(creator, signature, serialised_content)| {
+                        !creator.verify_signature(signature, serialised_content)
+                    })
+                    .unwrap_or(batch.len())
+            }
+        };
+
+        let result = if valid_count < hashed_events.len() {
+            Err(Error::SignatureFailure)
+        } else {
+            Ok(())
+        };
+        let verified_events = hashed_events
+            .into_iter()
+            .take(valid_count)
+            .map(|(packed_event, hash)| Self { packed_event, hash })
+            .collect();
+
+        (verified_events, result)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct UnpackedEvent<T: NetworkEvent, P: PublicId> {
     pub event: Event<P>,
@@ -657,29 +746,15 @@ fn compute_index_by_creator<P: PublicId>(self_parent: Option<&Event<P>>) -> usiz
 fn compute_event_hash_and_signature<T: NetworkEvent, S: SecretId>(
     content: &Content<Vote<T, S::PublicId>, EventHash, S::PublicId>,
     our_id: &S,
+    hasher: Hasher,
 ) -> (EventHash, <S::PublicId as PublicId>::Signature) {
     let serialised_content = serialise(&content);
-    let hash = EventHash(Hash::from(serialised_content.as_slice()));
+    let hash = EventHash::new(hasher, &serialised_content);
     let signature = our_id.sign_detached(&serialised_content);
 
     (hash, signature)
 }
 
-fn compute_event_hash_and_verify_signature<T: NetworkEvent, P: PublicId>(
-    content: &Content<Vote<T, P>, EventHash, P>,
-    signature: &P::Signature,
-) -> Result<EventHash, Error> {
-    let serialised_content = serialise(content);
-    if content
-        .creator
-        .verify_signature(signature, &serialised_content)
-    {
-        Ok(EventHash(Hash::from(serialised_content.as_slice())))
-    } else {
-        Err(Error::SignatureFailure)
-    }
-}
-
 #[cfg(any(test, feature = "testing"))]
 fn get_creator_initial<S: SecretId>(peer_list: &PeerList<S>, creator: PeerIndex) -> char {
     peer_list
@@ -726,6 +801,7 @@ mod tests {
         observation::Observation,
         peer_list::PeerState,
     };
+    use std::cell::RefCell;
 
     fn create_event_with_single_peer(id: &str) -> (EventContext, Event<PeerId>) {
         let context = EventContext::new(PeerId::new(id));
@@ -973,4 +1049,123 @@ mod tests {
             panic!("Expected SignatureFailure, but got {:?}", error);
         }
     }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn verify_all_signatures() {
+        let alice = PeerId::new("Alice");
+        let a_0 = PackedEvent::<Transaction, _>::new_initial(alice.clone());
+        let mut a_1 =
+            PackedEvent::new_requesting(alice.clone(), PeerId::new("Bob"), a_0.compute_hash());
+        let a_2 =
+            PackedEvent::new_requesting(alice.clone(), PeerId::new("Carol"), a_1.compute_hash());
+        let with_hash =
+            |packed_event: &PackedEvent<_, _>| (packed_event.clone(), packed_event.compute_hash());
+        let hashes = |verified_events: Vec<VerifiedEvent<_, _>>| {
+            verified_events
+                .into_iter()
+                .map(|verified_event| verified_event.hash)
+                .collect::<Vec<_>>()
+        };
+
+        let (verified_events, result) = VerifiedEvent::verify_all(
+            vec![with_hash(&a_0), with_hash(&a_1), with_hash(&a_2)],
+            |_| false,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            hashes(verified_events),
+            vec![a_0.compute_hash(), a_1.compute_hash(), a_2.compute_hash()]
+        );
+
+        // Only the events preceding the invalid one are returned.
+        a_1.signature = alice.sign_detached(&[123]);
+        let (verified_events, result) = VerifiedEvent::verify_all(
+            vec![with_hash(&a_0), with_hash(&a_1), with_hash(&a_2)],
+            |_| false,
+        );
+        assert_eq!(result, Err(Error::SignatureFailure));
+        assert_eq!(hashes(verified_events), vec![a_0.compute_hash()]);
+
+        // Known events are left out, even with an invalid signature.
+        let a_1_hash = a_1.compute_hash();
+        let (verified_events, result) = VerifiedEvent::verify_all(
+            vec![with_hash(&a_0), with_hash(&a_1), with_hash(&a_2)],
+            |hash| *hash == a_1_hash,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            hashes(verified_events),
+            vec![a_0.compute_hash(), a_2.compute_hash()]
+        );
+    }
+
+    // Public ID recording the size of each batch of signatures it is asked to verify.
+    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Debug)]
+    struct BatchingId(PeerId);
+
+    thread_local! {
+        static BATCH_SIZES: RefCell<Vec<usize>> = RefCell::new(vec![]);
+    }
+
+    impl PublicId for BatchingId {
+        type Signature = <PeerId as PublicId>::Signature;
+
+        fn verify_signature(&self, signature: &Self::Signature, data: &[u8]) -> bool {
+            self.0.verify_signature(signature, data)
+        }
+
+        fn verify_signatures(batch: &[(&Self, &Self::Signature, &[u8])]) -> bool {
+            BATCH_SIZES.with(|batch_sizes| batch_sizes.borrow_mut().push(batch.len()));
+            batch
+                .iter()
+                .all(|(public_id, signature, data)| public_id.verify_signature(signature, data))
+        }
+    }
+
+    #[test]
+    fn verify_all_signatures_as_one_batch() {
+        let alice = PeerId::new("Alice");
+        let new_event = |cause| {
+            let content = Content {
+                creator: BatchingId(alice.clone()),
+                cause,
+            };
+            let signature = alice.sign_detached(&serialise(&content));
+            let packed_event = PackedEvent::<Transaction, _> { content, signature };
+            let hash = packed_event.compute_hash();
+            (packed_event, hash)
+        };
+        let take_batch_sizes = || BATCH_SIZES.with(|batch_sizes| batch_sizes.replace(vec![]));
+
+        let a_0 = new_event(Cause::Initial);
+        let a_1 = new_event(Cause::Requesting {
+            self_parent: a_0.1,
+            recipient: BatchingId(PeerId::new("Bob")),
+        });
+        let a_2 = new_event(Cause::Requesting {
+            self_parent: a_1.1,
+            recipient: BatchingId(PeerId::new("Carol")),
+        });
+        let a_0_hash = a_0.1;
+
+        let (verified_events, result) =
+            VerifiedEvent::verify_all(vec![a_0.clone(), a_1.clone(), a_2.clone()], |_| false);
+        assert_eq!(result, Ok(()));
+        assert_eq!(verified_events.len(), 3);
+        assert_eq!(take_batch_sizes(), vec![3]);
+
+        // Known events aren't part of the batch.
+        let (verified_events, result) =
+            VerifiedEvent::verify_all(vec![a_0.clone(), a_1, a_2], |hash| *hash == a_0_hash);
+        assert_eq!(result, Ok(()));
+        assert_eq!(verified_events.len(), 2);
+        assert_eq!(take_batch_sizes(), vec![2]);
+
+        // There's no batch at all if every event is known.
+        let (verified_events, result) = VerifiedEvent::verify_all(vec![a_0], |_| true);
+        assert_eq!(result, Ok(()));
+        assert!(verified_events.is_empty());
+        assert!(take_batch_sizes().is_empty());
+    }
 }
@@ -10,6 +10,7 @@
 pub(crate) use self::tests::EventContext;
 use super::graph::Graph;
 use crate::{
+    hash::Hasher,
     id::SecretId,
     network_event::NetworkEvent,
     observation::{ConsensusMode, ObservationStore},
@@ -21,6 +22,7 @@ pub(crate) struct EventContextRef<'a, T: NetworkEvent, S: SecretId> {
     pub(crate) peer_list: &'a PeerList<S>,
     pub(crate) observations: &'a ObservationStore<T, S::PublicId>,
     pub(crate) consensus_mode: ConsensusMode,
+    pub(crate) hasher: Hasher,
 }
 
 // `#[derive(Clone)]` doesn't work here for some reason...
@@ -31,6 +33,7 @@ impl<'a, T: NetworkEvent, S: SecretId> Clone for EventContextRef<'a, T, S> {
             peer_list: self.peer_list,
             observations: self.observations,
             consensus_mode: self.consensus_mode,
+            hasher: self.hasher,
         }
     }
 }
@@ -68,6 +71,7 @@ mod tests {
                 peer_list: &self.peer_list,
                 observations: &self.observations,
                 consensus_mode: self.consensus_mode,
+                hasher: Hasher::default(),
             }
         }
     }
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::hash::Hash;
+use crate::hash::{FullDisplay, Hash, HashDomain, Hasher};
 use std::fmt::{self, Debug, Formatter};
 
 /// Hash of the event contents.
@@ -20,6 +20,14 @@ impl Debug for EventHash {
 }
 
 impl EventHash {
+    pub(crate) fn new(hasher: Hasher, serialised_content: &[u8]) -> Self {
+        EventHash(hasher.hash(HashDomain::Event, serialised_content))
+    }
+
+    pub(crate) fn full_display(&self) -> FullDisplay {
+        self.0.full_display()
+    }
+
     #[cfg(any(test, feature = "testing"))]
     pub(crate) const ZERO: Self = EventHash(Hash::ZERO);
 }
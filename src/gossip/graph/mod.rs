@@ -102,7 +102,6 @@ impl<P: PublicId> Graph<P> {
     }
 
     /// Gets `Event` by the given `hash`, if it exists.
-    #[cfg(any(feature = "malice-detection", feature = "dump-graphs"))]
     pub fn get_by_hash<'a>(&'a self, hash: &EventHash) -> Option<IndexedEventRef<'a, P>> {
         self.get_index(hash).and_then(|index| self.get(index))
     }
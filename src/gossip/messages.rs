@@ -6,30 +6,877 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{gossip::packed_event::PackedEvent, id::PublicId, network_event::NetworkEvent};
+use super::{compact::CompactEvents, event_hash::EventHash, packed_event::PackedEvent};
+use crate::{
+    codec::{self, Codec},
+    error::Error,
+    hash::{Hash, HashAlgorithm, Hasher, Sha3},
+    id::PublicId,
+    network_event::NetworkEvent,
+};
+use maidsafe_utilities::serialisation::{self, SerialisationError};
+use serde::{
+    de::{self, Deserialize, Deserializer},
+    ser::{self, Serialize, Serializer},
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    marker::PhantomData,
+};
 
-/// A gossip request message.
-#[serde(bound = "")]
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
-pub struct Request<T: NetworkEvent, P: PublicId> {
+/// Version of the wire protocol used to encode gossip messages.
+///
+/// * Version 1 sends the events of a message as a list of `PackedEvent`s.
+/// * Version 2 encodes them more compactly, referring to peers and to parents sent in the same
+///   message by their position.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Debug)]
+pub struct ProtocolVersion(pub u32);
+
+impl ProtocolVersion {
+    /// The newest version implemented by this crate.
+    pub const CURRENT: Self = ProtocolVersion(2);
+    /// The oldest version this crate is still able to handle.  New sections start on this version
+    /// and only move to a newer one via a consensused `Observation::Upgrade`.
+    pub const MIN_SUPPORTED: Self = ProtocolVersion(1);
+}
+
+// First version encoding the events of gossip messages using `CompactEvents`.
+const COMPACT_EVENTS_VERSION: ProtocolVersion = ProtocolVersion(2);
+
+impl Display for ProtocolVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// Inclusive range of protocol versions a peer is able to handle.  Every gossip message carries
+/// the sender's range so that the recipient can pick a version both sides understand.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub struct SupportedVersions {
+    /// Oldest supported version.
+    pub min: ProtocolVersion,
+    /// Newest supported version.
+    pub max: ProtocolVersion,
+}
+
+impl SupportedVersions {
+    /// The range of versions supported by this crate.
+    pub const OURS: Self = SupportedVersions {
+        min: ProtocolVersion::MIN_SUPPORTED,
+        max: ProtocolVersion::CURRENT,
+    };
+
+    /// Returns whether `version` lies within this range.
+    pub fn contains(&self, version: ProtocolVersion) -> bool {
+        self.min <= version && version <= self.max
+    }
+
+    /// Returns the newest version contained in both ranges, if any.
+    pub fn highest_common(&self, other: &Self) -> Option<ProtocolVersion> {
+        let version = self.max.min(other.max);
+        if version >= self.min && version >= other.min {
+            Some(version)
+        } else {
+            None
+        }
+    }
+}
+
+/// A gossip request message.  `H` is the algorithm used to hash the events, which must match the
+/// one of the `Parsec` instances exchanging the message.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Request<T: NetworkEvent, P: PublicId, H: HashAlgorithm = Sha3> {
+    pub(crate) version: ProtocolVersion,
+    pub(crate) supported_versions: SupportedVersions,
     pub(crate) packed_events: Vec<PackedEvent<T, P>>,
+    event_hashes: DecodedHashes,
+    // Encoded events of a message with a version we can't decode, kept as received so that the
+    // message serialises back to the same bytes.
+    undecoded_events: Option<Vec<u8>>,
+    hash_algorithm: PhantomData<H>,
 }
 
-impl<T: NetworkEvent, P: PublicId> Request<T, P> {
+impl<T: NetworkEvent, P: PublicId, H: HashAlgorithm> Request<T, P, H> {
     pub(crate) fn new(packed_events: Vec<PackedEvent<T, P>>) -> Self {
-        Self { packed_events }
+        Self::with_version(
+            ProtocolVersion::CURRENT,
+            SupportedVersions::OURS,
+            packed_events,
+        )
+    }
+
+    pub(crate) fn with_version(
+        version: ProtocolVersion,
+        supported_versions: SupportedVersions,
+        packed_events: Vec<PackedEvent<T, P>>,
+    ) -> Self {
+        Self {
+            version,
+            supported_versions,
+            packed_events,
+            event_hashes: DecodedHashes::default(),
+            undecoded_events: None,
+            hash_algorithm: PhantomData,
+        }
+    }
+
+    fn from_parts(
+        (version, supported_versions, packed_events, event_hashes): MessageParts<T, P>,
+    ) -> Self {
+        Self {
+            version,
+            supported_versions,
+            packed_events,
+            event_hashes: DecodedHashes(event_hashes),
+            undecoded_events: None,
+            hash_algorithm: PhantomData,
+        }
+    }
+
+    fn from_undecodable(envelope: Envelope) -> Self {
+        Self {
+            version: envelope.version,
+            supported_versions: envelope.supported_versions,
+            packed_events: vec![],
+            event_hashes: DecodedHashes::default(),
+            undecoded_events: Some(envelope.events),
+            hash_algorithm: PhantomData,
+        }
+    }
+
+    // Returns the events of this message along with their hashes.
+    pub(crate) fn into_hashed_events(self) -> Vec<(PackedEvent<T, P>, EventHash)> {
+        self.event_hashes
+            .zip_with(self.packed_events, Hasher::new::<H>())
+    }
+
+    /// Version of the protocol this message is encoded with.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Protocol versions supported by the sender of this message.
+    pub fn supported_versions(&self) -> SupportedVersions {
+        self.supported_versions
+    }
+
+    /// Encodes this request to be sent over the wire using the given codec.
+    pub fn encode<C: Codec>(&self) -> Result<Vec<u8>, Error> {
+        encode_message::<C, _, _>(
+            self.version,
+            self.supported_versions,
+            &self.packed_events,
+            self.undecoded_events.as_ref(),
+            Hasher::new::<H>(),
+        )
+    }
+
+    /// Decodes a request received over the wire, which was encoded with the given codec.  Fails
+    /// with `Error::IncompatibleProtocolVersion` if it uses a version this crate can't decode.
+    pub fn decode<C: Codec>(bytes: &[u8]) -> Result<Self, Error> {
+        decode_message::<C, _, _>(bytes, Hasher::new::<H>()).map(Self::from_parts)
     }
 }
 
-/// A gossip response message.
-#[serde(bound = "")]
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
-pub struct Response<T: NetworkEvent, P: PublicId> {
+/// A gossip response message.  `H` is the algorithm used to hash the events, which must match the
+/// one of the `Parsec` instances exchanging the message.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Response<T: NetworkEvent, P: PublicId, H: HashAlgorithm = Sha3> {
+    pub(crate) version: ProtocolVersion,
+    pub(crate) supported_versions: SupportedVersions,
     pub(crate) packed_events: Vec<PackedEvent<T, P>>,
+    event_hashes: DecodedHashes,
+    // Encoded events of a message with a version we can't decode, kept as received so that the
+    // message serialises back to the same bytes.
+    undecoded_events: Option<Vec<u8>>,
+    hash_algorithm: PhantomData<H>,
 }
 
-impl<T: NetworkEvent, P: PublicId> Response<T, P> {
+impl<T: NetworkEvent, P: PublicId, H: HashAlgorithm> Response<T, P, H> {
     pub(crate) fn new(packed_events: Vec<PackedEvent<T, P>>) -> Self {
-        Self { packed_events }
+        Self::with_version(
+            ProtocolVersion::CURRENT,
+            SupportedVersions::OURS,
+            packed_events,
+        )
+    }
+
+    pub(crate) fn with_version(
+        version: ProtocolVersion,
+        supported_versions: SupportedVersions,
+        packed_events: Vec<PackedEvent<T, P>>,
+    ) -> Self {
+        Self {
+            version,
+            supported_versions,
+            packed_events,
+            event_hashes: DecodedHashes::default(),
+            undecoded_events: None,
+            hash_algorithm: PhantomData,
+        }
+    }
+
+    fn from_parts(
+        (version, supported_versions, packed_events, event_hashes): MessageParts<T, P>,
+    ) -> Self {
+        Self {
+            version,
+            supported_versions,
+            packed_events,
+            event_hashes: DecodedHashes(event_hashes),
+            undecoded_events: None,
+            hash_algorithm: PhantomData,
+        }
+    }
+
+    fn from_undecodable(envelope: Envelope) -> Self {
+        Self {
+            version: envelope.version,
+            supported_versions: envelope.supported_versions,
+            packed_events: vec![],
+            event_hashes: DecodedHashes::default(),
+            undecoded_events: Some(envelope.events),
+            hash_algorithm: PhantomData,
+        }
+    }
+
+    // Returns the events of this message along with their hashes.
+    pub(crate) fn into_hashed_events(self) -> Vec<(PackedEvent<T, P>, EventHash)> {
+        self.event_hashes
+            .zip_with(self.packed_events, Hasher::new::<H>())
+    }
+
+    /// Version of the protocol this message is encoded with.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Protocol versions supported by the sender of this message.
+    pub fn supported_versions(&self) -> SupportedVersions {
+        self.supported_versions
+    }
+
+    /// Encodes this response to be sent over the wire using the given codec.
+    pub fn encode<C: Codec>(&self) -> Result<Vec<u8>, Error> {
+        encode_message::<C, _, _>(
+            self.version,
+            self.supported_versions,
+            &self.packed_events,
+            self.undecoded_events.as_ref(),
+            Hasher::new::<H>(),
+        )
+    }
+
+    /// Decodes a response received over the wire, which was encoded with the given codec.  Fails
+    /// with `Error::IncompatibleProtocolVersion` if it uses a version this crate can't decode.
+    pub fn decode<C: Codec>(bytes: &[u8]) -> Result<Self, Error> {
+        decode_message::<C, _, _>(bytes, Hasher::new::<H>()).map(Self::from_parts)
+    }
+}
+
+impl<T: NetworkEvent, P: PublicId, H: HashAlgorithm> Serialize for Request<T, P, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Envelope::seal(
+            self.version,
+            self.supported_versions,
+            &self.packed_events,
+            self.undecoded_events.as_ref(),
+            Hasher::new::<H>(),
+        )
+        .map_err(ser::Error::custom)?
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: NetworkEvent, P: PublicId, H: HashAlgorithm> Deserialize<'de> for Request<T, P, H> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let envelope = Envelope::deserialize(deserializer)?;
+        if envelope.is_supported() {
+            let parts = envelope
+                .open(Hasher::new::<H>())
+                .map_err(de::Error::custom)?;
+            Ok(Self::from_parts(parts))
+        } else {
+            // Keep the message without its events, for `Parsec` to reject it with
+            // `Error::IncompatibleProtocolVersion` rather than failing with an opaque error here.
+            Ok(Self::from_undecodable(envelope))
+        }
+    }
+}
+
+impl<T: NetworkEvent, P: PublicId, H: HashAlgorithm> Serialize for Response<T, P, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Envelope::seal(
+            self.version,
+            self.supported_versions,
+            &self.packed_events,
+            self.undecoded_events.as_ref(),
+            Hasher::new::<H>(),
+        )
+        .map_err(ser::Error::custom)?
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: NetworkEvent, P: PublicId, H: HashAlgorithm> Deserialize<'de> for Response<T, P, H> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let envelope = Envelope::deserialize(deserializer)?;
+        if envelope.is_supported() {
+            let parts = envelope
+                .open(Hasher::new::<H>())
+                .map_err(de::Error::custom)?;
+            Ok(Self::from_parts(parts))
+        } else {
+            // Keep the message without its events, for `Parsec` to reject it with
+            // `Error::IncompatibleProtocolVersion` rather than failing with an opaque error here.
+            Ok(Self::from_undecodable(envelope))
+        }
+    }
+}
+
+// The version, the sender's supported versions, the events and, if computed while decoding them,
+// the hashes of the events of a message.
+type MessageParts<T, P> = (
+    ProtocolVersion,
+    SupportedVersions,
+    Vec<PackedEvent<T, P>>,
+    Vec<EventHash>,
+);
+
+fn encode_message<C: Codec, T: NetworkEvent, P: PublicId>(
+    version: ProtocolVersion,
+    supported_versions: SupportedVersions,
+    packed_events: &[PackedEvent<T, P>],
+    undecoded_events: Option<&Vec<u8>>,
+    hasher: Hasher,
+) -> Result<Vec<u8>, Error> {
+    let envelope = Envelope::seal(
+        version,
+        supported_versions,
+        packed_events,
+        undecoded_events,
+        hasher,
+    )
+    .map_err(|error| {
+        debug!("Failed to encode the events of a message: {:?}", error);
+        Error::CodecFailure
+    })?;
+    if codec::is_canonical::<C>() {
+        return codec::encode::<C, _>(&envelope);
+    }
+
+    if undecoded_events.is_some() {
+        debug!(
+            "Can't re-encode message with unsupported protocol version {}",
+            version
+        );
+        return Err(Error::CodecFailure);
+    }
+    codec::encode::<C, _>(&CodecEnvelope {
+        version,
+        supported_versions,
+        digest: codec::digest(hasher, &envelope),
+        events: CodecEvents::encode(version, packed_events, hasher),
+    })
+}
+
+fn decode_message<C: Codec, T: NetworkEvent, P: PublicId>(
+    bytes: &[u8],
+    hasher: Hasher,
+) -> Result<MessageParts<T, P>, Error> {
+    if codec::is_canonical::<C>() {
+        return codec::decode::<C, Envelope>(bytes)?.open(hasher);
+    }
+
+    // Read the header on its own first, so that a message with a version we can't decode is
+    // reported as such rather than as a codec failure.
+    let header = codec::decode::<C, Header>(bytes)?;
+    check_supported(header.version, header.supported_versions)?;
+
+    let CodecEnvelope {
+        version,
+        supported_versions,
+        digest,
+        events,
+    } = codec::decode::<C, CodecEnvelope<T, P>>(bytes)?;
+    let (packed_events, event_hashes) = events.decode(hasher)?;
+    let envelope = Envelope::seal(version, supported_versions, &packed_events, None, hasher)
+        .map_err(|error| {
+            debug!("Failed to encode the events of a message: {:?}", error);
+            Error::CodecFailure
+        })?;
+    codec::check_digest(hasher, &envelope, &digest)?;
+    Ok((version, supported_versions, packed_events, event_hashes))
+}
+
+fn check_supported(
+    version: ProtocolVersion,
+    supported_versions: SupportedVersions,
+) -> Result<(), Error> {
+    if SupportedVersions::OURS.contains(version) {
+        Ok(())
+    } else {
+        debug!(
+            "Can't decode message with unsupported protocol version {}",
+            version
+        );
+        Err(Error::IncompatibleProtocolVersion {
+            version,
+            theirs: supported_versions,
+            ours: SupportedVersions::OURS,
+        })
+    }
+}
+
+// The events of a message and, if computed while decoding them, their hashes.
+type DecodedEvents<T, P> = (Vec<PackedEvent<T, P>>, Vec<EventHash>);
+
+// Hashes of the events of a message which were computed while decoding it, so that they don't have
+// to be computed again when handling it.  They are derived from the events, so they don't take part
+// in comparisons.
+#[derive(Clone, Default, Debug)]
+struct DecodedHashes(Vec<EventHash>);
+
+impl DecodedHashes {
+    // Pairs each of the events with its hash, only computing the ones which aren't known yet.
+    fn zip_with<T: NetworkEvent, P: PublicId>(
+        self,
+        packed_events: Vec<PackedEvent<T, P>>,
+        hasher: Hasher,
+    ) -> Vec<(PackedEvent<T, P>, EventHash)> {
+        if self.0.len() == packed_events.len() {
+            packed_events.into_iter().zip(self.0).collect()
+        } else {
+            packed_events
+                .into_iter()
+                .map(|packed_event| {
+                    let hash = packed_event.compute_hash_with(hasher);
+                    (packed_event, hash)
+                })
+                .collect()
+        }
+    }
+}
+
+impl PartialEq for DecodedHashes {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for DecodedHashes {}
+
+// Wire representation of both messages.  The header, i.e. the version and the sender's supported
+// versions, has the same format in every version, so that it can be read even when the events,
+// which are encoded separately in a format depending on the version, can't.
+#[derive(Serialize, Deserialize, Debug)]
+struct Envelope {
+    version: ProtocolVersion,
+    supported_versions: SupportedVersions,
+    events: Vec<u8>,
+}
+
+impl Envelope {
+    fn seal<T: NetworkEvent, P: PublicId>(
+        version: ProtocolVersion,
+        supported_versions: SupportedVersions,
+        packed_events: &[PackedEvent<T, P>],
+        undecoded_events: Option<&Vec<u8>>,
+        hasher: Hasher,
+    ) -> Result<Self, SerialisationError> {
+        let events = if let Some(events) = undecoded_events {
+            events.clone()
+        } else if version >= COMPACT_EVENTS_VERSION {
+            serialisation::serialise(&CompactEvents::encode(packed_events, hasher))?
+        } else {
+            serialisation::serialise(&packed_events)?
+        };
+        Ok(Envelope {
+            version,
+            supported_versions,
+            events,
+        })
+    }
+
+    fn is_supported(&self) -> bool {
+        SupportedVersions::OURS.contains(self.version)
+    }
+
+    fn open<T: NetworkEvent, P: PublicId>(
+        self,
+        hasher: Hasher,
+    ) -> Result<MessageParts<T, P>, Error> {
+        check_supported(self.version, self.supported_versions)?;
+
+        let log_error = |error| {
+            debug!("Failed to decode the events of a message: {:?}", error);
+            Error::CodecFailure
+        };
+        let (packed_events, event_hashes) = if self.version >= COMPACT_EVENTS_VERSION {
+            serialisation::deserialise::<CompactEvents<T, P>>(&self.events)
+                .map_err(log_error)?
+                .decode(hasher)?
+        } else {
+            let packed_events = serialisation::deserialise(&self.events).map_err(log_error)?;
+            (packed_events, vec![])
+        };
+        Ok((
+            self.version,
+            self.supported_versions,
+            packed_events,
+            event_hashes,
+        ))
+    }
+}
+
+// Header of a message, decoded on its own from messages encoded with a non-canonical codec.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: ProtocolVersion,
+    supported_versions: SupportedVersions,
+}
+
+// Representation of both messages on the wire when encoded with a non-canonical codec, which
+// encodes the events too rather than getting them as canonically encoded bytes.  It starts with
+// the same fields as `Header`, and carries the digest of the canonical encoding of the message,
+// i.e. of its `Envelope`.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct CodecEnvelope<T: NetworkEvent, P: PublicId> {
+    version: ProtocolVersion,
+    supported_versions: SupportedVersions,
+    digest: Hash,
+    events: CodecEvents<T, P>,
+}
+
+// The events of a message, in the format used by its version.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+enum CodecEvents<T: NetworkEvent, P: PublicId> {
+    Packed(Vec<PackedEvent<T, P>>),
+    Compact(CompactEvents<T, P>),
+}
+
+impl<T: NetworkEvent, P: PublicId> CodecEvents<T, P> {
+    fn encode(
+        version: ProtocolVersion,
+        packed_events: &[PackedEvent<T, P>],
+        hasher: Hasher,
+    ) -> Self {
+        if version >= COMPACT_EVENTS_VERSION {
+            CodecEvents::Compact(CompactEvents::encode(packed_events, hasher))
+        } else {
+            CodecEvents::Packed(packed_events.to_vec())
+        }
+    }
+
+    // A format not matching the version of the message is caught by the digest check, as the
+    // canonical encoding of the message depends on it.
+    fn decode(self, hasher: Hasher) -> Result<DecodedEvents<T, P>, Error> {
+        match self {
+            CodecEvents::Packed(packed_events) => Ok((packed_events, vec![])),
+            CodecEvents::Compact(compact_events) => compact_events.decode(hasher),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    #[cfg(feature = "json")]
+    use crate::codec::Json;
+    use crate::{
+        codec::Bincode,
+        mock::{PeerId, Transaction},
+        observation::Observation,
+        serialise,
+    };
+    use maidsafe_utilities::serialisation::deserialise;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::{env, fmt::Debug, fs, path::PathBuf};
+
+    // Set this environment variable to overwrite the golden files with the current encoding.  Only
+    // do so when adding the file for a new protocol version: the format of released versions must
+    // never change.
+    const UPDATE_GOLDEN_FILES_ENV: &str = "PARSEC_UPDATE_GOLDEN_FILES";
+
+    fn sample_events() -> Vec<PackedEvent<Transaction, PeerId>> {
+        let alice = PeerId::new("Alice");
+        let bob = PeerId::new("Bob");
+
+        let a_0 = PackedEvent::new_initial(alice.clone());
+        let a_1 = PackedEvent::new_observation(
+            alice.clone(),
+            a_0.compute_hash(),
+            Observation::OpaquePayload(Transaction::new("one")),
+        );
+        let a_2 = PackedEvent::new_requesting(alice.clone(), bob.clone(), a_1.compute_hash());
+        let b_0 = PackedEvent::new_initial(bob.clone());
+        let b_1 = PackedEvent::new_request(bob, b_0.compute_hash(), a_2.compute_hash());
+        let a_3 = PackedEvent::new_response(alice, a_2.compute_hash(), b_1.compute_hash());
+
+        vec![a_0, a_1, a_2, b_0, b_1, a_3]
+    }
+
+    fn check_golden_file<M>(name: &str, message: &M)
+    where
+        M: Serialize + DeserializeOwned + Debug + PartialEq,
+    {
+        let golden = check_golden_bytes(name, &serialise(message));
+        assert_eq!(unwrap!(deserialise::<M>(&golden)), *message);
+    }
+
+    // Checks that `serialised` matches the contents of the golden file, which are returned.
+    fn check_golden_bytes(name: &str, serialised: &[u8]) -> Vec<u8> {
+        let mut path = PathBuf::from("input_graphs");
+        path.push("gossip_messages_tests_wire_format");
+        path.push(name);
+
+        if env::var_os(UPDATE_GOLDEN_FILES_ENV).is_some() {
+            unwrap!(fs::write(&path, serialised));
+        }
+
+        let golden = unwrap!(fs::read(&path));
+        assert!(
+            serialised == &golden[..],
+            "The wire format of {} has changed. If intended, bump `ProtocolVersion::CURRENT`, \
+             add golden files for it and rerun with {} set.",
+            name,
+            UPDATE_GOLDEN_FILES_ENV
+        );
+        golden
+    }
+
+    fn versions(max: u32) -> (ProtocolVersion, SupportedVersions) {
+        let version = ProtocolVersion(max);
+        let supported_versions = SupportedVersions {
+            min: ProtocolVersion(1),
+            max: version,
+        };
+        (version, supported_versions)
+    }
+
+    #[test]
+    fn request_wire_format() {
+        for max in 1..=ProtocolVersion::CURRENT.0 {
+            let (version, supported_versions) = versions(max);
+            check_golden_file(
+                &format!("request_v{}.bin", max),
+                &Request::<_, _>::with_version(version, supported_versions, sample_events()),
+            );
+        }
+    }
+
+    #[test]
+    fn response_wire_format() {
+        for max in 1..=ProtocolVersion::CURRENT.0 {
+            let (version, supported_versions) = versions(max);
+            check_golden_file(
+                &format!("response_v{}.bin", max),
+                &Response::<_, _>::with_version(version, supported_versions, sample_events()),
+            );
+        }
+    }
+
+    #[test]
+    fn future_version_wire_format() {
+        // Message from a peer using a version newer than this crate's, with events in a format
+        // unknown to it.
+        let (version, supported_versions) = versions(3);
+        assert!(version > ProtocolVersion::CURRENT);
+        let envelope = Envelope {
+            version,
+            supported_versions,
+            events: vec![0xff; 8],
+        };
+        let golden = check_golden_bytes("request_v3.bin", &serialise(&envelope));
+
+        let expected = || Error::IncompatibleProtocolVersion {
+            version,
+            theirs: supported_versions,
+            ours: SupportedVersions::OURS,
+        };
+        assert_eq!(
+            Request::<Transaction, PeerId>::decode::<Bincode>(&golden).err(),
+            Some(expected())
+        );
+        assert_eq!(
+            Response::<Transaction, PeerId>::decode::<Bincode>(&golden).err(),
+            Some(expected())
+        );
+
+        // Deserialising it only yields its header, for `Parsec` to reject it with the same error,
+        // and it serialises back to the same bytes.
+        let request = unwrap!(deserialise::<Request<Transaction, PeerId>>(&golden));
+        assert_eq!(request.version(), version);
+        assert_eq!(request.supported_versions(), supported_versions);
+        assert!(request.packed_events.is_empty());
+        assert_eq!(serialise(&request), golden);
+    }
+
+    #[test]
+    fn compact_events() {
+        // Leave out the first event so that its child has to refer to it by hash.
+        let packed_events = sample_events().split_off(1);
+        let (version, supported_versions) = versions(COMPACT_EVENTS_VERSION.0);
+        let compact =
+            Request::<_, _>::with_version(version, supported_versions, packed_events.clone());
+        let (version, supported_versions) = versions(1);
+        let plain = Request::<_, _>::with_version(version, supported_versions, packed_events);
+
+        let serialised = serialise(&compact);
+        assert!(serialised.len() < serialise(&plain).len());
+        let decoded = unwrap!(deserialise::<Request<_, _>>(&serialised));
+        assert_eq!(decoded, compact);
+
+        // The hashes computed while decoding the events are kept for handling the message.
+        assert_eq!(decoded.event_hashes.0.len(), compact.packed_events.len());
+        for (packed_event, hash) in decoded.into_hashed_events() {
+            assert_eq!(packed_event.compute_hash(), hash);
+        }
+    }
+
+    // Non-canonical codec producing the same bytes as bincode, so that tests can tamper with the
+    // encoded messages.  Unlike `Bincode`, it ignores trailing bytes, so that the header of a
+    // message can be decoded on its own.
+    struct PlainBincode;
+
+    impl Codec for PlainBincode {
+        type Error = <Bincode as Codec>::Error;
+
+        fn encode<M: Serialize>(message: &M) -> Result<Vec<u8>, Self::Error> {
+            Bincode::encode(message)
+        }
+
+        fn decode<M: Serialize + DeserializeOwned>(mut bytes: &[u8]) -> Result<M, Self::Error> {
+            serialisation::deserialise_from(&mut bytes)
+        }
+    }
+
+    #[test]
+    fn codecs() {
+        for max in 1..=ProtocolVersion::CURRENT.0 {
+            let (version, supported_versions) = versions(max);
+            let request =
+                Request::<_, _>::with_version(version, supported_versions, sample_events());
+
+            // The canonical codec sends exactly the canonical encoding.
+            let encoded = unwrap!(request.encode::<Bincode>());
+            assert_eq!(encoded, serialise(&request));
+            assert_eq!(unwrap!(Request::decode::<Bincode>(&encoded)), request);
+
+            // Any other codec, even one producing the same bytes, has its output checked.
+            let encoded = unwrap!(request.encode::<PlainBincode>());
+            assert_ne!(encoded, serialise(&request));
+            assert_eq!(unwrap!(Request::decode::<PlainBincode>(&encoded)), request);
+
+            #[cfg(feature = "json")]
+            {
+                let encoded = unwrap!(request.encode::<Json>());
+                assert_eq!(unwrap!(Request::decode::<Json>(&encoded)), request);
+
+                // The events are encoded as JSON too, rather than as canonically encoded bytes.
+                let value: serde_json::Value = unwrap!(serde_json::from_slice(&encoded));
+                let format = if version >= COMPACT_EVENTS_VERSION {
+                    "Compact"
+                } else {
+                    "Packed"
+                };
+                assert!(!value["events"][format].is_null());
+            }
+        }
+    }
+
+    #[test]
+    fn codec_mismatch() {
+        let response = Response::<_, _>::new(sample_events());
+        let encoded = unwrap!(response.encode::<PlainBincode>());
+
+        assert_eq!(
+            Response::<Transaction, PeerId>::decode::<PlainBincode>(&encoded[..encoded.len() - 1]),
+            Err(Error::CodecFailure)
+        );
+
+        // Simulate a codec losing part of the message on the way.
+        let mut decoded: CodecEnvelope<Transaction, PeerId> =
+            unwrap!(PlainBincode::decode(&encoded));
+        let (mut packed_events, _) = unwrap!(decoded.events.decode(Hasher::default()));
+        let _ = packed_events.pop();
+        decoded.events = CodecEvents::encode(decoded.version, &packed_events, Hasher::default());
+        let encoded = unwrap!(PlainBincode::encode(&decoded));
+
+        assert_eq!(
+            Response::<Transaction, PeerId>::decode::<PlainBincode>(&encoded),
+            Err(Error::CodecMismatch)
+        );
+    }
+
+    #[test]
+    fn unsupported_version() {
+        // The header of a message can be read even if its events can't.
+        let next = ProtocolVersion(ProtocolVersion::CURRENT.0 + 1);
+        let theirs = SupportedVersions {
+            min: next,
+            max: next,
+        };
+        let envelope = Envelope {
+            version: next,
+            supported_versions: theirs,
+            events: vec![0xff; 4],
+        };
+        let expected = || Error::IncompatibleProtocolVersion {
+            version: next,
+            theirs,
+            ours: SupportedVersions::OURS,
+        };
+
+        let encoded = unwrap!(Bincode::encode(&envelope));
+        assert_eq!(
+            Request::<Transaction, PeerId>::decode::<Bincode>(&encoded).err(),
+            Some(expected())
+        );
+        let encoded = unwrap!(PlainBincode::encode(&envelope));
+        assert_eq!(
+            Response::<Transaction, PeerId>::decode::<PlainBincode>(&encoded).err(),
+            Some(expected())
+        );
+
+        #[cfg(feature = "json")]
+        {
+            let encoded = unwrap!(Json::encode(&serde_json::json!({
+                "version": next,
+                "supported_versions": theirs,
+                "events": { "Unknown": [1, 2, 3] },
+            })));
+            assert_eq!(
+                Request::<Transaction, PeerId>::decode::<Json>(&encoded).err(),
+                Some(expected())
+            );
+        }
+    }
+
+    #[test]
+    fn highest_common_version() {
+        let range = |min, max| SupportedVersions {
+            min: ProtocolVersion(min),
+            max: ProtocolVersion(max),
+        };
+
+        assert_eq!(
+            range(1, 3).highest_common(&range(2, 5)),
+            Some(ProtocolVersion(3))
+        );
+        assert_eq!(
+            range(2, 5).highest_common(&range(1, 3)),
+            Some(ProtocolVersion(3))
+        );
+        assert_eq!(
+            range(1, 1).highest_common(&range(1, 1)),
+            Some(ProtocolVersion(1))
+        );
+        assert_eq!(range(1, 2).highest_common(&range(3, 4)), None);
+        assert_eq!(range(3, 4).highest_common(&range(1, 2)), None);
+        assert!(range(1, 3).contains(ProtocolVersion(2)));
+        assert!(!range(1, 3).contains(ProtocolVersion(4)));
     }
 }
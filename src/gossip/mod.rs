@@ -8,6 +8,7 @@
 
 mod abstract_event;
 mod cause;
+mod compact;
 mod content;
 mod event;
 mod event_context;
@@ -17,7 +18,6 @@ mod graph;
 mod messages;
 mod packed_event;
 
-#[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
 pub(super) use self::cause::Cause;
 #[cfg(any(test, feature = "testing"))]
 pub(super) use self::event::CauseInput;
@@ -25,12 +25,12 @@ pub(super) use self::event::CauseInput;
 pub(super) use self::graph::snapshot::GraphSnapshot;
 pub(super) use self::{
     abstract_event::AbstractEventRef,
-    event::Event,
+    event::{Event, VerifiedEvent},
     event_context::EventContextRef,
     graph::{EventIndex, Graph, IndexedEventRef},
 };
 pub use self::{
     event_hash::EventHash,
-    messages::{Request, Response},
+    messages::{ProtocolVersion, Request, Response, SupportedVersions},
     packed_event::PackedEvent,
 };
@@ -14,7 +14,7 @@ use crate::{
     mock::{PeerId, Transaction},
     observation::Observation,
 };
-use crate::{hash::Hash, serialise, NetworkEvent, PublicId, Vote};
+use crate::{hash::Hasher, serialise, NetworkEvent, PublicId, Vote};
 use std::fmt::{self, Debug, Formatter};
 
 /// Packed event contains only content and signature.
@@ -39,8 +39,14 @@ impl<T: NetworkEvent, P: PublicId> Debug for PackedEvent<T, P> {
 }
 
 impl<T: NetworkEvent, P: PublicId> PackedEvent<T, P> {
+    pub(crate) fn compute_hash_with(&self, hasher: Hasher) -> EventHash {
+        EventHash::new(hasher, &serialise(&self.content))
+    }
+
+    // Hash of this event using the default hash algorithm.
+    #[cfg(any(test, feature = "testing"))]
     pub(crate) fn compute_hash(&self) -> EventHash {
-        EventHash(Hash::from(serialise(&self.content).as_slice()))
+        self.compute_hash_with(Hasher::default())
     }
 }
 
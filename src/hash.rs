@@ -6,11 +6,102 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use std::fmt::{self, Debug, Formatter};
-use tiny_keccak;
+use std::{
+    any::TypeId,
+    fmt::{self, Debug, Formatter},
+};
+use tiny_keccak::Keccak;
 
+/// Length in bytes of the digests produced by a `HashAlgorithm`.
 pub const HASH_LEN: usize = 32;
 
+/// A cryptographic hash function producing `HASH_LEN`-byte digests.  It is used to compute the
+/// hashes of events and observations, and so must be the same for all peers of a section.
+///
+/// Parsec takes care of separating event hashes from observation hashes, so implementations only
+/// need to provide the bare hash function.  The default `Sha3` is the exception: its hashes are
+/// left unseparated, so that they match the ones of peers predating configurable hash algorithms.
+pub trait HashAlgorithm: Clone + Debug + Eq + Send + 'static {
+    /// Returns the digest of the concatenation of `inputs`.
+    fn digest(inputs: &[&[u8]]) -> [u8; HASH_LEN];
+}
+
+/// SHA3-256, the default `HashAlgorithm`.
+///
+/// Its hashes are not domain-separated: event, observation and message hashes are plain SHA3-256
+/// digests, as they were before hash algorithms became configurable.  Separating them would change
+/// every event hash, and so has to come with a new protocol version.  This is deliberately deferred
+/// rather than done as part of making the algorithm configurable; until then, only the other
+/// algorithms get domain separation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sha3;
+
+impl HashAlgorithm for Sha3 {
+    fn digest(inputs: &[&[u8]]) -> [u8; HASH_LEN] {
+        let mut keccak = Keccak::new_sha3_256();
+        for input in inputs {
+            keccak.update(input);
+        }
+        let mut digest = [0; HASH_LEN];
+        keccak.finalize(&mut digest);
+        digest
+    }
+}
+
+/// The kind of data being hashed.  Unless the algorithm is `Sha3`, equal inputs hashed in different
+/// domains yield unrelated hashes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum HashDomain {
+    Event,
+    Observation,
+    Message,
+}
+
+impl HashDomain {
+    // Prefix prepended to the input.  No tag is a prefix of another one.
+    fn tag(self) -> &'static [u8] {
+        match self {
+            HashDomain::Event => b"parsec.event",
+            HashDomain::Observation => b"parsec.observation",
+            HashDomain::Message => b"parsec.message",
+        }
+    }
+}
+
+/// Type-erased `HashAlgorithm`, so that the algorithm chosen for `Parsec` doesn't have to be a
+/// type parameter of every type involved in hashing.
+#[derive(Clone, Copy)]
+pub(crate) struct Hasher {
+    digest: fn(&[&[u8]]) -> [u8; HASH_LEN],
+    separate_domains: bool,
+}
+
+impl Hasher {
+    pub fn new<H: HashAlgorithm>() -> Self {
+        // Changing the `Sha3` hashes would change the wire format of every protocol version, so
+        // domain separation is only applied to the other algorithms.  This is decided here rather
+        // than by the algorithm, so that no other algorithm can opt out of it.
+        Hasher {
+            digest: H::digest,
+            separate_domains: TypeId::of::<H>() != TypeId::of::<Sha3>(),
+        }
+    }
+
+    pub fn hash(self, domain: HashDomain, data: &[u8]) -> Hash {
+        if self.separate_domains {
+            Hash((self.digest)(&[domain.tag(), data]))
+        } else {
+            Hash((self.digest)(&[data]))
+        }
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Self::new::<Sha3>()
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Hash([u8; HASH_LEN]);
 
@@ -22,17 +113,6 @@ impl Hash {
     pub fn from_bytes(bytes: [u8; HASH_LEN]) -> Self {
         Hash(bytes)
     }
-
-    #[cfg(feature = "mock")]
-    pub fn as_bytes(&self) -> &[u8; HASH_LEN] {
-        &self.0
-    }
-}
-
-impl<'a> From<&'a [u8]> for Hash {
-    fn from(src: &'a [u8]) -> Self {
-        Hash(tiny_keccak::sha3_256(src))
-    }
 }
 
 impl Debug for Hash {
@@ -51,12 +131,12 @@ impl Debug for Hash {
     }
 }
 
-#[cfg(any(feature = "dump-graphs", not(any(test, feature = "testing"))))]
+pub(crate) use self::full::FullDisplay;
+
 mod full {
     use super::*;
     use std::fmt::Display;
 
-    #[cfg(feature = "dump-graphs")]
     impl Hash {
         pub fn full_display(&self) -> FullDisplay {
             FullDisplay(self)
@@ -74,3 +154,44 @@ mod full {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA3 under another name, so that it gets domain separation.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    struct OtherSha3;
+
+    impl HashAlgorithm for OtherSha3 {
+        fn digest(inputs: &[&[u8]]) -> [u8; HASH_LEN] {
+            Sha3::digest(inputs)
+        }
+    }
+
+    #[test]
+    fn domain_separation() {
+        let data = b"data";
+
+        let hasher = Hasher::new::<OtherSha3>();
+        assert_ne!(
+            hasher.hash(HashDomain::Event, data),
+            hasher.hash(HashDomain::Observation, data)
+        );
+        assert_eq!(
+            hasher.hash(HashDomain::Event, data),
+            Hash(Sha3::digest(&[b"parsec.eventdata"]))
+        );
+
+        // The default algorithm produces the same hashes as before it became configurable.
+        let hasher = Hasher::default();
+        assert_eq!(
+            hasher.hash(HashDomain::Event, data),
+            Hash(tiny_keccak::sha3_256(data))
+        );
+        assert_eq!(
+            hasher.hash(HashDomain::Observation, data),
+            Hash(tiny_keccak::sha3_256(data))
+        );
+    }
+}
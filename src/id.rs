@@ -16,6 +16,19 @@ pub trait PublicId: Clone + Eq + Ord + Hash + Serialize + DeserializeOwned + Deb
     type Signature: Clone + Eq + Ord + Hash + Serialize + DeserializeOwned + Debug;
     /// Verifies `signature` against `data` using this `PublicId`.  Returns `true` if valid.
     fn verify_signature(&self, signature: &Self::Signature, data: &[u8]) -> bool;
+
+    /// Verifies a batch of signatures, each given along with its signer and the signed data.
+    /// Returns `true` if all of them are valid.
+    ///
+    /// This is used to verify all the events of a gossip message at once.  The default
+    /// implementation calls `verify_signature` for each of them in turn; override it to use batch
+    /// verification if the signature scheme supports it, or to spread the work over a pool of
+    /// threads.
+    fn verify_signatures(batch: &[(&Self, &Self::Signature, &[u8])]) -> bool {
+        batch
+            .iter()
+            .all(|(public_id, signature, data)| public_id.verify_signature(signature, data))
+    }
 }
 
 /// The secret identity of a node.  It provides functionality to allow it to be used as an
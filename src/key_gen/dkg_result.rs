@@ -6,12 +6,16 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::{error::Error, id::SecretId};
+use maidsafe_utilities::serialisation;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     cmp::Ordering,
     fmt::{self, Debug, Formatter},
 };
-use threshold_crypto::{PublicKeySet, SecretKeyShare};
+use threshold_crypto::{
+    serde_impl::SerdeSecret, Ciphertext, PublicKey, PublicKeySet, SecretKey, SecretKeyShare,
+};
 
 #[derive(Clone)]
 /// DKG result
@@ -31,6 +35,43 @@ impl DkgResult {
             secret_key_share,
         }
     }
+
+    /// Export this result, including the secret key share, so it can be persisted and restored
+    /// with [DkgResultExport::import](struct.DkgResultExport.html#method.import) after a restart.
+    /// The secret key share is encrypted by `our_id` to itself using `SecretId::encrypt`.
+    pub fn export<S: SecretId>(&self, our_id: &S) -> Result<DkgResultExport, Error> {
+        self.export_with(|plaintext| our_id.encrypt(our_id.public_id(), plaintext))
+    }
+
+    /// Export this result, including the secret key share, encrypting the share with the
+    /// caller-supplied `key` instead of the node's own identity.
+    pub fn export_with_key(&self, key: &PublicKey) -> Result<DkgResultExport, Error> {
+        self.export_with(|plaintext| serialisation::serialise(&key.encrypt(plaintext)).ok())
+    }
+
+    fn export_with<F>(&self, encrypt: F) -> Result<DkgResultExport, Error>
+    where
+        F: FnOnce(&[u8]) -> Option<Vec<u8>>,
+    {
+        let encrypted_secret_key_share = match self.secret_key_share {
+            Some(ref secret_key_share) => {
+                // Include the public key set in the encrypted data so that the share can't be
+                // reattached to a different key set without being detected.
+                let plaintext = serialisation::serialise(&(
+                    &self.public_key_set,
+                    SerdeSecret(secret_key_share),
+                ))
+                .map_err(|_| Error::DkgResultEncryption)?;
+                Some(encrypt(&plaintext).ok_or(Error::DkgResultEncryption)?)
+            }
+            None => None,
+        };
+
+        Ok(DkgResultExport {
+            public_key_set: self.public_key_set.clone(),
+            encrypted_secret_key_share,
+        })
+    }
 }
 
 impl Debug for DkgResult {
@@ -100,3 +141,93 @@ impl<'a> Deserialize<'a> for DkgResultWrapper {
         })
     }
 }
+
+/// A `DkgResult` exported together with its encrypted secret key share.
+///
+/// Unlike `DkgResult` as carried in an `Observation::DkgResult`, this type serialises the secret
+/// key share (encrypted), so it can be stored by the node and imported again after a restart.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DkgResultExport {
+    public_key_set: PublicKeySet,
+    encrypted_secret_key_share: Option<Vec<u8>>,
+}
+
+impl DkgResultExport {
+    /// The public key set of the exported result.
+    pub fn public_key_set(&self) -> &PublicKeySet {
+        &self.public_key_set
+    }
+
+    /// Whether the export carries a secret key share.
+    pub fn has_secret_key_share(&self) -> bool {
+        self.encrypted_secret_key_share.is_some()
+    }
+
+    /// Import a result exported with
+    /// [DkgResult::export](struct.DkgResult.html#method.export), decrypting the secret key share
+    /// with `our_id` and reattaching it to `public_key_set`.
+    ///
+    /// Returns `Error::MismatchedPublicKeySet` if the export was made for a different key set and
+    /// `Error::DkgResultEncryption` if the share can't be decrypted by `our_id`.
+    pub fn import<S: SecretId>(
+        &self,
+        our_id: &S,
+        public_key_set: &PublicKeySet,
+    ) -> Result<DkgResult, Error> {
+        self.import_with(public_key_set, |ciphertext| {
+            our_id.decrypt(our_id.public_id(), ciphertext)
+        })
+    }
+
+    /// Import a result exported with
+    /// [DkgResult::export_with_key](struct.DkgResult.html#method.export_with_key), decrypting
+    /// the secret key share with the caller-supplied `key`.
+    pub fn import_with_key(
+        &self,
+        key: &SecretKey,
+        public_key_set: &PublicKeySet,
+    ) -> Result<DkgResult, Error> {
+        self.import_with(public_key_set, |ciphertext| {
+            let ciphertext: Ciphertext = serialisation::deserialise(ciphertext).ok()?;
+            key.decrypt(&ciphertext)
+        })
+    }
+
+    fn import_with<F>(&self, public_key_set: &PublicKeySet, decrypt: F) -> Result<DkgResult, Error>
+    where
+        F: FnOnce(&[u8]) -> Option<Vec<u8>>,
+    {
+        if self.public_key_set != *public_key_set {
+            return Err(Error::MismatchedPublicKeySet);
+        }
+
+        let secret_key_share = match self.encrypted_secret_key_share {
+            Some(ref ciphertext) => {
+                let plaintext = decrypt(ciphertext).ok_or(Error::DkgResultEncryption)?;
+                let (encrypted_public_key_set, secret_key_share): (
+                    PublicKeySet,
+                    SerdeSecret<SecretKeyShare>,
+                ) = serialisation::deserialise(&plaintext)
+                    .map_err(|_| Error::DkgResultEncryption)?;
+                if encrypted_public_key_set != *public_key_set {
+                    return Err(Error::MismatchedPublicKeySet);
+                }
+                Some(secret_key_share.into_inner())
+            }
+            None => None,
+        };
+
+        Ok(DkgResult::new(public_key_set.clone(), secret_key_share))
+    }
+}
+
+impl Debug for DkgResultExport {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "DkgResultExport({:?}, {})",
+            self.public_key_set,
+            self.has_secret_key_share()
+        )
+    }
+}
@@ -9,7 +9,7 @@
 /// Secure RNG used by Parsec for DKG:
 /// If feature = "dump-graphs" is enabled, allow dumping the produced value to allow replay.
 pub struct ParsecRng {
-    secure_rng: Box<dyn rand::Rng>,
+    secure_rng: Box<dyn rand::Rng + Send>,
     #[cfg(feature = "dump-graphs")]
     generated_values: Vec<u32>,
 }
@@ -17,7 +17,7 @@ pub struct ParsecRng {
 impl ParsecRng {
     /// Create ParsecRng that will output value from the secure_rng.
     /// `secure_rng`: a cryptographically secure RNG.
-    pub fn new(secure_rng: Box<dyn rand::Rng>) -> Self {
+    pub fn new(secure_rng: Box<dyn rand::Rng + Send>) -> Self {
         Self {
             secure_rng,
             #[cfg(feature = "dump-graphs")]
@@ -23,13 +23,16 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 
-use super::{dkg_threshold, KeyGen, PartOutcome};
+use super::{dkg_threshold, rng_adapter::RngAdapter, KeyGen, PartOutcome};
 use crate::mock::PeerId;
 use crate::{
     dev_utils::{Environment, RngChoice},
-    serialise,
+    error::Error,
+    serialise, DkgResult, DkgResultExport,
 };
+use maidsafe_utilities::serialisation::deserialise;
 use rand::Rng;
+use threshold_crypto::{Fr, SecretKey, SecretKeySet};
 
 // Alter the seed here to reproduce failures
 static SEED: RngChoice = RngChoice::SeededRandom;
@@ -170,3 +173,58 @@ fn test_key_gen_8() {
 fn test_key_gen_15() {
     test_key_gen(15);
 }
+
+#[test]
+fn dkg_result_export_and_import() {
+    let mut env = Environment::new(SEED);
+    let peer_id = unwrap!(PeerId::from_index(0));
+
+    let secret_key_set = SecretKeySet::random(1, &mut RngAdapter(&mut env.rng));
+    let public_key_set = secret_key_set.public_keys();
+    let dkg_result = DkgResult::new(
+        public_key_set.clone(),
+        Some(secret_key_set.secret_key_share(0)),
+    );
+
+    // Round trip through the node's own identity, including serialisation of the export.
+    let exported = unwrap!(dkg_result.export(&peer_id));
+    let exported: DkgResultExport = unwrap!(deserialise(&serialise(&exported)));
+    let imported = unwrap!(exported.import(&peer_id, &public_key_set));
+    assert_eq!(imported.public_key_set, public_key_set);
+    assert_eq!(
+        imported.secret_key_share,
+        Some(secret_key_set.secret_key_share(0))
+    );
+
+    // The share can't be reattached to a different public key set.
+    let other_public_key_set = SecretKeySet::random(1, &mut RngAdapter(&mut env.rng)).public_keys();
+    assert_eq!(
+        exported.import(&peer_id, &other_public_key_set).err(),
+        Some(Error::MismatchedPublicKeySet)
+    );
+
+    // Round trip through a caller-supplied key, which is required to decrypt the share.
+    let key = SecretKey::from_mut(&mut env.rng.gen::<Fr>());
+    let exported = unwrap!(dkg_result.export_with_key(&key.public_key()));
+    let imported = unwrap!(exported.import_with_key(&key, &public_key_set));
+    assert_eq!(
+        imported.secret_key_share,
+        Some(secret_key_set.secret_key_share(0))
+    );
+    assert_eq!(
+        exported
+            .import_with_key(
+                &SecretKey::from_mut(&mut env.rng.gen::<Fr>()),
+                &public_key_set
+            )
+            .err(),
+        Some(Error::DkgResultEncryption)
+    );
+
+    // Results without a share export and import without one.
+    let observer_result = DkgResult::new(public_key_set.clone(), None);
+    let exported = unwrap!(observer_result.export(&peer_id));
+    assert!(!exported.has_secret_key_share());
+    let imported = unwrap!(exported.import(&peer_id, &public_key_set));
+    assert!(imported.secret_key_share.is_none());
+}
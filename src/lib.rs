@@ -178,6 +178,8 @@ extern crate unwrap;
 pub mod dev_utils;
 
 mod block;
+mod codec;
+mod consensus_trace;
 mod dump_graph;
 mod error;
 mod gossip;
@@ -185,11 +187,13 @@ mod hash;
 mod id;
 mod key_gen;
 mod meta_voting;
+mod metrics;
 mod network_event;
 mod observation;
 mod parsec;
 mod parsec_helpers;
 mod peer_list;
+mod section_status;
 mod vote;
 
 #[cfg(all(test, feature = "mock"))]
@@ -208,15 +212,30 @@ pub mod mock;
 pub use crate::dump_graph::{DumpGraphMode, DIR, DUMP_MODE};
 pub use crate::{
     block::Block,
+    codec::{Bincode, Codec},
+    consensus_trace::{ConsensusTrace, MetaVoteStep, MetaVoteTrace, ObserverTrace},
+    dump_graph::{GraphDumpOptions, GraphFormat},
     error::{Error, Result},
-    gossip::{EventHash, PackedEvent, Request, Response},
+    gossip::{EventHash, PackedEvent, ProtocolVersion, Request, Response, SupportedVersions},
+    hash::{HashAlgorithm, Sha3, HASH_LEN},
     id::{Proof, PublicId, SecretId},
     key_gen::dkg_result::*,
+    metrics::{Metrics, NoMetrics},
     network_event::NetworkEvent,
-    observation::{ConsensusMode, Malice, Observation},
+    observation::{ConsensusMode, Malice, Observation, ObservationStatus},
     parsec::Parsec,
+    peer_list::PeerState,
+    section_status::{PeerStatus, SectionStatus},
     vote::Vote,
 };
+#[cfg(feature = "json")]
+pub use crate::{
+    codec::Json,
+    dump_graph::{
+        JsonCause, JsonEvent, JsonGraph, JsonMetaElection, JsonMetaEvent, JsonObservation,
+        JsonObserver, JsonPeer,
+    },
+};
 
 use maidsafe_utilities::serialisation;
 use serde::ser::Serialize;
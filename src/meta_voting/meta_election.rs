@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    meta_event::{MetaEvent, MetaEventBuilder},
+    meta_event::{MetaEvent, MetaEventBuilder, Observer},
     meta_vote::MetaVote,
 };
 use crate::{
@@ -131,6 +131,17 @@ impl MetaElection {
             .map_or(false, |(_, contents)| contents.contains(payload_key))
     }
 
+    /// Observer events, with their observees.  Events after the one being processed can still
+    /// hold meta-events from the previous meta-election.
+    pub fn observers(&self) -> impl Iterator<Item = (EventIndex, &PeerIndexSet)> {
+        self.meta_events
+            .iter()
+            .filter_map(|(event_index, meta_event)| match meta_event.observer {
+                Observer::This(ref observees) => Some((*event_index, observees)),
+                Observer::Ancestor | Observer::None => None,
+            })
+    }
+
     pub fn continue_consensus_start_index(&self) -> usize {
         self.continue_consensus_start_index
     }
@@ -154,7 +165,6 @@ impl MetaElection {
         self.consensus_history.extend(decided_keys);
     }
 
-    #[cfg(feature = "dump-graphs")]
     pub fn meta_events(&self) -> &FnvHashMap<EventIndex, MetaEvent> {
         &self.meta_events
     }
@@ -188,6 +198,25 @@ impl MetaElection {
         indices.iter().cloned()
     }
 
+    /// Events carrying a payload that hasn't been consensused yet and that isn't already
+    /// interesting content of the given creator.
+    pub fn unconsensused_events_not_interesting_to<'a>(
+        &'a self,
+        creator: PeerIndex,
+    ) -> impl Iterator<Item = EventIndex> + 'a {
+        let interesting_content = self
+            .interesting_events
+            .get(creator)
+            .map(|(_, contents)| contents);
+        self.unconsensused_events
+            .indices_by_key
+            .iter()
+            .filter(move |(key, _)| {
+                interesting_content.map_or(true, |contents| !contents.contains(*key))
+            })
+            .flat_map(|(_, indices)| indices.iter().cloned())
+    }
+
     fn add_interesting_event(
         &mut self,
         creator: PeerIndex,
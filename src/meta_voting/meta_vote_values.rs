@@ -185,6 +185,27 @@ impl MetaVoteValues {
         MetaVoteValues::Undecided(values)
     }
 
+    pub fn estimates(self) -> BoolSet {
+        match self {
+            MetaVoteValues::Decided(value) => BoolSet::Single(value),
+            MetaVoteValues::Undecided(values) => values.estimates.0,
+        }
+    }
+
+    pub fn bin_values(self) -> BoolSet {
+        match self {
+            MetaVoteValues::Decided(value) => BoolSet::Single(value),
+            MetaVoteValues::Undecided(values) => values.bin_values.0,
+        }
+    }
+
+    pub fn aux_value(self) -> Option<bool> {
+        match self {
+            MetaVoteValues::Decided(value) => Some(value),
+            MetaVoteValues::Undecided(values) => values.aux_value.0,
+        }
+    }
+
     pub fn count(self, total_peers: NonZeroUsize) -> MetaVoteCounts {
         // Counts the contribution of these MetaVoteValues
         let mut counts = MetaVoteCounts::default_counts(total_peers);
@@ -332,7 +353,6 @@ impl MetaVoteValues {
         }
     }
 
-    #[cfg(feature = "dump-graphs")]
     pub fn as_chars(self) -> (char, char, char, char) {
         let pretty_bool = |b: bool| {
             if b {
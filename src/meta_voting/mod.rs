@@ -17,10 +17,10 @@ mod meta_vote_values;
 pub(crate) use self::meta_election::snapshot::MetaElectionSnapshot;
 #[cfg(any(test, feature = "testing"))]
 pub(crate) use self::meta_election::UnconsensusedEvents;
-#[cfg(any(test, feature = "testing"))]
-pub(crate) use self::{bool_set::BoolSet, meta_vote_values::Step};
 pub(crate) use self::{
+    bool_set::BoolSet,
     meta_election::MetaElection,
     meta_event::{MetaEvent, MetaEventBuilder, Observer},
     meta_vote::MetaVote,
+    meta_vote_values::Step,
 };
@@ -0,0 +1,53 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::error::Error;
+use std::time::Duration;
+
+/// Receiver of measurements about the internals of a [Parsec](struct.Parsec.html) instance,
+/// installed with [Parsec::set_metrics](struct.Parsec.html#method.set_metrics).
+///
+/// All the methods do nothing by default, so implementors only need to override the ones they are
+/// interested in.  The methods take `&self` as they are called from within both mutating and
+/// non-mutating parts of the algorithm; implementors are expected to use interior mutability, as
+/// most metrics libraries do anyway.
+pub trait Metrics {
+    /// Called each time an event is added to the gossip graph, with the number of events the graph
+    /// contains afterwards.
+    fn event_added(&self, _graph_size: usize) {}
+
+    /// Called each time a gossip request or response is received, with the number of events it
+    /// carries.
+    fn gossip_received(&self, _num_events: usize) {}
+
+    /// Called each time a gossip request or response is rejected, with the reason for it.
+    fn gossip_rejected(&self, _error: &Error) {}
+
+    /// Called each time an event has been processed for consensus, with the time it took.
+    fn event_processed(&self, _duration: Duration) {}
+
+    /// Called each time a meta-election decides, with the number of meta-election rounds it took
+    /// and the number of payloads consensused as a result.  The number of rounds is zero if the
+    /// decision didn't require any meta-voting.
+    fn consensus_reached(&self, _rounds: usize, _num_payloads: usize) {}
+
+    /// Called each time blocks are created out of consensused payloads, with the number of blocks
+    /// that will be returned by `poll`.
+    fn blocks_created(&self, _num_blocks: usize) {}
+
+    /// Called each time a malicious behaviour is detected, with the name of the
+    /// [Malice](enum.Malice.html) variant describing it.
+    fn malice_detected(&self, _kind: &'static str) {}
+}
+
+/// `Metrics` implementation that discards all measurements.  `Parsec` behaves as if using it
+/// unless another one is set, except that it then doesn't take the measurements at all.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
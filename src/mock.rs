@@ -270,7 +270,8 @@ fn gen_keypair() -> (PublicKey, SecretKey) {
 
 #[cfg(feature = "mock")]
 fn derive_keypair(seed: &[u8]) -> (PublicKey, SecretKey) {
-    use crate::hash::Hash;
-    let hash = Hash::from(seed);
-    (PublicKey(*hash.as_bytes()), SecretKey(*hash.as_bytes()))
+    // Mock ids aren't tied to the `HashAlgorithm` of any `Parsec` instance, so always use `Sha3`.
+    use crate::hash::{HashAlgorithm, Sha3};
+    let bytes = Sha3::digest(&[seed]);
+    (PublicKey(bytes), SecretKey(bytes))
 }
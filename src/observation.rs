@@ -7,8 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    gossip::{EventHash, PackedEvent},
-    hash::Hash,
+    gossip::{EventHash, PackedEvent, ProtocolVersion},
+    hash::{Hash, HashDomain, Hasher},
     id::{PublicId, SecretId},
     key_gen::message::DkgMessage,
     network_event::NetworkEvent,
@@ -78,6 +78,19 @@ pub enum Observation<T: NetworkEvent, P: PublicId> {
     /// Vote for the next message (Part or Ack) to be handled for the Distributed Key Generation
     /// algorithm used by our common coin.
     DkgMessage(DkgMessage),
+    /// Vote to switch the section to the given protocol version.  Once consensused, every node
+    /// applies the new version from the block following this one onward.
+    ///
+    /// The version in effect only determines the encoding of the gossip messages a node sends (see
+    /// `ProtocolVersion`), never the blocks it outputs.  As nodes accept messages in every version
+    /// they support whatever the one in effect, they don't need to switch at the same time, so
+    /// nothing else depends on the height of the upgrade block.
+    Upgrade {
+        /// Protocol version to switch to
+        version: ProtocolVersion,
+        /// Extra arbitrary information for use by the client
+        related_info: Vec<u8>,
+    },
 }
 
 impl<T: NetworkEvent, P: PublicId> Observation<T, P> {
@@ -133,6 +146,7 @@ impl<T: NetworkEvent, P: PublicId> Debug for Observation<T, P> {
             Observation::OpaquePayload(payload) => {
                 write!(formatter, "OpaquePayload({:?})", payload)
             }
+            Observation::Upgrade { version, .. } => write!(formatter, "Upgrade({})", version),
         }
     }
 }
@@ -221,6 +235,24 @@ impl<T: NetworkEvent, P: PublicId> Malice<T, P> {
             | Malice::Unprovable(_) => vec![],
         }
     }
+
+    // Name of the variant, as reported to `Metrics`.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Malice::UnexpectedGenesis(_) => "UnexpectedGenesis",
+            Malice::DuplicateVote(_, _) => "DuplicateVote",
+            Malice::MissingGenesis(_) => "MissingGenesis",
+            Malice::IncorrectGenesis(_) => "IncorrectGenesis",
+            Malice::Fork(_) => "Fork",
+            Malice::InvalidAccusation(_) => "InvalidAccusation",
+            Malice::OtherParentBySameCreator(_) => "OtherParentBySameCreator",
+            Malice::SelfParentByDifferentCreator(_) => "SelfParentByDifferentCreator",
+            Malice::InvalidRequest(_) => "InvalidRequest",
+            Malice::InvalidResponse(_) => "InvalidResponse",
+            Malice::Unprovable(_) => "Unprovable",
+            Malice::Accomplice(_, _) => "Accomplice",
+        }
+    }
 }
 
 // For internal diagnostics only. The value is ignored in comparison, ordering or hashing.
@@ -281,9 +313,12 @@ impl<'a> Visitor<'a> for UnprovableMaliceVisitor {
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub(crate) struct ObservationHash(pub(crate) Hash);
 
-impl<'a, T: NetworkEvent, P: PublicId> From<&'a Observation<T, P>> for ObservationHash {
-    fn from(observation: &'a Observation<T, P>) -> Self {
-        ObservationHash(Hash::from(serialise(observation).as_slice()))
+impl ObservationHash {
+    pub fn new<T: NetworkEvent, P: PublicId>(
+        hasher: Hasher,
+        observation: &Observation<T, P>,
+    ) -> Self {
+        ObservationHash(hasher.hash(HashDomain::Observation, &serialise(observation)))
     }
 }
 
@@ -293,6 +328,28 @@ impl Debug for ObservationHash {
     }
 }
 
+/// How far an observation has progressed towards consensus, as seen by the owning peer.  Returned
+/// by [Parsec::observation_status](struct.Parsec.html#method.observation_status).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ObservationStatus<P: PublicId> {
+    /// No vote for the observation has been seen.
+    Unknown,
+    /// The observation has been voted for, but not consensused yet.
+    Voted {
+        /// The peers whose votes for the observation have been seen so far.
+        voters: BTreeSet<P>,
+    },
+    /// The observation has been consensused, but the block containing it hasn't been returned by
+    /// `poll` yet.
+    Consensused {
+        /// Position of the block in the consensus history, starting with the genesis block at 0.
+        height: usize,
+    },
+    /// The observation has been consensused and the block containing it has been returned by
+    /// `poll`, or won't be returned at all as it is internal to Parsec.
+    Polled,
+}
+
 // Container for observation with its metadata.
 #[derive(Debug)]
 pub(crate) struct ObservationInfo<T: NetworkEvent, P: PublicId> {
@@ -370,7 +427,7 @@ impl ObservationKey {
 }
 
 /// Number of votes necessary to reach consensus on an `OpaquePayload`.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ConsensusMode {
     /// One vote is enough.
     Single,
@@ -440,9 +497,10 @@ mod tests {
         assert!(!(malice1 < malice2));
         assert!(!(malice1 > malice2));
 
+        let hasher = Hasher::default();
         assert_eq!(
-            Hash::from(serialise(&malice1).as_slice()),
-            Hash::from(serialise(&malice2).as_slice())
+            hasher.hash(HashDomain::Observation, &serialise(&malice1)),
+            hasher.hash(HashDomain::Observation, &serialise(&malice2))
         );
     }
 
@@ -8,32 +8,35 @@
 
 #[cfg(all(test, feature = "mock"))]
 use crate::dev_utils::ParsedContents;
-#[cfg(all(test, feature = "malice-detection", feature = "mock"))]
-use crate::gossip::EventHash;
 #[cfg(all(test, any(feature = "testing", feature = "mock")))]
 use crate::gossip::GraphSnapshot;
 #[cfg(feature = "malice-detection")]
 use crate::observation::Malice;
 use crate::{
     block::{Block, BlockGroup},
-    dump_graph,
+    consensus_trace::{ConsensusTrace, DecisionRecord},
+    dump_graph::{self, GraphDumpOptions, GraphFormat},
     error::{Error, Result},
     gossip::{
-        Event, EventContextRef, EventIndex, Graph, IndexedEventRef, PackedEvent, Request, Response,
+        Event, EventContextRef, EventHash, EventIndex, Graph, IndexedEventRef, PackedEvent,
+        ProtocolVersion, Request, Response, SupportedVersions, VerifiedEvent,
     },
+    hash::{HashAlgorithm, Hasher, Sha3},
     id::{PublicId, SecretId},
     key_gen::{
         dkg_threshold, message::DkgMessage, parsec_rng::ParsecRng, Ack, AckOutcome, KeyGen, Part,
         PartOutcome,
     },
-    meta_voting::{MetaElection, MetaEvent, MetaEventBuilder, MetaVote, Observer},
+    meta_voting::{MetaElection, MetaEvent, MetaEventBuilder, MetaVote, Observer, Step},
+    metrics::Metrics,
     network_event::NetworkEvent,
     observation::{
         is_more_than_two_thirds, ConsensusMode, Observation, ObservationHash, ObservationKey,
-        ObservationStore,
+        ObservationStatus, ObservationStore,
     },
     parsec_helpers::find_interesting_content_for_event,
     peer_list::{Peer, PeerIndex, PeerIndexMap, PeerIndexSet, PeerList, PeerListChange, PeerState},
+    section_status::{PeerStatus, SectionStatus},
 };
 #[cfg(any(feature = "testing", all(test, feature = "mock")))]
 use crate::{
@@ -45,10 +48,12 @@ use itertools::Itertools;
 use std::ops::{Deref, DerefMut};
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
+    io::{self, Write},
     iter,
     marker::PhantomData,
     mem,
     num::NonZeroUsize,
+    time::Instant,
     usize,
 };
 
@@ -65,6 +70,10 @@ pub(crate) type KeyGenId = usize;
 /// types have to implement [NetworkEvent](trait.NetworkEvent.html) and
 /// [SecretId](trait.SecretId.html) traits, respectively.
 ///
+/// A third, optional type argument selects the [HashAlgorithm](trait.HashAlgorithm.html) used to
+/// hash events and observations.  It defaults to [Sha3](struct.Sha3.html) and has to be the same
+/// for all peers of a section.
+///
 /// The `Parsec` struct exposes two constructors:
 ///
 /// * [from_genesis](struct.Parsec.html#method.from_genesis), if the owning peer is a part of the
@@ -92,7 +101,7 @@ pub(crate) type KeyGenId = usize;
 /// section, i.e. a block with payload `Observation::Remove(our_id)` has been made stable.
 ///
 /// For more details, see the descriptions of methods below.
-pub struct Parsec<T: NetworkEvent, S: SecretId> {
+pub struct Parsec<T: NetworkEvent, S: SecretId, H: HashAlgorithm = Sha3> {
     // The PeerInfo of other nodes.
     peer_list: PeerList<S>,
     // Set of active distributed key generation, with a KeyGenId used by `DkgMessage`.
@@ -107,7 +116,14 @@ pub struct Parsec<T: NetworkEvent, S: SecretId> {
     consensused_blocks: VecDeque<BlockGroup<T, S::PublicId>>,
     // The map of meta votes of the events on each consensus block.
     meta_election: MetaElection,
+    // How each of the meta-elections decided since recording was enabled was decided, in consensus
+    // order.  `None` unless enabled, as nothing ever removes the records.
+    decision_records: Option<Vec<DecisionRecord>>,
     consensus_mode: ConsensusMode,
+    // Protocol version in effect in the section, as decided by consensused upgrades.
+    protocol_version: ProtocolVersion,
+    // Protocol versions this node is able to handle.
+    supported_versions: SupportedVersions,
     // Dkg messages to raise at the end of processing of current gossip message.
     pending_dkg_msgs: Vec<DkgMessage>,
     // Accusations to raise at the end of the processing of current gossip message.
@@ -121,9 +137,14 @@ pub struct Parsec<T: NetworkEvent, S: SecretId> {
     ignore_process_events: bool,
     // Provided RNG: Needs to be cryptographically secure RNG as it is used for DKG key generation.
     secure_rng: ParsecRng,
+    // Algorithm used to compute the hashes of events and observations.
+    hash_algorithm: PhantomData<H>,
+    // Receiver of measurements about the consensus internals.  `None` until one is set, in which
+    // case no measurements are taken, so that they don't cost anything, e.g. timing each event.
+    metrics: Option<Box<dyn Metrics + Send>>,
 }
 
-impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
+impl<T: NetworkEvent, S: SecretId, H: HashAlgorithm> Parsec<T, S, H> {
     /// Creates a new `Parsec` for a peer with the given ID and genesis peer IDs (ours included).
     ///
     /// * `our_id` is the value that will identify the owning peer in the network.
@@ -139,7 +160,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         genesis_group: &BTreeSet<S::PublicId>,
         genesis_related_info: Vec<u8>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         if !genesis_group.contains(our_id.public_id()) {
             log_or_panic!("Genesis group must contain us");
@@ -198,7 +219,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         genesis_group: &BTreeSet<S::PublicId>,
         section: &BTreeSet<S::PublicId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         if genesis_group.is_empty() {
             log_or_panic!("Genesis group can't be empty");
@@ -243,7 +264,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         peer_list: PeerList<S>,
         genesis_group: PeerIndexSet,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         dump_graph::init();
 
@@ -255,7 +276,10 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             consensused_blocks: VecDeque::new(),
             observations: BTreeMap::new(),
             meta_election: MetaElection::new(genesis_group),
+            decision_records: None,
             consensus_mode,
+            protocol_version: ProtocolVersion::MIN_SUPPORTED,
+            supported_versions: SupportedVersions::OURS,
             pending_dkg_msgs: vec![],
             #[cfg(feature = "malice-detection")]
             pending_accusations: vec![],
@@ -265,6 +289,25 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             ignore_process_events: false,
 
             secure_rng: ParsecRng::new(secure_rng),
+            hash_algorithm: PhantomData,
+            metrics: None,
+        }
+    }
+
+    /// Sets the receiver of measurements about the consensus internals, such as the size of the
+    /// gossip graph or the time spent computing consensus.  By default, no measurements are taken.
+    pub fn set_metrics(&mut self, metrics: Box<dyn Metrics + Send>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Enables or disables recording how each meta-election is decided, for `explain_consensus`.
+    /// It is disabled by default since the records are kept for as long as this instance lives.
+    /// Disabling it discards the records taken so far.
+    pub fn set_record_decisions(&mut self, enabled: bool) {
+        if !enabled {
+            self.decision_records = None;
+        } else if self.decision_records.is_none() {
+            self.decision_records = Some(vec![]);
         }
     }
 
@@ -273,6 +316,39 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.peer_list.our_pub_id()
     }
 
+    /// Returns the protocol version currently in effect in the section.  This starts at
+    /// `ProtocolVersion::MIN_SUPPORTED` and changes only once an `Observation::Upgrade` has been
+    /// consensused, starting from the block right after the upgrade block.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Returns the range of protocol versions we advertise to our peers and accept messages in.
+    pub fn supported_versions(&self) -> SupportedVersions {
+        self.supported_versions
+    }
+
+    /// Sets the range of protocol versions we advertise to our peers and accept messages in, which
+    /// defaults to `SupportedVersions::OURS`.  Nodes updated to a release supporting a new version
+    /// can keep it out of their range until the whole section runs such a release, then widen their
+    /// range and vote for an `Observation::Upgrade` to it, which is only allowed to versions within
+    /// the range.
+    ///
+    /// Returns `Error::InvalidSupportedVersions` if the range isn't within
+    /// `SupportedVersions::OURS` or doesn't contain the version currently in effect.
+    pub fn set_supported_versions(&mut self, supported_versions: SupportedVersions) -> Result<()> {
+        let ours = SupportedVersions::OURS;
+        if supported_versions.min < ours.min
+            || supported_versions.max > ours.max
+            || !supported_versions.contains(self.protocol_version)
+        {
+            return Err(Error::InvalidSupportedVersions);
+        }
+
+        self.supported_versions = supported_versions;
+        Ok(())
+    }
+
     /// Inserts the owning peer's vote for `observation` into the gossip graph. The subsequent
     /// gossip messages will spread the vote to other peers, eventually making it a candidate for
     /// the next consensused block.
@@ -289,6 +365,12 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             return Err(Error::DuplicateVote);
         }
 
+        if let Observation::Upgrade { version, .. } = observation {
+            if !self.supported_versions.contains(version) || version <= self.protocol_version {
+                return Err(Error::InvalidUpgrade);
+            }
+        }
+
         self.flush_pending_events()?;
 
         let self_parent = self.our_last_event_index()?;
@@ -312,7 +394,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     ///
     /// * `peer_id`: the intended recipient of the gossip message
     /// * returns a `Request` to be sent to the intended recipient
-    pub fn create_gossip(&mut self, peer_id: &S::PublicId) -> Result<Request<T, S::PublicId>> {
+    pub fn create_gossip(&mut self, peer_id: &S::PublicId) -> Result<Request<T, S::PublicId, H>> {
         let peer_index = self.get_peer_index(peer_id)?;
         self.confirm_allowed_to_gossip_to(peer_index)?;
 
@@ -323,7 +405,10 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         } else {
             self.graph.iter().map(|e| e.inner()).collect()
         };
-        self.pack_events(events).map(Request::new)
+        let version = self.protocol_version_for(peer_index);
+        self.pack_events(events).map(|packed_events| {
+            Request::with_version(version, self.supported_versions, packed_events)
+        })
     }
 
     fn create_sole_voter_gossip_event(&mut self) -> Result<()> {
@@ -358,16 +443,33 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     pub fn handle_request(
         &mut self,
         src: &S::PublicId,
-        req: Request<T, S::PublicId>,
-    ) -> Result<Response<T, S::PublicId>> {
+        req: Request<T, S::PublicId, H>,
+    ) -> Result<Response<T, S::PublicId, H>> {
         debug!(
             "{:?} received gossip request from {:?}",
             self.our_pub_id(),
             src
         );
 
+        if let Some(metrics) = &self.metrics {
+            metrics.gossip_received(req.packed_events.len());
+        }
+        let result = self.handle_request_impl(src, req);
+        if let (Some(metrics), Err(error)) = (&self.metrics, &result) {
+            metrics.gossip_rejected(error);
+        }
+        result
+    }
+
+    fn handle_request_impl(
+        &mut self,
+        src: &S::PublicId,
+        req: Request<T, S::PublicId, H>,
+    ) -> Result<Response<T, S::PublicId, H>> {
         let src_index = self.get_peer_index(src)?;
-        let other_parent = self.unpack_and_add_events(src_index, req.packed_events)?;
+        let version = req.version;
+        self.confirm_protocol_version(src_index, version, req.supported_versions)?;
+        let other_parent = self.unpack_and_add_events(src_index, req.into_hashed_events())?;
         self.create_dkg_events()?;
         #[cfg(feature = "malice-detection")]
         self.create_accusation_events(other_parent)?;
@@ -375,7 +477,9 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.flush_pending_events()?;
 
         let events = self.events_to_gossip_to_peer(src_index)?;
-        self.pack_events(events).map(Response::new)
+        self.pack_events(events).map(|packed_events| {
+            Response::with_version(version, self.supported_versions, packed_events)
+        })
     }
 
     /// Handles a `Response` the owning peer received from the `src` peer. Returns `Err` if the
@@ -383,7 +487,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     pub fn handle_response(
         &mut self,
         src: &S::PublicId,
-        resp: Response<T, S::PublicId>,
+        resp: Response<T, S::PublicId, H>,
     ) -> Result<()> {
         debug!(
             "{:?} received gossip response from {:?}",
@@ -391,8 +495,24 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             src
         );
 
+        if let Some(metrics) = &self.metrics {
+            metrics.gossip_received(resp.packed_events.len());
+        }
+        let result = self.handle_response_impl(src, resp);
+        if let (Some(metrics), Err(error)) = (&self.metrics, &result) {
+            metrics.gossip_rejected(error);
+        }
+        result
+    }
+
+    fn handle_response_impl(
+        &mut self,
+        src: &S::PublicId,
+        resp: Response<T, S::PublicId, H>,
+    ) -> Result<()> {
         let src_index = self.get_peer_index(src)?;
-        let other_parent = self.unpack_and_add_events(src_index, resp.packed_events)?;
+        self.confirm_protocol_version(src_index, resp.version, resp.supported_versions)?;
+        let other_parent = self.unpack_and_add_events(src_index, resp.into_hashed_events())?;
         self.create_dkg_events()?;
         #[cfg(feature = "malice-detection")]
         self.create_accusation_events(other_parent)?;
@@ -436,7 +556,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
 
     /// Checks if the given `observation` has already been voted for by the owning peer.
     pub fn have_voted_for(&self, observation: &Observation<T, S::PublicId>) -> bool {
-        let hash = ObservationHash::from(observation);
+        let hash = ObservationHash::new(self.hasher(), observation);
         let key = ObservationKey::new(hash, PeerIndex::OUR, self.consensus_mode.of(observation));
         self.observations
             .get(&key)
@@ -463,11 +583,170 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             .chain(self.our_unconsensused_observations())
     }
 
+    /// Returns how far the given `observation` has progressed towards consensus.  This works for
+    /// observations voted for by any peer, not only by the owning one.
+    ///
+    /// In `ConsensusMode::Single`, each peer's vote for the observation gets its own block, in which
+    /// case the status of the earliest consensused of these blocks is returned, if any.
+    pub fn observation_status(
+        &self,
+        observation: &Observation<T, S::PublicId>,
+    ) -> ObservationStatus<S::PublicId> {
+        let hash = ObservationHash::new(self.hasher(), observation);
+        let keys = match self.consensus_mode.of(observation) {
+            ConsensusMode::Supermajority => {
+                let key = ObservationKey::Supermajority(hash);
+                self.observations
+                    .get_key_value(&key)
+                    .into_iter()
+                    .collect_vec()
+            }
+            ConsensusMode::Single => self
+                .observations
+                .iter()
+                .filter(|(key, _)| *key.hash() == hash)
+                .collect_vec(),
+        };
+
+        if keys.is_empty() {
+            return ObservationStatus::Unknown;
+        }
+
+        let consensused = keys
+            .iter()
+            .filter(|(_, info)| info.consensused)
+            .filter_map(|(key, info)| {
+                self.meta_election
+                    .consensus_history()
+                    .iter()
+                    .position(|consensused_key| consensused_key == *key)
+                    .map(|height| (height, *key, info))
+            })
+            .min_by_key(|(height, _, _)| *height);
+
+        if let Some((height, key, info)) = consensused {
+            return if self.has_unpolled_blocks(key, &info.observation) {
+                ObservationStatus::Consensused { height }
+            } else {
+                ObservationStatus::Polled
+            };
+        }
+
+        let voters = keys
+            .iter()
+            .flat_map(|(key, _)| self.unconsensused_events(Some(key)))
+            .filter_map(|event| self.peer_list.get(event.creator()))
+            .map(|peer| peer.id().clone())
+            .collect();
+        ObservationStatus::Voted { voters }
+    }
+
+    /// Returns a snapshot of the state of the section as known by the owning peer: the state of
+    /// every peer along with how far behind our own knowledge it is, and how far the consensus has
+    /// progressed.
+    pub fn section_status(&self) -> SectionStatus<S::PublicId> {
+        let last_events = self
+            .peer_list
+            .iter()
+            .map(|(peer_index, peer)| {
+                let last_event = self
+                    .peer_list
+                    .last_event(peer_index)
+                    .and_then(|event_index| self.get_known_event(event_index).ok());
+                (peer_index, peer, last_event)
+            })
+            .collect_vec();
+
+        // Number of events by each creator we know of.
+        let known_events = last_events
+            .iter()
+            .map(|(peer_index, _, last_event)| {
+                let count = last_event.map_or(0, |event| event.index_by_creator() + 1);
+                (*peer_index, count)
+            })
+            .collect_vec();
+
+        let mut peers = last_events
+            .iter()
+            .map(|(_, peer, last_event)| {
+                let lag = known_events
+                    .iter()
+                    .map(|(creator, count)| {
+                        let seen = last_event
+                            .and_then(|event| event.last_ancestor_by(*creator))
+                            .map_or(0, |index_by_creator| index_by_creator + 1);
+                        count.saturating_sub(seen)
+                    })
+                    .sum();
+
+                PeerStatus {
+                    id: peer.id().clone(),
+                    state: peer.state(),
+                    last_event_index: last_event.map(|event| event.index_by_creator()),
+                    lag,
+                }
+            })
+            .collect_vec();
+        peers.sort_by(|lhs, rhs| lhs.id.cmp(&rhs.id));
+
+        let meta_election_round = self
+            .peer_list
+            .our_events()
+            .rev()
+            .filter_map(|event_index| self.meta_election.populated_meta_votes(event_index))
+            .next()
+            .and_then(|meta_votes| {
+                meta_votes
+                    .iter()
+                    .filter_map(|(_, votes)| votes.last())
+                    .map(|vote| vote.round)
+                    .max()
+            });
+
+        SectionStatus {
+            peers,
+            unconsensused_observations: self
+                .observations
+                .values()
+                .filter(|info| !info.consensused)
+                .count(),
+            consensused_blocks: self.meta_election.consensus_history().len(),
+            meta_election_round,
+        }
+    }
+
+    /// Returns how the block at the given `height` was decided: the observer events of each
+    /// voter, the progression of the meta votes and the coin tosses they used.  Heights count all
+    /// consensused blocks, internal ones included, as in `ObservationStatus::Consensused`.
+    ///
+    /// Returns `None` if no block has been consensused at that height yet, or if it was consensused
+    /// while recording decisions wasn't enabled with `set_record_decisions`.
+    pub fn explain_consensus(&self, height: usize) -> Option<ConsensusTrace<S::PublicId>> {
+        self.decision_records
+            .as_ref()?
+            .iter()
+            .find(|record| record.heights.contains(&height))
+            .map(|record| record.to_trace(&self.peer_list))
+    }
+
+    /// Writes the gossip graph along with the parts of our state selected by `options` to
+    /// `writer`, in the given `format`.  Unlike the dumps enabled by the `dump-graphs` feature, this
+    /// never writes any secrets, so it can be used in production, e.g. to diagnose a stuck
+    /// section on demand.
+    pub fn write_graph<W: Write>(
+        &self,
+        format: GraphFormat,
+        options: GraphDumpOptions,
+        writer: W,
+    ) -> io::Result<()> {
+        dump_graph::write(&self.graph_info(), format, options, writer)
+    }
+
     fn our_consensused_observations(&self) -> impl Iterator<Item = &Observation<T, S::PublicId>> {
-        self.observations.values().filter_map(move |info| {
+        self.observations.iter().filter_map(move |(key, info)| {
             if info.created_by_us
                 && info.consensused
-                && self.has_our_unpolled_blocks(&info.observation)
+                && self.has_unpolled_blocks(key, &info.observation)
             {
                 Some(&info.observation)
             } else {
@@ -486,7 +765,11 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         })
     }
 
-    fn has_our_unpolled_blocks(&self, payload: &Observation<T, S::PublicId>) -> bool {
+    fn has_unpolled_blocks(
+        &self,
+        payload_key: &ObservationKey,
+        payload: &Observation<T, S::PublicId>,
+    ) -> bool {
         let mut matching_blocks = self
             .consensused_blocks
             .iter()
@@ -494,14 +777,16 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             .filter(|block| block.payload() == payload);
 
         // In `Supermajority` mode, check only if the payload matches, as there can be blocks not
-        // signed by us, yet with payloads voted for by us.
-        // In `Single` mode, on the other hand, check also that we signed it, to avoid false
+        // signed by the voter, yet with payloads voted for by them.
+        // In `Single` mode, on the other hand, check also that the voter signed it, to avoid false
         // positives when there are blocks with the same payloads but signed by someone else.
-        match self.consensus_mode.of(payload) {
-            ConsensusMode::Supermajority => matching_blocks.next().is_some(),
-            ConsensusMode::Single => {
-                matching_blocks.any(|block| block.is_signed_by(self.our_pub_id()))
-            }
+        match *payload_key {
+            ObservationKey::Supermajority(_) => matching_blocks.next().is_some(),
+            ObservationKey::Single(_, creator) => self
+                .peer_list
+                .get(creator)
+                .map(|peer| matching_blocks.any(|block| block.is_signed_by(peer.id())))
+                .unwrap_or(false),
         }
     }
 
@@ -535,6 +820,47 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
     }
 
+    // Records the versions advertised by the peer, so our future gossip to it uses one they
+    // understand, then checks that we can handle the version the message is encoded with.
+    fn confirm_protocol_version(
+        &mut self,
+        peer_index: PeerIndex,
+        version: ProtocolVersion,
+        theirs: SupportedVersions,
+    ) -> Result<()> {
+        self.peer_list.record_supported_versions(peer_index, theirs);
+
+        let ours = self.supported_versions;
+        if ours.contains(version) {
+            Ok(())
+        } else {
+            debug!(
+                "{:?} received message from {:?} with unsupported protocol version {} (ours: {:?})",
+                self.our_pub_id(),
+                peer_index,
+                version,
+                ours,
+            );
+            Err(Error::IncompatibleProtocolVersion {
+                version,
+                theirs,
+                ours,
+            })
+        }
+    }
+
+    // Returns the newest protocol version supported by both us and the given peer, but never newer
+    // than the version in effect in the section, nor than the newest one we support, in case the
+    // section upgraded without us.  Falls back to that version if the peer hasn't advertised its
+    // versions yet or if there is no overlap, in which case the peer will reject the message.
+    fn protocol_version_for(&self, peer_index: PeerIndex) -> ProtocolVersion {
+        let newest = self.protocol_version.min(self.supported_versions.max);
+        self.peer_list
+            .supported_versions(peer_index)
+            .and_then(|theirs| self.supported_versions.highest_common(&theirs))
+            .map_or(newest, |version| version.min(newest))
+    }
+
     fn confirm_self_state(&self, required: PeerState) -> Result<()> {
         let actual = self.peer_list.our_state();
         if actual.contains(required) {
@@ -609,17 +935,27 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     fn unpack_and_add_events(
         &mut self,
         src_index: PeerIndex,
-        packed_events: Vec<PackedEvent<T, S::PublicId>>,
+        hashed_events: Vec<(PackedEvent<T, S::PublicId>, EventHash)>,
     ) -> Result<EventIndex> {
         self.confirm_self_state(PeerState::RECV)?;
         self.confirm_peer_state(src_index, PeerState::SEND)?;
 
-        let hash_of_last_event = packed_events
+        let hash_of_last_event = hashed_events
             .last()
-            .map(PackedEvent::compute_hash)
+            .map(|(_, hash)| *hash)
             .ok_or_else(|| Error::InvalidMessage)?;
-        for packed_event in packed_events {
-            if let Some(event) = self.unpack(packed_event)? {
+        // Reject duplicates before verifying or unpacking any of their events.
+        if let Some(last_event_index) = self.graph.get_index(&hash_of_last_event) {
+            self.confirm_not_handled_yet(last_event_index)?;
+        }
+
+        // Verify all the signatures of the events we don't have yet up front, but still add the
+        // events preceding an invalid one.
+        let graph = &self.graph;
+        let (verified_events, verification_result) =
+            VerifiedEvent::verify_all(hashed_events, |hash| graph.contains(hash));
+        for verified_event in verified_events {
+            if let Some(event) = self.unpack(verified_event)? {
                 let event_creator = event.creator();
                 let event_index = self.add_event(event)?;
 
@@ -634,6 +970,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 self.detect_accomplice(event_index)?;
             }
         }
+        verification_result?;
 
         #[cfg(feature = "malice-detection")]
         self.detect_premature_gossip()?;
@@ -645,11 +982,39 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         Ok(last_event_index)
     }
 
+    // Returns `Err(Error::DuplicateMessage)` if we've already handled a message whose last event is
+    // `other_parent`, i.e. if we already have a sync event, or a pending one, with that
+    // other-parent.  Our sync event must have been added after its other-parent, so we only need
+    // to look at our events which come after it.
+    fn confirm_not_handled_yet(&self, other_parent: EventIndex) -> Result<()> {
+        let handled = self
+            .peer_list
+            .our_events()
+            .rev()
+            .take_while(|index| *index > other_parent)
+            .filter_map(|index| self.graph.get(index))
+            .any(|event| event.other_parent() == Some(other_parent))
+            || self.pending_events.iter().any(|event| match event {
+                PendingEvent::Sync {
+                    other_parent: pending_other_parent,
+                    ..
+                } => *pending_other_parent == other_parent,
+                _ => false,
+            });
+
+        if handled {
+            Err(Error::DuplicateMessage)
+        } else {
+            Ok(())
+        }
+    }
+
     fn unpack(
         &mut self,
-        packed_event: PackedEvent<T, S::PublicId>,
+        verified_event: VerifiedEvent<T, S::PublicId>,
     ) -> Result<Option<Event<S::PublicId>>> {
-        if let Some(unpacked_event) = Event::unpack(packed_event, self.event_context())? {
+        if let Some(unpacked_event) = Event::unpack_verified(verified_event, self.event_context())?
+        {
             if let Some((payload_key, observation_info)) = unpacked_event.observation_for_store {
                 let _ = self
                     .observations
@@ -712,6 +1077,9 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             });
 
         let event_index = self.insert_event(event);
+        if let Some(metrics) = &self.metrics {
+            metrics.event_added(self.graph.len());
+        }
 
         let _ = unconsensused_payload_key.map(|payload_key| {
             self.meta_election
@@ -746,7 +1114,13 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     fn process_events(&mut self, mut start_index: usize) -> Result<()> {
         'outer: loop {
             for event_index in self.graph.indices_from(start_index) {
-                match self.process_event(event_index)? {
+                let start_time = self.metrics.as_ref().map(|_| Instant::now());
+                let action = self.process_event(event_index)?;
+                if let (Some(metrics), Some(start_time)) = (&self.metrics, start_time) {
+                    metrics.event_processed(start_time.elapsed());
+                }
+
+                match action {
                     PostProcessAction::Restart(new_start_index)
                         if new_start_index <= event_index.topological_index() =>
                     {
@@ -776,6 +1150,15 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
 
         self.output_consensus_info(&payload_keys);
+        if let Some(metrics) = &self.metrics {
+            metrics.consensus_reached(self.decision_rounds(event_index), payload_keys.len());
+        }
+        if self.decision_records.is_some() {
+            let decision_record = self.create_decision_record(event_index, payload_keys.len())?;
+            if let Some(decision_records) = &mut self.decision_records {
+                decision_records.push(decision_record);
+            }
+        }
 
         let blocks = self.create_blocks(&payload_keys)?;
         if !blocks.is_empty() {
@@ -784,9 +1167,13 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
 
         self.mark_observations_as_consensused(&payload_keys);
 
+        let first_block_number = self.meta_election.consensus_history().len();
         let peer_list_changes = payload_keys
             .iter()
-            .filter_map(|payload_key| self.handle_consensus(event_index, payload_key))
+            .enumerate()
+            .filter_map(|(offset, payload_key)| {
+                self.handle_consensus(event_index, first_block_number + offset, payload_key)
+            })
             .collect();
 
         self.meta_election
@@ -797,14 +1184,20 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         Ok(PostProcessAction::Restart(start_index))
     }
 
-    fn output_consensus_info(&self, payload_keys: &[ObservationKey]) {
-        dump_graph::to_file(dump_graph::ToFileInfo {
-            owner_id: self.our_pub_id(),
+    fn graph_info(&self) -> dump_graph::GraphInfo<T, S> {
+        dump_graph::GraphInfo {
             consensus_mode: self.consensus_mode,
             gossip_graph: &self.graph,
             meta_election: &self.meta_election,
             peer_list: &self.peer_list,
             observations: &self.observations,
+        }
+    }
+
+    fn output_consensus_info(&self, payload_keys: &[ObservationKey]) {
+        dump_graph::to_file(dump_graph::ToFileInfo {
+            owner_id: self.our_pub_id(),
+            graph: self.graph_info(),
             secure_rng: &self.secure_rng,
             key_gens_and_next_id: (&self.key_gen, &self.key_gen_next_id),
             info: &dump_graph::DumpGraphContext::ConsensusReached,
@@ -825,6 +1218,83 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
     }
 
+    // Returns the number of meta-election rounds it took for the meta votes of the given event to
+    // decide, or zero if there are none.
+    fn decision_rounds(&self, event_index: EventIndex) -> usize {
+        self.meta_election
+            .populated_meta_votes(event_index)
+            .and_then(|meta_votes| {
+                meta_votes
+                    .iter()
+                    .filter_map(|(_, votes)| votes.last())
+                    .map(|vote| vote.round + 1)
+                    .max()
+            })
+            .unwrap_or(0)
+    }
+
+    // Records the observer events and the meta votes which led the given event to decide the
+    // current meta-election.
+    fn create_decision_record(
+        &self,
+        event_index: EventIndex,
+        num_blocks: usize,
+    ) -> Result<DecisionRecord> {
+        let first_height = self.meta_election.consensus_history().len();
+        let meta_votes = match self.meta_election.populated_meta_votes(event_index) {
+            Some(meta_votes) => meta_votes.clone(),
+            None => {
+                // We're the only voter, so no meta-voting took place.
+                return Ok(DecisionRecord {
+                    heights: first_height..first_height + num_blocks,
+                    decided_by: None,
+                    observers: PeerIndexMap::new(),
+                    meta_votes: PeerIndexMap::new(),
+                    coin_tosses: PeerIndexMap::new(),
+                });
+            }
+        };
+
+        // Only the first observer event of each creator counts.
+        let mut observers: BTreeMap<PeerIndex, (usize, EventHash, &PeerIndexSet)> = BTreeMap::new();
+        for (observer_index, observees) in self.meta_election.observers() {
+            if observer_index.topological_index() > event_index.topological_index() {
+                // Not processed during this meta-election yet.
+                continue;
+            }
+            let observer = self.get_known_event(observer_index)?;
+            let entry = (observer.index_by_creator(), *observer.hash(), observees);
+            match observers.get(&observer.creator()) {
+                Some((index_by_creator, _, _)) if *index_by_creator <= entry.0 => (),
+                _ => {
+                    let _ = observers.insert(observer.creator(), entry);
+                }
+            }
+        }
+
+        let voters = self.voters();
+        let mut coin_tosses = PeerIndexMap::new();
+        for (peer_index, votes) in &meta_votes {
+            let votes = votes
+                .iter()
+                .filter(|vote| vote.step == Step::GenuineFlip)
+                .cloned()
+                .collect_vec();
+            let _ = coin_tosses.insert(peer_index, self.toss_coins(voters, peer_index, &votes)?);
+        }
+
+        Ok(DecisionRecord {
+            heights: first_height..first_height + num_blocks,
+            decided_by: Some(*self.get_known_event(event_index)?.hash()),
+            observers: observers
+                .into_iter()
+                .map(|(creator, (_, hash, observees))| (creator, (hash, observees.clone())))
+                .collect(),
+            meta_votes,
+            coin_tosses,
+        })
+    }
+
     fn mark_observations_as_consensused(&mut self, payload_keys: &[ObservationKey]) {
         for payload_key in payload_keys {
             if let Some(info) = self.observations.get_mut(payload_key) {
@@ -843,6 +1313,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     fn handle_consensus(
         &mut self,
         event_index: EventIndex,
+        block_number: usize,
         payload_key: &ObservationKey,
     ) -> Option<PeerListChange> {
         match self
@@ -886,6 +1357,10 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 }
                 None
             }
+            Some(Observation::Upgrade { version, .. }) => {
+                self.handle_upgrade_consensus(block_number, version);
+                None
+            }
             Some(Observation::Genesis { .. }) | Some(Observation::OpaquePayload(_)) => None,
             None => {
                 log_or_panic!("Failed to get observation from hash.");
@@ -894,6 +1369,41 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
     }
 
+    // This function must be called on consensus on an `Upgrade` observation.
+    fn handle_upgrade_consensus(&mut self, block_number: usize, version: ProtocolVersion) {
+        if version <= self.protocol_version {
+            debug!(
+                "{:?} ignoring upgrade to {} in block {} as {} is already in effect.",
+                self.our_pub_id(),
+                version,
+                block_number,
+                self.protocol_version
+            );
+            return;
+        }
+
+        if !self.supported_versions.contains(version) {
+            // We still follow the section so that our view of the consensus stays consistent, but
+            // we'll keep gossiping using the newest version we support.
+            error!(
+                "{:?} section upgraded to unsupported protocol {} in block {}. This node needs to \
+                 be upgraded.",
+                self.our_pub_id(),
+                version,
+                block_number
+            );
+        }
+
+        info!(
+            "{:?} switching from protocol {} to {} after block {}",
+            self.our_pub_id(),
+            self.protocol_version,
+            version,
+            block_number
+        );
+        self.protocol_version = version;
+    }
+
     fn handle_dkg_message(&mut self, payload_key: &ObservationKey, msg: DkgMessage) -> Option<()> {
         let creator_id = self.peer_list.get(payload_key.peer_index()?)?.id().clone();
 
@@ -1136,9 +1646,16 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             self.is_interesting_payload(builder, &peers_that_can_vote, payload_key)
         };
 
+        // Events carrying payloads the creator already finds interesting can't contribute new
+        // interesting content, so skip them rather than checking all the unconsensused events.
+        let unconsensused_events = self
+            .meta_election
+            .unconsensused_events_not_interesting_to(builder.event().creator())
+            .filter_map(|index| self.get_known_event(index).ok());
+
         let payloads = find_interesting_content_for_event(
             builder.event(),
-            self.unconsensused_events(None),
+            unconsensused_events,
             consistent_cmp,
             is_descendant,
             is_already_interesting_content,
@@ -1518,8 +2035,12 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 }
             })
             .collect();
+        let blocks = blocks?;
+        if let Some(metrics) = &self.metrics {
+            metrics.blocks_created(blocks.len());
+        }
 
-        Ok(BlockGroup(blocks?))
+        Ok(BlockGroup(blocks))
     }
 
     // Returns the number of peers that created events which are seen by event X (descendant) and
@@ -1689,9 +2210,14 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             peer_list: &self.peer_list,
             observations: &self.observations,
             consensus_mode: self.consensus_mode,
+            hasher: self.hasher(),
         }
     }
 
+    fn hasher(&self) -> Hasher {
+        Hasher::new::<H>()
+    }
+
     #[cfg(any(all(test, feature = "mock"), feature = "malice-detection"))]
     fn event_payload<'a>(
         &'a self,
@@ -1720,7 +2246,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
 }
 
 #[cfg(feature = "malice-detection")]
-impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
+impl<T: NetworkEvent, S: SecretId, H: HashAlgorithm> Parsec<T, S, H> {
     fn create_accusation_events(&mut self, other_parent: EventIndex) -> Result<()> {
         let pending_accusations = mem::replace(&mut self.pending_accusations, vec![]);
         for (offender, malice) in pending_accusations {
@@ -2080,6 +2606,9 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     }
 
     fn accuse(&mut self, offender: PeerIndex, malice: Malice<T, S::PublicId>) {
+        if let Some(metrics) = &self.metrics {
+            metrics.malice_detected(malice.kind());
+        }
         self.pending_accusations.push((offender, malice));
     }
 
@@ -2252,15 +2781,11 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     }
 }
 
-impl<T: NetworkEvent, S: SecretId> Drop for Parsec<T, S> {
+impl<T: NetworkEvent, S: SecretId, H: HashAlgorithm> Drop for Parsec<T, S, H> {
     fn drop(&mut self) {
         dump_graph::to_file(dump_graph::ToFileInfo {
             owner_id: self.our_pub_id(),
-            consensus_mode: self.consensus_mode,
-            gossip_graph: &self.graph,
-            meta_election: &self.meta_election,
-            peer_list: &self.peer_list,
-            observations: &self.observations,
+            graph: self.graph_info(),
             secure_rng: &self.secure_rng,
             key_gens_and_next_id: (&self.key_gen, &self.key_gen_next_id),
             info: &dump_graph::DumpGraphContext::DroppingParsec,
@@ -2309,7 +2834,7 @@ enum PendingEvent<T: NetworkEvent, P: PublicId> {
 }
 
 #[cfg(any(test, feature = "testing"))]
-impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
+impl<T: NetworkEvent, S: SecretId, H: HashAlgorithm> Parsec<T, S, H> {
     // Disable processing consensus on this instance (speed up processing).
     pub(crate) fn set_ignore_process_events(&mut self) {
         self.ignore_process_events = true;
@@ -2321,12 +2846,28 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     }
 }
 
+#[cfg(test)]
+impl<T: NetworkEvent, S: SecretId, H: HashAlgorithm> Parsec<T, S, H> {
+    // Hashes of all the events in the graph, along with the events themselves in packed form.
+    pub(crate) fn hashed_packed_events(&self) -> Vec<(EventHash, PackedEvent<T, S::PublicId>)> {
+        self.graph
+            .iter()
+            .map(|event| (*event.hash(), unwrap!(event.pack(self.event_context()))))
+            .collect()
+    }
+
+    // Keys of the consensused observations, in order of consensus.
+    pub(crate) fn consensus_history(&self) -> &[ObservationKey] {
+        self.meta_election.consensus_history()
+    }
+}
+
 #[cfg(any(feature = "testing", all(test, feature = "mock")))]
 impl Parsec<Transaction, PeerId> {
     #[cfg(all(test, feature = "mock"))]
     pub(crate) fn from_parsed_contents(
         mut parsed_contents: ParsedContents,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         let peer_list = PeerList::new(parsed_contents.our_id);
         let mut parsec = Parsec::empty(
@@ -2396,7 +2937,7 @@ impl<T: NetworkEvent, S: SecretId> TestParsec<T, S> {
         our_id: S,
         genesis_group: &BTreeSet<S::PublicId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         TestParsec(Parsec::from_genesis(
             our_id,
@@ -2412,7 +2953,7 @@ impl<T: NetworkEvent, S: SecretId> TestParsec<T, S> {
         genesis_group: &BTreeSet<S::PublicId>,
         section: &BTreeSet<S::PublicId>,
         consensus_mode: ConsensusMode,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         TestParsec(Parsec::from_existing(
             our_id,
@@ -2454,6 +2995,22 @@ impl<T: NetworkEvent, S: SecretId> TestParsec<T, S> {
         self.0.confirm_allowed_to_gossip_to(peer_index)
     }
 
+    // Warning: only add events created using this instance of `Parsec`. Adding an event from other
+    // instance is not detectable and might lead to incorrect test results. To add event from other
+    // instance, first `pack_event` it using that other instance, then add it using
+    // `unpack_and_add_event`.
+    pub fn add_event(&mut self, event: Event<S::PublicId>) -> Result<EventIndex> {
+        self.0.add_event(event)
+    }
+
+    pub fn new_event_from_observation(
+        &mut self,
+        self_parent: EventIndex,
+        observation: Observation<T, S::PublicId>,
+    ) -> Result<Event<S::PublicId>> {
+        self.0.new_event_from_observation(self_parent, observation)
+    }
+
     #[cfg(all(test, feature = "mock"))]
     pub fn event_payload(
         &self,
@@ -2461,13 +3018,20 @@ impl<T: NetworkEvent, S: SecretId> TestParsec<T, S> {
     ) -> Option<&Observation<T, S::PublicId>> {
         self.0.event_payload(event)
     }
+
+    // Unlike `Parsec::set_supported_versions`, allows versions newer than this crate's, to simulate
+    // peers running a newer release.
+    #[cfg(all(test, feature = "mock"))]
+    pub fn set_supported_versions_unchecked(&mut self, supported_versions: SupportedVersions) {
+        self.0.supported_versions = supported_versions;
+    }
 }
 
 #[cfg(all(test, feature = "mock"))]
 impl TestParsec<Transaction, PeerId> {
     pub fn from_parsed_contents(
         parsed_contents: ParsedContents,
-        secure_rng: Box<dyn rand::Rng>,
+        secure_rng: Box<dyn rand::Rng + Send>,
     ) -> Self {
         TestParsec(Parsec::from_parsed_contents(parsed_contents, secure_rng))
     }
@@ -2493,31 +3057,16 @@ impl TestParsec<Transaction, PeerId> {
         &mut self,
         packed_event: PackedEvent<Transaction, PeerId>,
     ) -> Result<EventIndex> {
-        match self.0.unpack(packed_event)? {
+        let verified_event = VerifiedEvent::verify(packed_event, self.0.hasher())?;
+        match self.0.unpack(verified_event)? {
             Some(event) => self.0.add_event(event),
             None => Err(Error::Logic),
         }
     }
 
-    // Warning: only add events created using this instance of `Parsec`. Adding an event from other
-    // instance is not detectable and might lead to incorrect test results. To add event from other
-    // instance, first `pack_event` it using that other instance, then add it using
-    // `unpack_and_add_event`.
-    pub fn add_event(&mut self, event: Event<PeerId>) -> Result<EventIndex> {
-        self.0.add_event(event)
-    }
-
     pub fn event_creator_id(&self, event: &Event<PeerId>) -> &PeerId {
         unwrap!(self.0.event_creator_id(event))
     }
-
-    pub fn new_event_from_observation(
-        &mut self,
-        self_parent: EventIndex,
-        observation: Observation<Transaction, PeerId>,
-    ) -> Result<Event<PeerId>> {
-        self.0.new_event_from_observation(self_parent, observation)
-    }
 }
 
 #[cfg(all(test, feature = "malice-detection", feature = "mock"))]
@@ -2557,7 +3106,9 @@ impl TestParsec<Transaction, PeerId> {
     ) -> EventHash {
         let src_index = unwrap!(self.0.get_peer_index(src));
         let last_hash = unwrap!(req.packed_events.last()).compute_hash();
-        let other_parent = unwrap!(self.0.unpack_and_add_events(src_index, req.packed_events));
+        let other_parent = unwrap!(self
+            .0
+            .unpack_and_add_events(src_index, req.into_hashed_events()));
         unwrap!(self.0.create_accusation_events(other_parent));
 
         let invalid_observation = Observation::<Transaction, _>::Accusation {
@@ -2586,7 +3137,9 @@ impl TestParsec<Transaction, PeerId> {
         req: Request<Transaction, PeerId>,
     ) {
         let src_index = unwrap!(self.0.get_peer_index(src));
-        let other_parent = unwrap!(self.0.unpack_and_add_events(src_index, req.packed_events));
+        let other_parent = unwrap!(self
+            .0
+            .unpack_and_add_events(src_index, req.into_hashed_events()));
         self.0.pending_accusations.clear();
         unwrap!(self.0.create_sync_event(true, other_parent));
     }
@@ -98,7 +98,7 @@ where
 mod tests {
     use super::*;
     use crate::{
-        hash::Hash,
+        hash::{HashDomain, Hasher},
         observation::{ConsensusMode, ObservationHash},
         peer_list::PeerIndex,
     };
@@ -181,7 +181,7 @@ mod tests {
     }
 
     fn observation_hash_from_u8(value: u8) -> ObservationHash {
-        ObservationHash(Hash::from(vec![value].as_slice()))
+        ObservationHash(Hasher::default().hash(HashDomain::Observation, &[value]))
     }
 
     fn find_observation_hash_index(hash: &ObservationHash, hashes: &[ObservationHash]) -> u8 {
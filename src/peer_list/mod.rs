@@ -24,7 +24,7 @@ use crate::gossip::Graph;
 use crate::mock::PeerId;
 use crate::{
     error::Error,
-    gossip::{EventIndex, IndexedEventRef},
+    gossip::{EventIndex, IndexedEventRef, SupportedVersions},
     id::SecretId,
 };
 use std::{
@@ -218,6 +218,19 @@ impl<S: SecretId> PeerList<S> {
             .flat_map(move |peer| peer.events_by_index(index_by_creator))
     }
 
+    /// Returns the protocol versions the given peer last advertised to us, if any.
+    pub fn supported_versions(&self, peer_index: PeerIndex) -> Option<SupportedVersions> {
+        self.get(peer_index)
+            .and_then(|peer| peer.supported_versions)
+    }
+
+    /// Record the protocol versions the given peer advertised to us.
+    pub fn record_supported_versions(&mut self, index: PeerIndex, versions: SupportedVersions) {
+        if let Some(peer) = self.get_known_mut(index) {
+            peer.supported_versions = Some(versions)
+        }
+    }
+
     /// Record that the given peer gossiped to us the given event.
     pub fn record_gossiped_event_by(&mut self, index: PeerIndex, event_index: EventIndex) {
         if let Some(peer) = self.get_known_mut(index) {
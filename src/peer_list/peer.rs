@@ -8,7 +8,7 @@
 
 use super::peer_state::PeerState;
 use crate::{
-    gossip::{EventIndex, IndexedEventRef},
+    gossip::{EventIndex, IndexedEventRef, SupportedVersions},
     id::PublicId,
 };
 use itertools::Itertools;
@@ -23,6 +23,8 @@ pub(crate) struct Peer<P: PublicId> {
     presence: Presence,
     pub(super) events: Events,
     pub(super) last_gossiped_event: Option<EventIndex>,
+    // Protocol versions the peer advertised in the last gossip message we received from it.
+    pub(super) supported_versions: Option<SupportedVersions>,
     // As a performance optimisation we keep track of which events we've cleared for Accomplice
     // accusations.
     #[cfg(feature = "malice-detection")]
@@ -36,6 +38,7 @@ impl<P: PublicId> Peer<P> {
             presence: Presence::Present(state),
             events: Events::new(),
             last_gossiped_event: None,
+            supported_versions: None,
             #[cfg(feature = "malice-detection")]
             accomplice_event_checkpoint: None,
         }
@@ -20,7 +20,7 @@ use std::{
 ///           others. For others it means we can send gossips to them.
 ///
 /// If all three are enabled, the state is called `active`. If none is enabled, it's `inactive`.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PeerState(u8);
 
 impl PeerState {
@@ -34,30 +34,37 @@ impl PeerState {
     /// The peer can participate in DKG.
     pub const DKG: Self = PeerState(0b0000_1000);
 
+    /// State with no flag enabled.
     pub fn inactive() -> Self {
         PeerState(0)
     }
 
+    /// State with `VOTE`, `SEND` and `RECV` enabled.
     pub fn active() -> Self {
         Self::VOTE | Self::SEND | Self::RECV
     }
 
+    /// Returns whether all the flags enabled in `other` are enabled in this state too.
     pub fn contains(self, other: Self) -> bool {
         self.0 & other.0 == other.0
     }
 
+    /// Returns whether `VOTE` is enabled.
     pub fn can_vote(self) -> bool {
         self.contains(Self::VOTE)
     }
 
+    /// Returns whether `DKG` is enabled.
     pub fn can_dkg(self) -> bool {
         self.contains(Self::DKG)
     }
 
+    /// Returns whether `SEND` is enabled.
     pub fn can_send(self) -> bool {
         self.contains(Self::SEND)
     }
 
+    /// Returns whether `RECV` is enabled.
     pub fn can_recv(self) -> bool {
         self.contains(Self::RECV)
     }
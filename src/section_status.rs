@@ -0,0 +1,40 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{id::PublicId, peer_list::PeerState};
+
+/// Snapshot of the state of the section as known by the owning peer, intended to help diagnose why
+/// a section doesn't make progress.  Returned by
+/// [Parsec::section_status](struct.Parsec.html#method.section_status).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionStatus<P: PublicId> {
+    /// Status of every peer in our peer list, ourselves included, ordered by their IDs.
+    pub peers: Vec<PeerStatus<P>>,
+    /// Number of observations that have been voted for, but not consensused yet.
+    pub unconsensused_observations: usize,
+    /// Number of blocks consensused so far, internal ones included.
+    pub consensused_blocks: usize,
+    /// Highest round reached by the meta votes of our latest meta-event in the current
+    /// meta-election, or `None` if we haven't started meta-voting in it yet.
+    pub meta_election_round: Option<usize>,
+}
+
+/// Status of a single peer, as part of a [SectionStatus](struct.SectionStatus.html).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PeerStatus<P: PublicId> {
+    /// ID of the peer.
+    pub id: P,
+    /// State of the peer. Removed peers are `inactive`.
+    pub state: PeerState,
+    /// Index-by-creator of the last event created by the peer we know of, if any.
+    pub last_event_index: Option<usize>,
+    /// Number of events we know of that the peer's last event we know of doesn't have as its
+    /// ancestors. This is how far behind our knowledge of the section that peer is, as far as we
+    /// can tell.
+    pub lag: usize,
+}
@@ -8,6 +8,7 @@
 
 use crate::{
     error::Error,
+    hash::Hasher,
     id::{Proof, PublicId, SecretId},
     network_event::NetworkEvent,
     observation::{ConsensusMode, Observation, ObservationHash, ObservationKey, ObservationStore},
@@ -78,9 +79,10 @@ impl<P: PublicId> VoteKey<P> {
         vote: Vote<T, P>,
         creator: PeerIndex,
         consensus_mode: ConsensusMode,
+        hasher: Hasher,
     ) -> (Self, Observation<T, P>) {
         let consensus_mode = consensus_mode.of(&vote.payload);
-        let hash = ObservationHash::from(&vote.payload);
+        let hash = ObservationHash::new(hasher, &vote.payload);
         let payload_key = ObservationKey::new(hash, creator, consensus_mode);
 
         let vote_key = Self {
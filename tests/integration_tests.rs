@@ -61,16 +61,21 @@ extern crate unwrap;
 use maidsafe_utilities::log;
 use parsec::{
     dev_utils::{
+        check_interleavings, check_interleavings_with, minimise_schedule,
         proptest::{arbitrary_delay, ScheduleOptionsStrategy, ScheduleStrategy},
-        DelayDistribution, Environment, Genesis, ObservationEvent, ObservationSchedule, RngChoice,
-        Sampling, Schedule, ScheduleOptions,
+        ConsensusError, DelayDistribution, Environment, Genesis, InterleavingBounds,
+        MaliciousBehaviourType, Network, ObservationEvent, ObservationSchedule, Partition,
+        RngChoice, Sampling, Schedule, ScheduleEvent, ScheduleOptions, Topology, VirtualClock,
     },
     mock::{PeerId, Transaction, NAMES},
     ConsensusMode, Observation,
 };
 use proptest::{prelude::ProptestConfig, test_runner::FileFailurePersistence};
 use rand::Rng;
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    panic::{self, AssertUnwindSafe},
+};
 
 // Alter the seed here to reproduce failures
 static SEED: RngChoice = RngChoice::SeededRandom;
@@ -168,7 +173,7 @@ fn faulty_third_terminate_concurrently() {
     let mut env = Environment::new(SEED);
 
     let mut failures = BTreeMap::new();
-    let _ = failures.insert(env.rng.gen_range(10, 50), num_faulty);
+    let _ = failures.insert(env.rng.gen_range(100, 500), num_faulty);
     let options = ScheduleOptions {
         genesis_size: num_peers,
         opaque_to_add: num_observations,
@@ -609,6 +614,546 @@ fn consensus_with_fork() {
     unwrap!(env.execute_schedule(schedule));
 }
 
+#[test]
+fn consensus_with_malicious_behaviours() {
+    let behaviours = [
+        MaliciousBehaviourType::EquivocatingVote,
+        MaliciousBehaviourType::DuplicateVote,
+        MaliciousBehaviourType::WithholdEvents,
+        MaliciousBehaviourType::InvalidAccusation,
+        MaliciousBehaviourType::PrematureGossip,
+        MaliciousBehaviourType::Spam,
+        MaliciousBehaviourType::ForgedRecipient,
+    ];
+    for behaviour in &behaviours {
+        let mut env = Environment::new(SEED);
+        let options = ScheduleOptions {
+            genesis_size: 5,
+            malicious_genesis_count: 1,
+            opaque_to_add: 2,
+            // Give premature gossip a chance to happen.
+            peers_to_add: 1,
+            malicious_behaviours: vec![*behaviour],
+            ..Default::default()
+        };
+        let schedule = Schedule::new(&mut env, &options);
+        let result = env.execute_schedule(schedule);
+        assert!(result.is_ok(), "{:?}: {:?} {:?}", behaviour, env, result);
+    }
+}
+
+fn peer_ids(names: &[&str]) -> BTreeSet<PeerId> {
+    names.iter().map(|name| PeerId::new(name)).collect()
+}
+
+#[test]
+fn consensus_after_even_partition_heals() {
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 4,
+        partitions: vec![Partition {
+            start_time_ms: 0,
+            heal_time_ms: 3000,
+            groups: vec![peer_ids(&["Alice", "Bob"]), peer_ids(&["Carol", "Dave"])],
+        }],
+        ..Default::default()
+    };
+
+    // Neither half can reach consensus on its own, so no peer has any block before healing.
+    let mut env = Environment::new(SEED);
+    let mut schedule = Schedule::new(&mut env, &options);
+    let heal_index = unwrap!(schedule.events.iter().position(|event| match *event {
+        ScheduleEvent::Heal => true,
+        _ => false,
+    }));
+    schedule.events.truncate(heal_index);
+    schedule.additional_steps = 0..0;
+    unwrap!(env.execute_schedule_prefix(schedule));
+    assert!(env
+        .network
+        .peers
+        .values()
+        .all(|peer| peer.blocks().next().is_none()));
+
+    // Running the same schedule to the end, consensus is reached after healing.
+    let mut env = Environment::new(SEED);
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+}
+
+#[test]
+fn consensus_with_isolated_peer_and_dropped_messages() {
+    // The majority keeps making progress while Eric is cut off, and Eric catches up afterwards.
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        opaque_to_add: 4,
+        partitions: vec![Partition {
+            start_time_ms: 200,
+            heal_time_ms: 4000,
+            groups: vec![peer_ids(&["Eric"])],
+        }],
+        drop_partitioned_messages: true,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+}
+
+#[test]
+fn consensus_with_lost_duplicated_and_reordered_messages() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        opaque_to_add: 5,
+        prob_message_drop: 0.2,
+        prob_message_duplication: 0.2,
+        prob_message_reorder: 0.2,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+}
+
+#[test]
+fn consensus_with_message_faults_and_dynamic_membership() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 3,
+        peers_to_add: 2,
+        peers_to_remove: 1,
+        prob_message_drop: 0.1,
+        prob_message_duplication: 0.1,
+        prob_message_reorder: 0.1,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+}
+
+#[test]
+fn consensus_across_regions() {
+    // Two regions with fast links within them and slow ones between them, plus a peer whose links
+    // to everyone are slower still.
+    let mut env = Environment::new(SEED);
+    let mut topology = Topology::with_regions(
+        vec![
+            peer_ids(&["Alice", "Bob", "Carol"]),
+            peer_ids(&["Dave", "Eric", "Fred"]),
+        ],
+        DelayDistribution::Constant(10),
+        DelayDistribution::Poisson(150.0),
+    );
+    for peer_id in peer_ids(&["Alice", "Bob", "Carol", "Dave", "Eric", "Fred"]) {
+        let _ = topology.links.insert(
+            (peer_id, PeerId::new("Gina")),
+            DelayDistribution::Constant(300),
+        );
+    }
+    let options = ScheduleOptions {
+        genesis_size: 7,
+        opaque_to_add: 4,
+        topology: Some(topology),
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn replay_saved_schedule() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        malicious_genesis_count: 1,
+        opaque_to_add: 3,
+        peers_to_add: 1,
+        partitions: vec![Partition {
+            start_time_ms: 100,
+            heal_time_ms: 500,
+            groups: vec![peer_ids(&["Alice", "Bob"])],
+        }],
+        topology: Some(Topology::with_regions(
+            vec![peer_ids(&["Alice", "Carol"])],
+            DelayDistribution::Constant(10),
+            DelayDistribution::Poisson(80.0),
+        )),
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+
+    let mut saved = vec![];
+    unwrap!(schedule.write(&mut saved));
+    let loaded = unwrap!(Schedule::load(&saved[..]));
+    assert_eq!(format!("{:?}", loaded), format!("{:?}", schedule));
+    assert_eq!(
+        format!("{:?}", loaded.options),
+        format!("{:?}", schedule.options)
+    );
+    assert_eq!(loaded.seed, schedule.seed);
+
+    unwrap!(env.execute_schedule(schedule));
+    let blocks = blocks_by_peer(&env);
+
+    // The saved schedule alone reproduces the run, whatever the seed of the environment.
+    let mut replay_env = Environment::new(RngChoice::SeededRandom);
+    unwrap!(replay_env.execute_schedule(loaded));
+    assert_eq!(blocks_by_peer(&replay_env), blocks);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn replay_schedule_saved_in_steps() {
+    // Saved before the virtual clock, with durations in global steps and gossiping with a
+    // probability of 0.3 per step.
+    let file = unwrap!(std::fs::File::open(
+        "input_graphs/integration_tests_replay_schedule_saved_in_steps/schedule.json"
+    ));
+    let schedule = unwrap!(Schedule::load(file));
+    assert_eq!(schedule.options.clock, VirtualClock::new(1));
+    assert_eq!(schedule.options.prob_gossip, Some(0.3));
+    assert_eq!(schedule.options.liveness_bound_ms, Some(2000));
+    assert_eq!(schedule.options.partitions[0].start_time_ms, 10);
+    assert_eq!(schedule.options.partitions[0].heal_time_ms, 60);
+
+    // The replay matches the run made when the schedule was saved.
+    let mut env = Environment::new(RngChoice::SeededRandom);
+    let report = unwrap!(env.execute_schedule(schedule));
+    assert_eq!((report.requests, report.responses), (78, 75));
+    for (peer_id, blocks) in blocks_by_peer(&env) {
+        if peer_id == PeerId::new("Carol") {
+            assert!(blocks.is_empty());
+        } else {
+            assert_eq!(
+                format!("{:?}", blocks),
+                "[Genesis({Alice, Bob, Carol, Dave}), OpaquePayload(1), Add(Eric), \
+                 OpaquePayload(0)]"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn blocks_by_peer(env: &Environment) -> BTreeMap<PeerId, Vec<Observation<Transaction, PeerId>>> {
+    env.network
+        .peers
+        .iter()
+        .map(|(peer_id, peer)| {
+            let blocks = peer.blocks_payloads().into_iter().cloned().collect();
+            (peer_id.clone(), blocks)
+        })
+        .collect()
+}
+
+#[test]
+fn minimise_failing_schedule() {
+    // The partition never heals, so neither half can reach consensus and the schedule fails with
+    // `WrongBlocksNumber`.
+    let seed = RngChoice::SeededXor([1, 2, 3, 4]);
+    let mut env = Environment::new(seed);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 1,
+        partitions: vec![Partition {
+            start_time_ms: 0,
+            heal_time_ms: 3000,
+            groups: vec![peer_ids(&["Alice", "Bob"]), peer_ids(&["Carol", "Dave"])],
+        }],
+        ..Default::default()
+    };
+    let mut schedule = Schedule::new(&mut env, &options);
+    schedule.events.retain(|event| match *event {
+        ScheduleEvent::Heal => false,
+        _ => true,
+    });
+    // As consensus is never reached, every step gets executed: keep just enough of them for the
+    // peers to reach consensus without the partition.
+    schedule.additional_steps.end = schedule.additional_steps.start + 300;
+
+    let (minimised, error) = unwrap!(minimise_schedule(schedule, || Environment::new(seed)));
+    match error {
+        ConsensusError::WrongBlocksNumber { .. } => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+    assert!(minimised.min_observations >= 1);
+    assert!(minimised.events.iter().any(|event| match *event {
+        ScheduleEvent::Partition(_) => true,
+        _ => false,
+    }));
+
+    let replayed = unwrap!(Environment::new(seed)
+        .execute_schedule(minimised.clone())
+        .err());
+    assert_eq!(format!("{:?}", replayed), format!("{:?}", error));
+
+    // The minimised schedule is 1-minimal: it doesn't fail the same way without any single one of
+    // its events but genesis.
+    for index in 1..minimised.events.len() {
+        let mut candidate = minimised.clone();
+        let removed = candidate.events.remove(index);
+        candidate.recompute_expectations(ConsensusMode::Supermajority);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            Environment::new(seed).execute_schedule(candidate)
+        }));
+        if let Ok(Err(ConsensusError::WrongBlocksNumber { .. })) = result {
+            panic!("Still fails without {:?}", removed);
+        }
+    }
+}
+
+#[test]
+fn recomputed_expectations_match_generated_ones() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 5,
+        peers_to_add: 2,
+        peers_to_remove: 1,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    let mut recomputed = schedule.clone();
+    recomputed.recompute_expectations(env.network.consensus_mode());
+    assert_eq!(recomputed.peers, schedule.peers);
+    assert_eq!(recomputed.min_observations, schedule.min_observations);
+    assert_eq!(recomputed.max_observations, schedule.max_observations);
+}
+
+#[test]
+fn check_bounded_interleavings() {
+    let seed = RngChoice::SeededXor([1, 2, 3, 4]);
+    let transaction = Observation::OpaquePayload(Transaction::new("one"));
+    let mut schedule = Schedule::from_votes(
+        &mut Environment::new(seed),
+        Genesis::new(peer_ids(&["Alice", "Bob", "Carol"])),
+        &[transaction.clone()],
+        &ScheduleOptions::default(),
+    );
+    // Round-robin gossip bringing the peers just short of consensus, so that the explored
+    // interleavings can reach it.
+    let names = ["Alice", "Bob", "Carol"];
+    for round in 0..9 {
+        let sender = PeerId::new(names[round % names.len()]);
+        let recipient = PeerId::new(names[(round + 1) % names.len()]);
+        schedule
+            .events
+            .push(ScheduleEvent::Gossip(sender.clone(), recipient.clone()));
+        schedule
+            .events
+            .push(ScheduleEvent::Deliver(sender.clone(), recipient.clone()));
+        schedule
+            .events
+            .push(ScheduleEvent::Deliver(recipient, sender));
+    }
+    let bounds = InterleavingBounds {
+        max_depth: 4,
+        max_messages_in_flight: 2,
+    };
+
+    let mut consensus_reached = false;
+    let num_interleavings = unwrap!(check_interleavings_with(
+        schedule,
+        &bounds,
+        || Environment::new(seed),
+        |network: &Network| {
+            consensus_reached |= network
+                .peers
+                .values()
+                .any(|peer| peer.blocks_payloads().contains(&&transaction));
+            Ok(())
+        }
+    ));
+    assert!(num_interleavings > 1);
+    assert!(consensus_reached);
+}
+
+#[test]
+fn check_bounded_interleavings_finds_counterexample() {
+    // Only the interleavings in which a peer handles a response get its graph past 8 events.
+    let property =
+        |network: &Network| match network.peers.values().find(|peer| peer.graph_size() > 8) {
+            Some(peer) => Err(ConsensusError::PropertyViolated(format!(
+                "{:?} has {} events",
+                peer.id(),
+                peer.graph_size()
+            ))),
+            None => Ok(()),
+        };
+
+    let seed = RngChoice::SeededXor([1, 2, 3, 4]);
+    let schedule = Schedule::from_votes(
+        &mut Environment::new(seed),
+        Genesis::new(peer_ids(&["Alice", "Bob", "Carol"])),
+        &[Observation::OpaquePayload(Transaction::new("one"))],
+        &ScheduleOptions::default(),
+    );
+    let bounds = InterleavingBounds {
+        max_depth: 5,
+        max_messages_in_flight: 2,
+    };
+
+    let (counterexample, error) =
+        match check_interleavings_with(schedule, &bounds, || Environment::new(seed), &property) {
+            Ok(num_interleavings) => panic!(
+                "No counterexample among {} interleavings",
+                num_interleavings
+            ),
+            Err(counterexample) => counterexample,
+        };
+
+    let mut env = Environment::new(seed);
+    unwrap!(env.execute_schedule_prefix(counterexample));
+    let replayed = unwrap!(property(&env.network).err());
+    assert_eq!(format!("{:?}", replayed), format!("{:?}", error));
+}
+
+#[test]
+fn check_bounded_interleavings_reports_panics() {
+    let seed = RngChoice::SeededXor([1, 2, 3, 4]);
+    let schedule = Schedule::from_votes(
+        &mut Environment::new(seed),
+        Genesis::new(peer_ids(&["Alice", "Bob", "Carol"])),
+        &[Observation::OpaquePayload(Transaction::new("one"))],
+        &ScheduleOptions::default(),
+    );
+    let property = |network: &Network| {
+        if network.peers.values().any(|peer| peer.graph_size() > 8) {
+            panic!("Too many events");
+        }
+        Ok(())
+    };
+
+    match check_interleavings_with(
+        schedule,
+        &InterleavingBounds::default(),
+        || Environment::new(seed),
+        property,
+    ) {
+        Err((_, ConsensusError::Panicked(message))) => assert_eq!(message, "Too many events"),
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+#[test]
+fn liveness_after_failures() {
+    let mut env = Environment::new(SEED);
+    let mut failures = BTreeMap::new();
+    let _ = failures.insert(200, 2);
+    let options = ScheduleOptions {
+        genesis_size: 7,
+        opaque_to_add: 5,
+        deterministic_failures: failures,
+        liveness_bound_ms: Some(10_000),
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+
+    let result = env.execute_schedule(schedule);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn stuck_observation_detected() {
+    // Every peer votes for the opaque observation, but all the messages get lost, so it never
+    // gets consensused.
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 1,
+        prob_message_drop: 1.0,
+        liveness_bound_ms: Some(4000),
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+
+    match env.execute_schedule(schedule) {
+        Err(ConsensusError::StuckObservations { observations, .. }) => {
+            assert_eq!(observations.len(), 1);
+            assert!(observations[0].is_opaque());
+        }
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    // A single vote isn't enough for an opaque observation to be expected to get consensused.
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        opaque_voters: Sampling::Constant(1),
+        prob_message_drop: 0.0,
+        ..options
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    if let Err(ConsensusError::StuckObservations { observations, .. }) =
+        env.execute_schedule(schedule)
+    {
+        panic!("Unexpected stuck observations {:?}", observations);
+    }
+}
+
+#[test]
+fn performance_report() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 3,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    let report = unwrap!(env.execute_schedule(schedule));
+
+    // The genesis block and the opaque ones, all polled by every peer.
+    assert_eq!(report.blocks.len(), 4);
+    for block in &report.blocks {
+        assert_eq!(block.consensus_times_ms.len(), 4);
+        if block.payload.is_opaque() {
+            assert!(block.first_vote_time_ms.is_some());
+            assert_eq!(block.latencies().count(), 4);
+        } else {
+            assert!(block.first_vote_time_ms.is_none());
+        }
+    }
+    assert!(unwrap!(report.mean_latency()) > 0.0);
+
+    assert!(report.requests > 0);
+    assert!(report.responses <= report.requests);
+    assert!(report.request_bytes > 0 && report.response_bytes > 0);
+    assert_eq!(report.graph_sizes.len(), 4);
+    assert!(report.graph_sizes.values().all(|size| *size > 0));
+    assert!(report
+        .meta_election_rounds
+        .values()
+        .all(|rounds| !rounds.is_empty()));
+}
+
+#[test]
+fn report_in_simulated_time() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 2,
+        gossip_period_ms: 100,
+        delay_distr: DelayDistribution::Poisson(20.0),
+        clock: VirtualClock::new(5),
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    let report = unwrap!(env.execute_schedule(schedule));
+
+    // Times are those of the start of global steps, none of them after the end of the simulation.
+    assert_eq!(env.network.now(), report.end_time_ms);
+    assert!(report.end_time_ms > 0 && report.end_time_ms % 5 == 0);
+    for block in &report.blocks {
+        assert!(block
+            .first_vote_time_ms
+            .iter()
+            .chain(block.consensus_times_ms.values())
+            .all(|time_ms| time_ms % 5 == 0 && *time_ms <= report.end_time_ms));
+    }
+    assert!(unwrap!(report.mean_latency()) > 0.0);
+    assert!(unwrap!(report.blocks_per_second()) > 0.0);
+}
+
 #[test]
 fn grow_network_from_two_nodes() {
     let mut env = Environment::new(SEED);
@@ -648,7 +1193,7 @@ proptest! {
             recv_trans: (0.001..0.5).into(),
             failure: (0.0..1.0).into(),
             vote_duplication: (0.0..0.5).into(),
-            delay_distr: arbitrary_delay(0..10, 0.0..10.0),
+            delay_distr: arbitrary_delay(0..100, 0.0..100.0),
         },
     }) {
         let _ = log::init(true);